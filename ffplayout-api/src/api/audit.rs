@@ -0,0 +1,401 @@
+/// Audit Log
+///
+/// Middleware which records every mutating API call in the audit table,
+/// with user, channel, route, a summary of the parameters, result status and client IP.
+use std::{
+    future::{ready, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::PayloadError,
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        Method,
+    },
+    web, Error, HttpMessage,
+};
+use futures_util::{future::LocalBoxFuture, stream, Stream, StreamExt};
+use serde_json::Value;
+use simplelog::*;
+use sqlx::{Pool, Sqlite};
+
+use crate::db::{
+    handles,
+    models::{AuditEntry, LoginUser},
+};
+
+// Maximal length from the stored parameters
+const PARAMS_MAX_LENGTH: usize = 1024;
+
+// Maximal length from a JSON body, the default limit from web::JsonConfig
+const BODY_MAX_LENGTH: usize = 2_097_152;
+
+// Routes where {id} is the channel id
+const CHANNEL_ROUTES: [&str; 5] = [
+    "/api/channel/",
+    "/api/control/",
+    "/api/file/",
+    "/api/playlist/",
+    "/api/playout/",
+];
+
+// Fields which never get into the audit log
+const HIDDEN_FIELDS: [&str; 3] = ["password", "token", "secret"];
+
+type BodyStream = Pin<Box<dyn Stream<Item = Result<web::Bytes, PayloadError>>>>;
+
+pub struct AuditLog;
+
+impl<S, B> Transform<S, ServiceRequest> for AuditLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AuditLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuditLogMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AuditLogMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuditLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            // browsing files is a POST request, but changes nothing
+            if !is_mutating(req.method()) || req.path().ends_with("/browse/") {
+                return service.call(req).await;
+            }
+
+            let is_json = req
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|c| c.to_str().ok())
+                .is_some_and(|c| c.starts_with("application/json"));
+            let mut body = None;
+
+            if is_json {
+                if req
+                    .headers()
+                    .get(CONTENT_LENGTH)
+                    .and_then(|l| l.to_str().ok())
+                    .and_then(|l| l.parse::<usize>().ok())
+                    .is_some_and(|l| l > BODY_MAX_LENGTH)
+                {
+                    return Err(PayloadError::Overflow.into());
+                }
+
+                let mut payload = req.take_payload();
+                let mut bytes = web::BytesMut::new();
+
+                while let Some(chunk) = payload.next().await {
+                    let chunk = chunk?;
+
+                    // the Json extractor would reject it anyway, don't buffer it
+                    if bytes.len() + chunk.len() > BODY_MAX_LENGTH {
+                        return Err(PayloadError::Overflow.into());
+                    }
+
+                    bytes.extend_from_slice(&chunk);
+                }
+
+                let bytes = bytes.freeze();
+                let body_stream: BodyStream = Box::pin(stream::once(ready(Ok(bytes.clone()))));
+                req.set_payload(Payload::from(body_stream));
+                body = Some(bytes);
+            }
+
+            let query = req.query_string().to_string();
+            let method = req.method().to_string();
+            let path = req.path().to_string();
+            let client_ip = req
+                .connection_info()
+                .realip_remote_addr()
+                .unwrap_or_default()
+                .to_string();
+            let pool = req.app_data::<web::Data<Pool<Sqlite>>>().cloned();
+            let json: Option<Value> = body.and_then(|b| serde_json::from_slice(&b).ok());
+
+            // the preset or schedule is maybe deleted after the call
+            let stored_channel = match (&pool, body_channel_id(&json)) {
+                (Some(pool), None) => stored_channel_id(pool, &path).await,
+                _ => None,
+            };
+
+            let mut entry = AuditEntry {
+                method,
+                params: params_summary(&query, json.clone()),
+                client_ip,
+                ..Default::default()
+            };
+
+            // calls which are refused by the authentication don't reach the routes
+            let result = service.call(req).await;
+
+            match &result {
+                Ok(res) => {
+                    let request = res.request();
+                    let route = request
+                        .match_pattern()
+                        .unwrap_or_else(|| request.path().to_string());
+                    let user = request.extensions().get::<LoginUser>().cloned();
+
+                    entry.user_id = user.as_ref().map(|u| u.id);
+                    entry.username = user.map(|u| u.username).unwrap_or_default();
+                    entry.channel_id = channel_id(&route, request.match_info().get("id"), &json)
+                        .or(stored_channel);
+                    entry.route = route;
+                    entry.status = res.status().as_u16();
+                }
+                Err(e) => {
+                    entry.channel_id = channel_id(&path, path_id(&path), &json).or(stored_channel);
+                    entry.route = path.clone();
+                    entry.status = e.as_response_error().status_code().as_u16();
+                }
+            }
+
+            entry.path = path;
+
+            if let Some(pool) = pool {
+                if let Err(e) = handles::insert_audit_entry(&pool, entry).await {
+                    error!("Write audit log failed: {e}");
+                }
+            }
+
+            result
+        })
+    }
+}
+
+fn is_mutating(method: &Method) -> bool {
+    [Method::POST, Method::PUT, Method::PATCH, Method::DELETE].contains(method)
+}
+
+/// Get channel id from route, or from the `channel_id` field in the request body.
+fn channel_id(route: &str, id: Option<&str>, json: &Option<Value>) -> Option<i32> {
+    if CHANNEL_ROUTES.iter().any(|r| route.starts_with(r)) {
+        return id.and_then(|i| i.parse().ok());
+    }
+
+    body_channel_id(json)
+}
+
+fn body_channel_id(json: &Option<Value>) -> Option<i32> {
+    json.as_ref()
+        .and_then(|j| j.get("channel_id"))
+        .and_then(|c| c.as_i64())
+        .map(|c| c as i32)
+}
+
+/// Get the {id} from a channel route path, when the route was not matched.
+fn path_id(path: &str) -> Option<&str> {
+    CHANNEL_ROUTES
+        .iter()
+        .find_map(|r| path.strip_prefix(r))
+        .and_then(|p| p.split('/').next())
+}
+
+/// Get channel id from the stored preset or schedule, for routes without channel id.
+async fn stored_channel_id(conn: &Pool<Sqlite>, path: &str) -> Option<i32> {
+    if let Some(id) = path.strip_prefix("/api/presets/schedules/") {
+        let schedule = handles::select_text_schedule(conn, &id.parse().ok()?).await;
+
+        return schedule.ok().map(|s| s.channel_id);
+    }
+
+    let id = path.strip_prefix("/api/presets/")?.parse().ok()?;
+
+    handles::select_preset(conn, &id)
+        .await
+        .ok()
+        .map(|p| p.channel_id)
+}
+
+fn hide_fields(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, val) in map.iter_mut() {
+                if HIDDEN_FIELDS.contains(&key.as_str()) {
+                    *val = Value::String("***".to_string());
+                } else {
+                    hide_fields(val);
+                }
+            }
+        }
+        Value::Array(list) => list.iter_mut().for_each(hide_fields),
+        _ => (),
+    }
+}
+
+/// Summarize query and body, secrets are hidden and long bodies get truncated.
+fn params_summary(query: &str, json: Option<Value>) -> String {
+    let mut params = vec![];
+
+    if !query.is_empty() {
+        params.push(query.to_string());
+    }
+
+    if let Some(mut json) = json {
+        hide_fields(&mut json);
+        params.push(json.to_string());
+    }
+
+    let mut summary = params.join(" ");
+
+    if summary.len() > PARAMS_MAX_LENGTH {
+        let mut end = PARAMS_MAX_LENGTH;
+
+        while !summary.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        summary.truncate(end);
+        summary.push_str("...");
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{error::ErrorUnauthorized, http::StatusCode, test, App, HttpResponse};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::migrations::migrate;
+
+    async fn audit_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        migrate(&pool, "").await.unwrap();
+
+        pool
+    }
+
+    async fn audit_entries(conn: &Pool<Sqlite>) -> Vec<(Option<i32>, String, u16)> {
+        sqlx::query_as("SELECT channel_id, route, status FROM audit_log ORDER BY id")
+            .fetch_all(conn)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn large_body_is_not_buffered() {
+        let app = test::init_service(
+            App::new()
+                .wrap(AuditLog)
+                .route("/api/test", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let body = format!("{{\"text\": \"{}\"}}", "a".repeat(BODY_MAX_LENGTH));
+        let req = test::TestRequest::post()
+            .uri("/api/test")
+            .insert_header((CONTENT_TYPE, "application/json"))
+            .set_payload(body)
+            .to_request();
+
+        let res = test::try_call_service(&app, req).await;
+
+        assert_eq!(
+            res.err().map(|e| e.as_response_error().status_code()),
+            Some(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+    }
+
+    #[actix_web::test]
+    async fn unauthenticated_call_is_recorded() {
+        let pool = audit_pool().await;
+        let app = test::init_service(
+            App::new().app_data(web::Data::new(pool.clone())).service(
+                web::scope("/api")
+                    // refuses every request, like the bearer authentication with an invalid token
+                    .wrap_fn(|_, _| {
+                        ready(Err::<ServiceResponse, _>(ErrorUnauthorized(
+                            "Invalid API token",
+                        )))
+                    })
+                    .wrap(AuditLog)
+                    .route("/file/{id}/remove/", web::post().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/file/2/remove/")
+            .set_json(serde_json::json!({"source": "/folder"}))
+            .to_request();
+
+        assert!(test::try_call_service(&app, req).await.is_err());
+        assert_eq!(
+            audit_entries(&pool).await,
+            vec![(Some(2), "/api/file/2/remove/".to_string(), 401)]
+        );
+    }
+
+    #[actix_web::test]
+    async fn deleted_preset_keeps_channel() {
+        let pool = audit_pool().await;
+
+        sqlx::query("INSERT INTO channels (name, preview_url, config_path, extra_extensions, service) VALUES('Channel 1', '', '', '', 'ffplayout.service'), ('Channel 2', '', '', '', 'ffplayout@2.service')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO presets (name, text, x, y, fontsize, line_spacing, fontcolor, box, boxcolor, boxborderw, alpha, channel_id) VALUES('ticker', '', '0', '0', '24', '4', 'white', '0', 'black', '4', '1', 2)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let app = test::init_service(App::new().app_data(web::Data::new(pool.clone())).service(
+            web::scope("/api").wrap(AuditLog).route(
+                "/presets/{id}",
+                web::delete().to(
+                    |pool: web::Data<Pool<Sqlite>>, id: web::Path<i32>| async move {
+                        handles::delete_preset(&pool, &id).await.unwrap();
+
+                        HttpResponse::Ok().finish()
+                    },
+                ),
+            ),
+        ))
+        .await;
+
+        let req = test::TestRequest::delete()
+            .uri("/api/presets/1")
+            .to_request();
+
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+        assert_eq!(
+            audit_entries(&pool).await,
+            vec![(Some(2), "/api/presets/{id}".to_string(), 200)]
+        );
+    }
+}
@@ -1,20 +1,32 @@
-use actix_web::error::ErrorUnauthorized;
+use std::collections::HashMap;
+
+use actix_web::error::{ErrorInternalServerError, ErrorUnauthorized};
 use actix_web::Error;
 use chrono::{TimeDelta, Utc};
 use jsonwebtoken::{self, DecodingKey, EncodingKey, Header, Validation};
+use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use simplelog::*;
+use sqlx::{Pool, Sqlite};
 
+use crate::db::{handles, models::LoginUser};
 use crate::utils::{GlobalSettings, Role};
 
 // Token lifetime
 const JWT_EXPIRATION_DAYS: i64 = 7;
 
+/// Prefix from long lived API tokens, to distinguish them from JWTs
+pub const API_TOKEN_PREFIX: &str = "ffp_";
+
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Claims {
     pub id: i32,
     pub username: String,
     pub role: Role,
-    exp: i64,
+    #[serde(default)]
+    pub jti: String,
+    pub exp: i64,
 }
 
 impl Claims {
@@ -23,6 +35,7 @@ impl Claims {
             id,
             username,
             role,
+            jti: random_string(24),
             exp: (Utc::now() + TimeDelta::try_days(JWT_EXPIRATION_DAYS).unwrap()).timestamp(),
         }
     }
@@ -44,3 +57,83 @@ pub async fn decode_jwt(token: &str) -> Result<Claims, Error> {
         .map(|data| data.claims)
         .map_err(|e| ErrorUnauthorized(e.to_string()))
 }
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// Create a new API token, the plain token is only known by the user.
+pub fn generate_api_token() -> String {
+    format!("{API_TOKEN_PREFIX}{}", random_string(40))
+}
+
+/// API tokens are stored as SHA-256 hash.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Get user with permissions from a JWT or an API token.
+///
+/// User, roles and channel memberships are read on every request,
+/// so changes, revoked tokens and disabled users take effect immediately.
+pub async fn authenticate(conn: &Pool<Sqlite>, token: &str) -> Result<LoginUser, Error> {
+    let (user_id, api_token) = if token.starts_with(API_TOKEN_PREFIX) {
+        let api_token = handles::select_api_token(conn, &hash_token(token))
+            .await
+            .map_err(|_| ErrorUnauthorized("Invalid API token"))?;
+
+        (api_token.user_id, Some(api_token))
+    } else {
+        let claims = decode_jwt(token).await?;
+
+        if handles::select_revoked(conn, &claims.jti)
+            .await
+            .map_err(ErrorInternalServerError)?
+        {
+            return Err(ErrorUnauthorized("Token is revoked"));
+        }
+
+        (claims.id, None)
+    };
+
+    let user = handles::select_user_by_id(conn, user_id)
+        .await
+        .map_err(|_| ErrorUnauthorized("User not exists"))?;
+
+    if user.disabled.unwrap_or_default() {
+        return Err(ErrorUnauthorized("User is disabled"));
+    }
+
+    let role = handles::select_role(conn, &user.role_id.unwrap_or_default())
+        .await
+        .unwrap_or(Role::Guest);
+    let mut channels = HashMap::new();
+
+    match handles::select_user_channels(conn, user.id).await {
+        Ok(members) => {
+            for member in members {
+                channels.insert(member.channel_id, member.role);
+            }
+        }
+        Err(e) => error!("{e}"),
+    }
+
+    let mut login_user = LoginUser::new(user.id, user.username, role, channels);
+
+    if let Some(api_token) = api_token {
+        let all_channels: Vec<i32> = handles::select_all_channels(conn)
+            .await
+            .map_err(ErrorInternalServerError)?
+            .iter()
+            .map(|c| c.id)
+            .collect();
+
+        login_user.restrict(&api_token, &all_channels);
+    }
+
+    Ok(login_user)
+}
@@ -5,74 +5,21 @@ use argon2::{
     Argon2, PasswordHasher,
 };
 
+use chrono::{TimeDelta, Utc};
 use rand::{distributions::Alphanumeric, Rng};
 use simplelog::*;
-use sqlx::{migrate::MigrateDatabase, sqlite::SqliteQueryResult, Pool, Sqlite};
+use sqlx::{migrate::MigrateDatabase, sqlite::SqliteQueryResult, Pool, QueryBuilder, Sqlite};
 use tokio::task;
 
 use crate::db::{
     db_pool,
-    models::{Channel, TextPreset, User},
+    migrations::migrate,
+    models::{
+        ApiToken, AuditEntry, AuditFilter, Channel, ChannelConfig, ChannelMember, TextPreset,
+        TextSchedule, User,
+    },
 };
-use crate::utils::{db_path, local_utc_offset, GlobalSettings, Role};
-
-async fn create_schema(conn: &Pool<Sqlite>) -> Result<SqliteQueryResult, sqlx::Error> {
-    let query = "PRAGMA foreign_keys = ON;
-    CREATE TABLE IF NOT EXISTS global
-        (
-            id                       INTEGER PRIMARY KEY AUTOINCREMENT,
-            secret                   TEXT NOT NULL,
-            UNIQUE(secret)
-        );
-    CREATE TABLE IF NOT EXISTS roles
-        (
-            id                       INTEGER PRIMARY KEY AUTOINCREMENT,
-            name                     TEXT NOT NULL,
-            UNIQUE(name)
-        );
-    CREATE TABLE IF NOT EXISTS channels
-        (
-            id                       INTEGER PRIMARY KEY AUTOINCREMENT,
-            name                     TEXT NOT NULL,
-            preview_url              TEXT NOT NULL,
-            config_path              TEXT NOT NULL,
-            extra_extensions         TEXT NOT NULL,
-            service                  TEXT NOT NULL,
-            UNIQUE(name, service)
-        );
-    CREATE TABLE IF NOT EXISTS presets
-        (
-            id                       INTEGER PRIMARY KEY AUTOINCREMENT,
-            name                     TEXT NOT NULL,
-            text                     TEXT NOT NULL,
-            x                        TEXT NOT NULL,
-            y                        TEXT NOT NULL,
-            fontsize                 TEXT NOT NULL,
-            line_spacing             TEXT NOT NULL,
-            fontcolor                TEXT NOT NULL,
-            box                      TEXT NOT NULL,
-            boxcolor                 TEXT NOT NULL,
-            boxborderw               TEXT NOT NULL,
-            alpha                    TEXT NOT NULL,
-            channel_id               INTEGER NOT NULL DEFAULT 1,
-            FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE SET NULL ON DELETE SET NULL,
-            UNIQUE(name)
-        );
-    CREATE TABLE IF NOT EXISTS user
-        (
-            id                       INTEGER PRIMARY KEY AUTOINCREMENT,
-            mail                     TEXT NOT NULL,
-            username                 TEXT NOT NULL,
-            password                 TEXT NOT NULL,
-            role_id                  INTEGER NOT NULL DEFAULT 2,
-            channel_id               INTEGER NOT NULL DEFAULT 1,
-            FOREIGN KEY (role_id)    REFERENCES roles (id) ON UPDATE SET NULL ON DELETE SET NULL,
-            FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE SET NULL ON DELETE SET NULL,
-            UNIQUE(mail, username)
-        );";
-
-    sqlx::query(query).execute(conn).await
-}
+use crate::utils::{db_path, utc_offset, ChannelRole, GlobalSettings, Role};
 
 pub async fn db_init(domain: Option<String>) -> Result<&'static str, Box<dyn std::error::Error>> {
     let db_path = db_path()?;
@@ -80,13 +27,12 @@ pub async fn db_init(domain: Option<String>) -> Result<&'static str, Box<dyn std
     if !Sqlite::database_exists(db_path).await.unwrap_or(false) {
         Sqlite::create_database(db_path).await.unwrap();
 
-        let pool = db_pool().await?;
-
-        match create_schema(&pool).await {
-            Ok(_) => info!("Database created Successfully"),
-            Err(e) => panic!("{e}"),
-        }
+        info!("Database created Successfully");
     }
+
+    let pool = db_pool().await?;
+    migrate(&pool, db_path).await?;
+
     let secret: String = rand::thread_rng()
         .sample_iter(&Alphanumeric)
         .take(80)
@@ -122,8 +68,6 @@ pub async fn db_init(domain: Option<String>) -> Result<&'static str, Box<dyn std
         ('Scrolling Text', 'We have a very important announcement to make.', 'ifnot(ld(1),st(1,t));if(lt(t,ld(1)+1),w+4,w-w/12*mod(t-ld(1),12*(w+tw)/w))', '(h-line_h)*0.9',
             '24', '4', '#ffffff', '1', '#000000@0x80', '4', '1.0', '1');";
 
-    let pool = db_pool().await?;
-
     sqlx::query(query)
         .bind(secret)
         .bind(url)
@@ -144,7 +88,7 @@ pub async fn select_channel(conn: &Pool<Sqlite>, id: &i32) -> Result<Channel, sq
     let query = "SELECT * FROM channels WHERE id = $1";
     let mut result: Channel = sqlx::query_as(query).bind(id).fetch_one(conn).await?;
 
-    result.utc_offset = local_utc_offset();
+    result.utc_offset = utc_offset(None);
 
     Ok(result)
 }
@@ -154,7 +98,7 @@ pub async fn select_all_channels(conn: &Pool<Sqlite>) -> Result<Vec<Channel>, sq
     let mut results: Vec<Channel> = sqlx::query_as(query).fetch_all(conn).await?;
 
     for result in results.iter_mut() {
-        result.utc_offset = local_utc_offset();
+        result.utc_offset = utc_offset(None);
     }
 
     Ok(results)
@@ -177,6 +121,20 @@ pub async fn update_channel(
         .await
 }
 
+pub async fn update_channel_autostart(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    autostart: bool,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "UPDATE channels SET autostart = $2 WHERE id = $1";
+
+    sqlx::query(query)
+        .bind(id)
+        .bind(autostart)
+        .execute(conn)
+        .await
+}
+
 pub async fn insert_channel(conn: &Pool<Sqlite>, channel: Channel) -> Result<Channel, sqlx::Error> {
     let query = "INSERT INTO channels (name, preview_url, config_path, extra_extensions, service) VALUES($1, $2, $3, $4, $5)";
     let result = sqlx::query(query)
@@ -198,11 +156,63 @@ pub async fn delete_channel(
     conn: &Pool<Sqlite>,
     id: &i32,
 ) -> Result<SqliteQueryResult, sqlx::Error> {
-    let query = "DELETE FROM channels WHERE id = $1";
+    let query = "DELETE FROM user_channels WHERE channel_id = $1;
+        DELETE FROM configs WHERE channel_id = $1;
+        DELETE FROM channels WHERE id = $1;";
 
     sqlx::query(query).bind(id).execute(conn).await
 }
 
+pub async fn select_config(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+) -> Result<ChannelConfig, sqlx::Error> {
+    let query = "SELECT * FROM configs WHERE channel_id = $1 ORDER BY id DESC LIMIT 1";
+
+    sqlx::query_as(query).bind(channel_id).fetch_one(conn).await
+}
+
+pub async fn select_config_version(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    id: i32,
+) -> Result<ChannelConfig, sqlx::Error> {
+    let query = "SELECT * FROM configs WHERE channel_id = $1 AND id = $2";
+
+    sqlx::query_as(query)
+        .bind(channel_id)
+        .bind(id)
+        .fetch_one(conn)
+        .await
+}
+
+/// Get all config versions from channel, without content and newest first.
+pub async fn select_config_history(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+) -> Result<Vec<ChannelConfig>, sqlx::Error> {
+    let query = "SELECT id, channel_id, username, created_at FROM configs
+        WHERE channel_id = $1 ORDER BY id DESC";
+
+    sqlx::query_as(query).bind(channel_id).fetch_all(conn).await
+}
+
+pub async fn insert_config(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    config: &str,
+    username: &str,
+) -> Result<ChannelConfig, sqlx::Error> {
+    let query = "INSERT INTO configs (channel_id, config, username) VALUES($1, $2, $3) RETURNING *";
+
+    sqlx::query_as(query)
+        .bind(channel_id)
+        .bind(config)
+        .bind(username)
+        .fetch_one(conn)
+        .await
+}
+
 pub async fn select_last_channel(conn: &Pool<Sqlite>) -> Result<i32, sqlx::Error> {
     let query = "SELECT id FROM channels ORDER BY id DESC LIMIT 1;";
 
@@ -217,7 +227,8 @@ pub async fn select_role(conn: &Pool<Sqlite>, id: &i32) -> Result<Role, sqlx::Er
 }
 
 pub async fn select_login(conn: &Pool<Sqlite>, user: &str) -> Result<User, sqlx::Error> {
-    let query = "SELECT id, mail, username, password, role_id FROM user WHERE username = $1";
+    let query =
+        "SELECT id, mail, username, password, role_id, disabled FROM user WHERE username = $1";
 
     sqlx::query_as(query).bind(user).fetch_one(conn).await
 }
@@ -229,13 +240,13 @@ pub async fn select_user(conn: &Pool<Sqlite>, user: &str) -> Result<User, sqlx::
 }
 
 pub async fn select_user_by_id(conn: &Pool<Sqlite>, id: i32) -> Result<User, sqlx::Error> {
-    let query = "SELECT id, mail, username, role_id FROM user WHERE id = $1";
+    let query = "SELECT id, mail, username, role_id, disabled FROM user WHERE id = $1";
 
     sqlx::query_as(query).bind(id).fetch_one(conn).await
 }
 
 pub async fn select_users(conn: &Pool<Sqlite>) -> Result<Vec<User>, sqlx::Error> {
-    let query = "SELECT id, username FROM user";
+    let query = "SELECT id, username, disabled FROM user";
 
     sqlx::query_as(query).fetch_all(conn).await
 }
@@ -257,13 +268,30 @@ pub async fn insert_user(
 
     let query = "INSERT INTO user (mail, username, password, role_id) VALUES($1, $2, $3, $4)";
 
-    sqlx::query(query)
+    let result = sqlx::query(query)
         .bind(user.mail)
         .bind(user.username)
         .bind(password_hash)
         .bind(user.role_id)
         .execute(conn)
-        .await
+        .await?;
+
+    // global admins have access to all channels, all others need a membership
+    if user.role_id != Some(1) {
+        let role = user
+            .channel_role
+            .unwrap_or_else(|| ChannelRole::from_role_id(user.role_id));
+
+        insert_member(
+            conn,
+            result.last_insert_rowid() as i32,
+            user.channel_id.unwrap_or(1),
+            role,
+        )
+        .await?;
+    }
+
+    Ok(result)
 }
 
 pub async fn update_user(
@@ -280,11 +308,155 @@ pub async fn delete_user(
     conn: &Pool<Sqlite>,
     name: &str,
 ) -> Result<SqliteQueryResult, sqlx::Error> {
-    let query = "DELETE FROM user WHERE username = $1;";
+    let query =
+        "DELETE FROM user_channels WHERE user_id IN (SELECT id FROM user WHERE username = $1);
+        DELETE FROM api_tokens WHERE user_id IN (SELECT id FROM user WHERE username = $1);
+        DELETE FROM user WHERE username = $1;";
 
     sqlx::query(query).bind(name).execute(conn).await
 }
 
+pub async fn select_api_tokens(
+    conn: &Pool<Sqlite>,
+    user_id: i32,
+) -> Result<Vec<ApiToken>, sqlx::Error> {
+    let query = "SELECT * FROM api_tokens WHERE user_id = $1";
+
+    sqlx::query_as(query).bind(user_id).fetch_all(conn).await
+}
+
+/// Get the API token from its hash.
+///
+/// `last_used` is updated at most once per minute, so that busy tokens don't write on every request.
+pub async fn select_api_token(conn: &Pool<Sqlite>, hash: &str) -> Result<ApiToken, sqlx::Error> {
+    let query = "SELECT * FROM api_tokens WHERE token_hash = $1";
+    let token: ApiToken = sqlx::query_as(query).bind(hash).fetch_one(conn).await?;
+
+    // same format as CURRENT_TIMESTAMP, so the values can be compared as strings
+    let threshold = (Utc::now() - TimeDelta::minutes(1))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+
+    if token.last_used.as_ref().is_some_and(|t| *t >= threshold) {
+        return Ok(token);
+    }
+
+    let query = "UPDATE api_tokens SET last_used = CURRENT_TIMESTAMP WHERE token_hash = $1
+        RETURNING *";
+
+    sqlx::query_as(query).bind(hash).fetch_one(conn).await
+}
+
+pub async fn insert_api_token(
+    conn: &Pool<Sqlite>,
+    token: ApiToken,
+) -> Result<ApiToken, sqlx::Error> {
+    let query = "INSERT INTO api_tokens (user_id, name, token_hash, role, channels)
+        VALUES($1, $2, $3, $4, $5) RETURNING *";
+
+    sqlx::query_as(query)
+        .bind(token.user_id)
+        .bind(token.name)
+        .bind(token.token_hash)
+        .bind(token.role)
+        .bind(token.channels)
+        .fetch_one(conn)
+        .await
+}
+
+pub async fn delete_api_token(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    user_id: i32,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "DELETE FROM api_tokens WHERE id = $1 AND user_id = $2";
+
+    sqlx::query(query)
+        .bind(id)
+        .bind(user_id)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_revoked(conn: &Pool<Sqlite>, jti: &str) -> Result<bool, sqlx::Error> {
+    let query = "SELECT COUNT(*) FROM revoked_tokens WHERE jti = $1";
+    let count: i32 = sqlx::query_scalar(query).bind(jti).fetch_one(conn).await?;
+
+    Ok(count > 0)
+}
+
+/// Add JWT to the revocation list, expired entries are not needed anymore.
+pub async fn insert_revoked(
+    conn: &Pool<Sqlite>,
+    jti: &str,
+    expires: i64,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "DELETE FROM revoked_tokens WHERE expires < strftime('%s', 'now');
+        INSERT OR IGNORE INTO revoked_tokens (jti, expires) VALUES($1, $2);";
+
+    sqlx::query(query)
+        .bind(jti)
+        .bind(expires)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_user_channels(
+    conn: &Pool<Sqlite>,
+    user_id: i32,
+) -> Result<Vec<ChannelMember>, sqlx::Error> {
+    let query = "SELECT user_id, channel_id, role FROM user_channels WHERE user_id = $1";
+
+    sqlx::query_as(query).bind(user_id).fetch_all(conn).await
+}
+
+pub async fn select_members(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+) -> Result<Vec<ChannelMember>, sqlx::Error> {
+    let query = "SELECT m.user_id, u.username, m.channel_id, m.role FROM user_channels m
+        INNER JOIN user u ON u.id = m.user_id WHERE m.channel_id = $1";
+
+    sqlx::query_as(query).bind(channel_id).fetch_all(conn).await
+}
+
+pub async fn insert_member(
+    conn: &Pool<Sqlite>,
+    user_id: i32,
+    channel_id: i32,
+    role: ChannelRole,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "INSERT INTO user_channels (user_id, channel_id, role) VALUES($1, $2, $3)
+        ON CONFLICT(user_id, channel_id) DO UPDATE SET role = excluded.role";
+
+    sqlx::query(query)
+        .bind(user_id)
+        .bind(channel_id)
+        .bind(role)
+        .execute(conn)
+        .await
+}
+
+pub async fn delete_member(
+    conn: &Pool<Sqlite>,
+    user_id: i32,
+    channel_id: i32,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "DELETE FROM user_channels WHERE user_id = $1 AND channel_id = $2";
+
+    sqlx::query(query)
+        .bind(user_id)
+        .bind(channel_id)
+        .execute(conn)
+        .await
+}
+
+pub async fn select_preset(conn: &Pool<Sqlite>, id: &i32) -> Result<TextPreset, sqlx::Error> {
+    let query = "SELECT * FROM presets WHERE id = $1";
+
+    sqlx::query_as(query).bind(id).fetch_one(conn).await
+}
+
 pub async fn select_presets(conn: &Pool<Sqlite>, id: i32) -> Result<Vec<TextPreset>, sqlx::Error> {
     let query = "SELECT * FROM presets WHERE channel_id = $1";
 
@@ -298,7 +470,7 @@ pub async fn update_preset(
 ) -> Result<SqliteQueryResult, sqlx::Error> {
     let query =
         "UPDATE presets SET name = $1, text = $2, x = $3, y = $4, fontsize = $5, line_spacing = $6,
-        fontcolor = $7, alpha = $8, box = $9, boxcolor = $10, boxborderw = $11 WHERE id = $12";
+        fontcolor = $7, alpha = $8, box = $9, boxcolor = $10, boxborderw = $11, layer = $12 WHERE id = $13";
 
     sqlx::query(query)
         .bind(preset.name)
@@ -312,6 +484,7 @@ pub async fn update_preset(
         .bind(preset.r#box)
         .bind(preset.boxcolor)
         .bind(preset.boxborderw)
+        .bind(preset.layer)
         .bind(id)
         .execute(conn)
         .await
@@ -322,8 +495,8 @@ pub async fn insert_preset(
     preset: TextPreset,
 ) -> Result<SqliteQueryResult, sqlx::Error> {
     let query =
-        "INSERT INTO presets (channel_id, name, text, x, y, fontsize, line_spacing, fontcolor, alpha, box, boxcolor, boxborderw)
-            VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)";
+        "INSERT INTO presets (channel_id, name, text, x, y, fontsize, line_spacing, fontcolor, alpha, box, boxcolor, boxborderw, layer)
+            VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)";
 
     sqlx::query(query)
         .bind(preset.channel_id)
@@ -338,6 +511,7 @@ pub async fn insert_preset(
         .bind(preset.r#box)
         .bind(preset.boxcolor)
         .bind(preset.boxborderw)
+        .bind(preset.layer)
         .execute(conn)
         .await
 }
@@ -350,3 +524,235 @@ pub async fn delete_preset(
 
     sqlx::query(query).bind(id).execute(conn).await
 }
+
+pub async fn select_text_schedule(
+    conn: &Pool<Sqlite>,
+    id: &i32,
+) -> Result<TextSchedule, sqlx::Error> {
+    let query = "SELECT * FROM text_schedules WHERE id = $1";
+
+    sqlx::query_as(query).bind(id).fetch_one(conn).await
+}
+
+pub async fn select_text_schedules(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+) -> Result<Vec<TextSchedule>, sqlx::Error> {
+    let query = "SELECT * FROM text_schedules WHERE channel_id = $1";
+
+    sqlx::query_as(query).bind(channel_id).fetch_all(conn).await
+}
+
+pub async fn select_enabled_text_schedules(
+    conn: &Pool<Sqlite>,
+) -> Result<Vec<TextSchedule>, sqlx::Error> {
+    let query = "SELECT * FROM text_schedules WHERE enabled = 1 ORDER BY channel_id, layer, id";
+
+    sqlx::query_as(query).fetch_all(conn).await
+}
+
+pub async fn insert_text_schedule(
+    conn: &Pool<Sqlite>,
+    schedule: TextSchedule,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "INSERT INTO text_schedules (channel_id, name, presets, start_at, end_at, days, from_time, to_time, dwell, crawl, crawl_speed, enabled, layer)
+        VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)";
+
+    sqlx::query(query)
+        .bind(schedule.channel_id)
+        .bind(schedule.name)
+        .bind(schedule.presets)
+        .bind(schedule.start_at)
+        .bind(schedule.end_at)
+        .bind(schedule.days)
+        .bind(schedule.from_time)
+        .bind(schedule.to_time)
+        .bind(schedule.dwell)
+        .bind(schedule.crawl)
+        .bind(schedule.crawl_speed)
+        .bind(schedule.enabled)
+        .bind(schedule.layer)
+        .execute(conn)
+        .await
+}
+
+pub async fn update_text_schedule(
+    conn: &Pool<Sqlite>,
+    id: &i32,
+    schedule: TextSchedule,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "UPDATE text_schedules SET channel_id = $1, name = $2, presets = $3, start_at = $4, end_at = $5,
+        days = $6, from_time = $7, to_time = $8, dwell = $9, crawl = $10, crawl_speed = $11, enabled = $12, layer = $13 WHERE id = $14";
+
+    sqlx::query(query)
+        .bind(schedule.channel_id)
+        .bind(schedule.name)
+        .bind(schedule.presets)
+        .bind(schedule.start_at)
+        .bind(schedule.end_at)
+        .bind(schedule.days)
+        .bind(schedule.from_time)
+        .bind(schedule.to_time)
+        .bind(schedule.dwell)
+        .bind(schedule.crawl)
+        .bind(schedule.crawl_speed)
+        .bind(schedule.enabled)
+        .bind(schedule.layer)
+        .bind(id)
+        .execute(conn)
+        .await
+}
+
+pub async fn delete_text_schedule(
+    conn: &Pool<Sqlite>,
+    id: &i32,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "DELETE FROM text_schedules WHERE id = $1";
+
+    sqlx::query(query).bind(id).execute(conn).await
+}
+
+pub async fn insert_audit_entry(
+    conn: &Pool<Sqlite>,
+    entry: AuditEntry,
+) -> Result<SqliteQueryResult, sqlx::Error> {
+    let query = "INSERT INTO audit_log (user_id, username, channel_id, method, route, path, params, status, client_ip)
+        VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9)";
+
+    sqlx::query(query)
+        .bind(entry.user_id)
+        .bind(entry.username)
+        .bind(entry.channel_id)
+        .bind(entry.method)
+        .bind(entry.route)
+        .bind(entry.path)
+        .bind(entry.params)
+        .bind(entry.status)
+        .bind(entry.client_ip)
+        .execute(conn)
+        .await
+}
+
+fn push_audit_filter(builder: &mut QueryBuilder<Sqlite>, filter: &AuditFilter) {
+    builder.push(" WHERE 1 = 1");
+
+    if let Some(user) = &filter.user {
+        builder.push(" AND username = ").push_bind(user.clone());
+    }
+
+    if let Some(channel) = filter.channel {
+        builder.push(" AND channel_id = ").push_bind(channel);
+    }
+
+    if let Some(route) = &filter.route {
+        builder
+            .push(" AND route LIKE ")
+            .push_bind(format!("%{route}%"));
+    }
+
+    if let Some(method) = &filter.method {
+        builder
+            .push(" AND method = ")
+            .push_bind(method.to_uppercase());
+    }
+
+    if let Some(status) = filter.status {
+        builder.push(" AND status = ").push_bind(status);
+    }
+
+    if let Some(from) = &filter.from {
+        builder.push(" AND created_at >= ").push_bind(from.clone());
+    }
+
+    if let Some(to) = &filter.to {
+        builder.push(" AND created_at <= ").push_bind(to.clone());
+    }
+}
+
+/// Get filtered audit entries, newest first, and the number of all matching entries.
+pub async fn select_audit_entries(
+    conn: &Pool<Sqlite>,
+    filter: &AuditFilter,
+) -> Result<(Vec<AuditEntry>, i64), sqlx::Error> {
+    let mut count_query = QueryBuilder::new("SELECT COUNT(*) FROM audit_log");
+    push_audit_filter(&mut count_query, filter);
+    let total: i64 = count_query.build_query_scalar().fetch_one(conn).await?;
+
+    let mut query = QueryBuilder::new("SELECT * FROM audit_log");
+    push_audit_filter(&mut query, filter);
+    query
+        .push(" ORDER BY id DESC LIMIT ")
+        .push_bind(filter.per_page)
+        .push(" OFFSET ")
+        .push_bind((filter.page - 1) * filter.per_page);
+
+    let entries = query.build_query_as().fetch_all(conn).await?;
+
+    Ok((entries, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn api_token_last_used_is_throttled() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        migrate(&pool, "").await.unwrap();
+
+        sqlx::query("INSERT INTO channels (name, preview_url, config_path, extra_extensions, service) VALUES('Channel 1', '', '', '', 'ffplayout.service')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO roles (name) VALUES('admin')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO user (mail, username, password, role_id) VALUES('user@mail.org', 'user', '', 1)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO api_tokens (user_id, name, token_hash) VALUES(1, 'monitoring', 'hash')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let first = select_api_token(&pool, "hash").await.unwrap();
+
+        assert!(first.last_used.is_some());
+
+        // a recent use is not written again
+        sqlx::query("UPDATE api_tokens SET last_used = datetime('now', '-30 seconds')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let recent: Option<String> = sqlx::query_scalar("SELECT last_used FROM api_tokens")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            select_api_token(&pool, "hash").await.unwrap().last_used,
+            recent
+        );
+
+        sqlx::query("UPDATE api_tokens SET last_used = datetime('now', '-2 minutes')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(select_api_token(&pool, "hash").await.unwrap().last_used > recent);
+    }
+}
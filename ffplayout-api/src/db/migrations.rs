@@ -0,0 +1,415 @@
+/// Database Migrations
+///
+/// Every schema change is a migration with an increasing version. Applied versions are stored
+/// in the `schema_version` table, so only new migrations run, on startup or with `ffpapi --migrate`.
+/// Before an existing database gets changed, a backup of the sqlite file is created.
+use chrono::Local;
+use simplelog::*;
+use sqlx::{Pool, Sqlite};
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// All migrations, ordered by version. Never change a released migration, add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: "
+        CREATE TABLE IF NOT EXISTS global
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                secret                   TEXT NOT NULL,
+                UNIQUE(secret)
+            );
+        CREATE TABLE IF NOT EXISTS roles
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                name                     TEXT NOT NULL,
+                UNIQUE(name)
+            );
+        CREATE TABLE IF NOT EXISTS channels
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                name                     TEXT NOT NULL,
+                preview_url              TEXT NOT NULL,
+                config_path              TEXT NOT NULL,
+                extra_extensions         TEXT NOT NULL,
+                service                  TEXT NOT NULL,
+                UNIQUE(name, service)
+            );
+        CREATE TABLE IF NOT EXISTS presets
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                name                     TEXT NOT NULL,
+                text                     TEXT NOT NULL,
+                x                        TEXT NOT NULL,
+                y                        TEXT NOT NULL,
+                fontsize                 TEXT NOT NULL,
+                line_spacing             TEXT NOT NULL,
+                fontcolor                TEXT NOT NULL,
+                box                      TEXT NOT NULL,
+                boxcolor                 TEXT NOT NULL,
+                boxborderw               TEXT NOT NULL,
+                alpha                    TEXT NOT NULL,
+                channel_id               INTEGER NOT NULL DEFAULT 1,
+                FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE SET NULL ON DELETE SET NULL,
+                UNIQUE(name)
+            );
+        CREATE TABLE IF NOT EXISTS user
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                mail                     TEXT NOT NULL,
+                username                 TEXT NOT NULL,
+                password                 TEXT NOT NULL,
+                role_id                  INTEGER NOT NULL DEFAULT 2,
+                channel_id               INTEGER NOT NULL DEFAULT 1,
+                FOREIGN KEY (role_id)    REFERENCES roles (id) ON UPDATE SET NULL ON DELETE SET NULL,
+                FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE SET NULL ON DELETE SET NULL,
+                UNIQUE(mail, username)
+            );",
+    },
+    Migration {
+        version: 2,
+        description: "channel memberships",
+        // users get access to the channel from their channel_id, normal users as operator, guests as viewer
+        sql: "
+        CREATE TABLE IF NOT EXISTS user_channels
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id                  INTEGER NOT NULL,
+                channel_id               INTEGER NOT NULL,
+                role                     TEXT NOT NULL DEFAULT 'viewer',
+                FOREIGN KEY (user_id)    REFERENCES user (id) ON UPDATE CASCADE ON DELETE CASCADE,
+                FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE,
+                UNIQUE(user_id, channel_id)
+            );
+        INSERT OR IGNORE INTO user_channels (user_id, channel_id, role)
+            SELECT id, channel_id, CASE role_id WHEN 3 THEN 'viewer' ELSE 'operator' END
+            FROM user WHERE role_id != 1;",
+    },
+    Migration {
+        version: 3,
+        description: "api tokens and disabled users",
+        sql: "
+        CREATE TABLE IF NOT EXISTS api_tokens
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id                  INTEGER NOT NULL,
+                name                     TEXT NOT NULL,
+                token_hash               TEXT NOT NULL,
+                role                     TEXT NOT NULL DEFAULT 'viewer',
+                channels                 TEXT NOT NULL DEFAULT '',
+                created_at               TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                last_used                TEXT,
+                FOREIGN KEY (user_id)    REFERENCES user (id) ON UPDATE CASCADE ON DELETE CASCADE,
+                UNIQUE(token_hash),
+                UNIQUE(user_id, name)
+            );
+        CREATE TABLE IF NOT EXISTS revoked_tokens
+            (
+                jti                      TEXT PRIMARY KEY,
+                expires                  INTEGER NOT NULL
+            );
+        ALTER TABLE user ADD COLUMN disabled INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 4,
+        description: "audit log",
+        sql: "
+        CREATE TABLE IF NOT EXISTS audit_log
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id                  INTEGER,
+                username                 TEXT NOT NULL DEFAULT '',
+                channel_id               INTEGER,
+                method                   TEXT NOT NULL,
+                route                    TEXT NOT NULL,
+                path                     TEXT NOT NULL,
+                params                   TEXT NOT NULL DEFAULT '',
+                status                   INTEGER NOT NULL,
+                client_ip                TEXT NOT NULL DEFAULT '',
+                created_at               TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+        CREATE INDEX IF NOT EXISTS audit_log_created ON audit_log (created_at);",
+    },
+    Migration {
+        version: 5,
+        description: "playout config in database",
+        // configs are imported from the channel config_path, when they are read the first time
+        sql: "
+        CREATE TABLE IF NOT EXISTS configs
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                channel_id               INTEGER NOT NULL,
+                config                   TEXT NOT NULL,
+                username                 TEXT NOT NULL DEFAULT '',
+                created_at               TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE
+            );
+        CREATE INDEX IF NOT EXISTS configs_channel ON configs (channel_id, id);",
+    },
+    Migration {
+        version: 6,
+        description: "channel autostart",
+        // engines of channels with autostart are started from the ffpapi supervisor
+        sql: "ALTER TABLE channels ADD COLUMN autostart INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 7,
+        description: "text schedules",
+        // presets and days are comma separated id lists, like the channels from api tokens
+        sql: "
+        CREATE TABLE IF NOT EXISTS text_schedules
+            (
+                id                       INTEGER PRIMARY KEY AUTOINCREMENT,
+                channel_id               INTEGER NOT NULL,
+                name                     TEXT NOT NULL,
+                presets                  TEXT NOT NULL,
+                start_at                 TEXT NOT NULL DEFAULT '',
+                end_at                   TEXT NOT NULL DEFAULT '',
+                days                     TEXT NOT NULL DEFAULT '',
+                from_time                TEXT NOT NULL DEFAULT '',
+                to_time                  TEXT NOT NULL DEFAULT '',
+                dwell                    INTEGER NOT NULL DEFAULT 10,
+                crawl                    INTEGER NOT NULL DEFAULT 0,
+                crawl_speed              INTEGER NOT NULL DEFAULT 100,
+                enabled                  INTEGER NOT NULL DEFAULT 1,
+                FOREIGN KEY (channel_id) REFERENCES channels (id) ON UPDATE CASCADE ON DELETE CASCADE,
+                UNIQUE(channel_id, name)
+            );",
+    },
+    Migration {
+        version: 8,
+        description: "text layers",
+        // empty layer means the default text layer from the engine
+        sql: "
+        ALTER TABLE presets ADD COLUMN layer TEXT NOT NULL DEFAULT '';
+        ALTER TABLE text_schedules ADD COLUMN layer TEXT NOT NULL DEFAULT '';",
+    },
+];
+
+const VERSION_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS schema_version
+        (
+            version                  INTEGER PRIMARY KEY,
+            description              TEXT NOT NULL,
+            applied_at               TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );";
+
+async fn table_exists(conn: &Pool<Sqlite>, table: &str) -> Result<bool, sqlx::Error> {
+    let query = "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = $1";
+    let count: i32 = sqlx::query_scalar(query)
+        .bind(table)
+        .fetch_one(conn)
+        .await?;
+
+    Ok(count > 0)
+}
+
+async fn column_exists(
+    conn: &Pool<Sqlite>,
+    table: &str,
+    column: &str,
+) -> Result<bool, sqlx::Error> {
+    let query = "SELECT COUNT(*) FROM pragma_table_info($1) WHERE name = $2";
+    let count: i32 = sqlx::query_scalar(query)
+        .bind(table)
+        .bind(column)
+        .fetch_one(conn)
+        .await?;
+
+    Ok(count > 0)
+}
+
+/// Guess the version from databases, which are created before versioned migrations existed.
+async fn legacy_version(conn: &Pool<Sqlite>) -> Result<i64, sqlx::Error> {
+    let mut version = 0;
+
+    if table_exists(conn, "user").await? {
+        version = 1;
+    }
+
+    if version == 1 && table_exists(conn, "user_channels").await? {
+        version = 2;
+    }
+
+    if version == 2
+        && table_exists(conn, "api_tokens").await?
+        && column_exists(conn, "user", "disabled").await?
+    {
+        version = 3;
+    }
+
+    if version == 3 && table_exists(conn, "audit_log").await? {
+        version = 4;
+    }
+
+    Ok(version)
+}
+
+/// Get the current schema version, 0 means the database is empty.
+pub async fn schema_version(conn: &Pool<Sqlite>) -> Result<i64, sqlx::Error> {
+    if !table_exists(conn, "schema_version").await? {
+        return legacy_version(conn).await;
+    }
+
+    let query = "SELECT COALESCE(MAX(version), 0) FROM schema_version";
+
+    sqlx::query_scalar(query).fetch_one(conn).await
+}
+
+/// Write a consistent copy of the database next to the original file.
+async fn backup(conn: &Pool<Sqlite>, db_path: &str, version: i64) -> Result<String, sqlx::Error> {
+    let backup_path = format!(
+        "{db_path}.v{version}-{}.bak",
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+
+    sqlx::query("VACUUM INTO $1")
+        .bind(&backup_path)
+        .execute(conn)
+        .await?;
+
+    Ok(backup_path)
+}
+
+/// Apply all pending migrations, every migration runs in its own transaction.
+///
+/// Returns the schema version after migrating.
+pub async fn migrate(conn: &Pool<Sqlite>, db_path: &str) -> Result<i64, sqlx::Error> {
+    let current = schema_version(conn).await?;
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+
+    if !pending.is_empty() && current > 0 {
+        let backup_path = backup(conn, db_path, current).await?;
+
+        info!("Database backup saved to: <b><magenta>{backup_path}</></b>");
+    }
+
+    if !table_exists(conn, "schema_version").await? {
+        sqlx::query(VERSION_TABLE).execute(conn).await?;
+
+        // mark the already existing schema of older databases as applied
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= current) {
+            sqlx::query("INSERT INTO schema_version (version, description) VALUES($1, $2)")
+                .bind(migration.version)
+                .bind(migration.description)
+                .execute(conn)
+                .await?;
+        }
+    }
+
+    if pending.is_empty() {
+        return Ok(current);
+    }
+
+    for migration in pending {
+        let mut tx = conn.begin().await?;
+
+        sqlx::query(migration.sql).execute(&mut *tx).await?;
+        sqlx::query("INSERT INTO schema_version (version, description) VALUES($1, $2)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        info!(
+            "Apply database migration {}: {}",
+            migration.version, migration.description
+        );
+    }
+
+    schema_version(conn).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    async fn memory_pool() -> Pool<Sqlite> {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    async fn applied(conn: &Pool<Sqlite>) -> Vec<i64> {
+        sqlx::query_scalar("SELECT version FROM schema_version ORDER BY version")
+            .fetch_all(conn)
+            .await
+            .unwrap()
+    }
+
+    fn latest() -> i64 {
+        MIGRATIONS.last().unwrap().version
+    }
+
+    #[actix_web::test]
+    async fn fresh_database_gets_all_migrations() {
+        let pool = memory_pool().await;
+
+        assert_eq!(schema_version(&pool).await.unwrap(), 0);
+        assert_eq!(migrate(&pool, "").await.unwrap(), latest());
+        assert_eq!(
+            applied(&pool).await,
+            MIGRATIONS.iter().map(|m| m.version).collect::<Vec<i64>>()
+        );
+    }
+
+    #[actix_web::test]
+    async fn baseline_schema_is_detected() {
+        let db_path = env::temp_dir().join(format!("ffp_migrate_{}.db", std::process::id()));
+        let db_path = db_path.to_string_lossy().to_string();
+        let pool = SqlitePoolOptions::new()
+            .connect(&format!("sqlite:{db_path}?mode=rwc"))
+            .await
+            .unwrap();
+
+        // database from before versioned migrations existed
+        sqlx::query(MIGRATIONS[0].sql).execute(&pool).await.unwrap();
+
+        assert_eq!(legacy_version(&pool).await.unwrap(), 1);
+        assert_eq!(migrate(&pool, &db_path).await.unwrap(), latest());
+        assert_eq!(applied(&pool).await.len(), MIGRATIONS.len());
+
+        pool.close().await;
+
+        // the backup from the old schema is next to the database file
+        let backups: Vec<_> = fs::read_dir(env::temp_dir())
+            .unwrap()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.to_string_lossy().starts_with(&format!("{db_path}.v1-")))
+            .collect();
+
+        assert_eq!(backups.len(), 1);
+
+        for file in backups.iter().chain([&PathBuf::from(&db_path)]) {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[actix_web::test]
+    async fn second_run_applies_nothing() {
+        let pool = memory_pool().await;
+
+        migrate(&pool, "").await.unwrap();
+
+        let before = applied(&pool).await;
+
+        assert_eq!(migrate(&pool, "").await.unwrap(), latest());
+        assert_eq!(applied(&pool).await, before);
+        assert_eq!(legacy_version(&pool).await.unwrap(), 4);
+    }
+}
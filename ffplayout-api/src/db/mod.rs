@@ -1,6 +1,7 @@
 use sqlx::{Pool, Sqlite, SqlitePool};
 
 pub mod handles;
+pub mod migrations;
 pub mod models;
 
 use crate::utils::db_path;
@@ -1,9 +1,13 @@
+use std::collections::HashMap;
+
 use regex::Regex;
 use serde::{
     de::{self, Visitor},
     Deserialize, Serialize,
 };
 
+use crate::utils::{ChannelRole, Role};
+
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow)]
 pub struct User {
     #[sqlx(default)]
@@ -22,6 +26,12 @@ pub struct User {
     #[sqlx(default)]
     #[serde(skip_serializing)]
     pub channel_id: Option<i32>,
+    #[sqlx(skip)]
+    #[serde(skip_serializing, default)]
+    pub channel_role: Option<ChannelRole>,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<bool>,
     #[sqlx(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
@@ -35,13 +45,186 @@ fn empty_string() -> String {
 pub struct LoginUser {
     pub id: i32,
     pub username: String,
+    pub role: Role,
+    pub channels: HashMap<i32, ChannelRole>,
+    /// ID from the API token, when the request was not authenticated with a login token
+    pub token_id: Option<i32>,
 }
 
 impl LoginUser {
-    pub fn new(id: i32, username: String) -> Self {
-        Self { id, username }
+    pub fn new(id: i32, username: String, role: Role, channels: HashMap<i32, ChannelRole>) -> Self {
+        Self {
+            id,
+            username,
+            role,
+            channels,
+            token_id: None,
+        }
+    }
+
+    /// Limit the permissions to the scope of an API token.
+    ///
+    /// Admin tokens with full scope stay admin, all others lose the global admin role
+    /// and get the token role in every channel from `all_channels`.
+    pub fn restrict(&mut self, token: &ApiToken, all_channels: &[i32]) {
+        let allowed = token.channel_ids();
+
+        if self.role == Role::Admin {
+            if token.role == ChannelRole::ChannelAdmin && allowed.is_empty() {
+                self.token_id = Some(token.id);
+                return;
+            }
+
+            self.role = Role::User;
+            self.channels = all_channels
+                .iter()
+                .map(|id| (*id, ChannelRole::ChannelAdmin))
+                .collect();
+        }
+
+        self.channels
+            .retain(|id, _| allowed.is_empty() || allowed.contains(id));
+
+        for role in self.channels.values_mut() {
+            *role = (*role).min(token.role);
+        }
+
+        self.token_id = Some(token.id);
+    }
+
+    /// Get role from user in channel, global admins are channel admins everywhere.
+    pub fn channel_role(&self, id: i32) -> Option<ChannelRole> {
+        if self.role == Role::Admin {
+            return Some(ChannelRole::ChannelAdmin);
+        }
+
+        self.channels.get(&id).copied()
     }
 }
+
+#[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct ChannelMember {
+    #[serde(skip_deserializing)]
+    pub user_id: i32,
+    #[sqlx(default)]
+    #[serde(skip_deserializing)]
+    pub username: String,
+    #[serde(skip_deserializing)]
+    pub channel_id: i32,
+    pub role: ChannelRole,
+}
+
+/// Long lived API token, only the hash from the token is stored.
+///
+/// The token can't have more permissions then its user, `role` limits the channel role
+/// and `channels` the accessible channels. Empty `channels` means all channels from the user.
+#[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct ApiToken {
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    #[serde(skip_deserializing)]
+    pub user_id: i32,
+    pub name: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub role: ChannelRole,
+    #[serde(
+        default,
+        serialize_with = "serialize_id_list",
+        deserialize_with = "deserialize_id_list"
+    )]
+    pub channels: String,
+    #[serde(skip_deserializing)]
+    pub created_at: String,
+    #[serde(skip_deserializing)]
+    pub last_used: Option<String>,
+}
+
+impl ApiToken {
+    pub fn channel_ids(&self) -> Vec<i32> {
+        self.channels
+            .split(',')
+            .filter_map(|c| c.trim().parse().ok())
+            .collect()
+    }
+}
+
+fn serialize_id_list<S>(ids: &str, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let list: Vec<i32> = ids.split(',').filter_map(|c| c.parse().ok()).collect();
+
+    list.serialize(serializer)
+}
+
+fn deserialize_id_list<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let list: Vec<i32> = Vec::deserialize(deserializer)?;
+
+    Ok(list
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<String>>()
+        .join(","))
+}
+
+/// Saved version of a channel playout config, in YAML format.
+///
+/// The newest version per channel is the active one, all others are the history.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct ChannelConfig {
+    pub id: i32,
+    pub channel_id: i32,
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub config: String,
+    pub username: String,
+    pub created_at: String,
+}
+
+/// Recorded mutating API call.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub username: String,
+    pub channel_id: Option<i32>,
+    pub method: String,
+    pub route: String,
+    pub path: String,
+    pub params: String,
+    pub status: u16,
+    pub client_ip: String,
+    pub created_at: String,
+}
+
+/// Filter for reading the audit log, `from` and `to` are compared with the timestamp in UTC.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AuditFilter {
+    pub user: Option<String>,
+    pub channel: Option<i32>,
+    pub route: Option<String>,
+    pub method: Option<String>,
+    pub status: Option<u16>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(default = "default_page")]
+    pub page: i64,
+    #[serde(default = "default_per_page")]
+    pub per_page: i64,
+}
+
+fn default_page() -> i64 {
+    1
+}
+
+fn default_per_page() -> i64 {
+    50
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow)]
 pub struct TextPreset {
     #[sqlx(default)]
@@ -63,6 +246,75 @@ pub struct TextPreset {
     pub boxborderw: String,
     #[serde(deserialize_with = "deserialize_number_or_string")]
     pub alpha: String,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub layer: String,
+}
+
+/// Schedule for sending text presets automatically to the engine.
+///
+/// A schedule is active between `start_at` and `end_at`, on the week `days` (1 = Monday)
+/// and at these days between `from_time` and `to_time`. Empty values mean no limit.
+/// The `presets` rotate every `dwell` seconds, with `crawl` the text moves from right to left.
+/// A not empty `layer` sends the presets to this text layer, instead of the layer from the preset.
+#[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow)]
+pub struct TextSchedule {
+    #[sqlx(default)]
+    #[serde(skip_deserializing)]
+    pub id: i32,
+    pub channel_id: i32,
+    pub name: String,
+    #[serde(
+        serialize_with = "serialize_id_list",
+        deserialize_with = "deserialize_id_list"
+    )]
+    pub presets: String,
+    #[serde(default)]
+    pub start_at: String,
+    #[serde(default)]
+    pub end_at: String,
+    #[serde(
+        default,
+        serialize_with = "serialize_id_list",
+        deserialize_with = "deserialize_id_list"
+    )]
+    pub days: String,
+    #[serde(default)]
+    pub from_time: String,
+    #[serde(default)]
+    pub to_time: String,
+    #[serde(default = "default_dwell")]
+    pub dwell: i64,
+    #[serde(default)]
+    pub crawl: bool,
+    #[serde(default = "default_crawl_speed")]
+    pub crawl_speed: i64,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub layer: String,
+}
+
+impl TextSchedule {
+    pub fn preset_ids(&self) -> Vec<i32> {
+        self.presets
+            .split(',')
+            .filter_map(|p| p.trim().parse().ok())
+            .collect()
+    }
+}
+
+fn default_dwell() -> i64 {
+    10
+}
+
+fn default_crawl_speed() -> i64 {
+    100
+}
+
+fn default_true() -> bool {
+    true
 }
 
 /// Deserialize number or string
@@ -111,7 +363,15 @@ pub struct Channel {
     pub extra_extensions: String,
     pub service: String,
 
+    #[sqlx(default)]
+    #[serde(default)]
+    pub timezone: String,
+
     #[sqlx(default)]
     #[serde(default)]
     pub utc_offset: i32,
+
+    #[sqlx(default)]
+    #[serde(default)]
+    pub autostart: bool,
 }
@@ -1,12 +1,13 @@
 use std::{
     env,
     process::exit,
-    sync::{Arc, Mutex},
+    sync::{atomic::Ordering, Arc, Mutex},
 };
 
 use actix_files::Files;
 use actix_web::{
-    dev::ServiceRequest, middleware::Logger, web, App, Error, HttpMessage, HttpServer,
+    dev::ServiceRequest, error::ErrorInternalServerError, middleware::Logger, web, App, Error,
+    HttpMessage, HttpServer,
 };
 use actix_web_grants::authorities::AttachAuthorities;
 use actix_web_httpauth::{extractors::bearer::BearerAuth, middleware::HttpAuthentication};
@@ -18,15 +19,18 @@ use clap::Parser;
 use lazy_static::lazy_static;
 use path_clean::PathClean;
 use simplelog::*;
+use sqlx::{Pool, Sqlite};
 use sysinfo::{Disks, Networks, System};
 
 pub mod api;
 pub mod db;
 pub mod utils;
 
-use api::{auth, routes::*};
-use db::{db_pool, models::LoginUser};
-use utils::{args_parse::Args, control::ProcessControl, db_path, init_config, run_args};
+use api::{audit::AuditLog, auth, routes::*};
+use db::{db_pool, migrations::migrate};
+use utils::{
+    args_parse::Args, db_path, init_config, run_args, supervisor::Supervisor, text_schedule,
+};
 
 #[cfg(any(debug_assertions, not(feature = "embed_frontend")))]
 use utils::public_path;
@@ -49,13 +53,14 @@ async fn validator(
     req: ServiceRequest,
     credentials: BearerAuth,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
-    // We just get permissions from JWT
-    match auth::decode_jwt(credentials.token()).await {
-        Ok(claims) => {
-            req.attach(vec![claims.role]);
+    let Some(pool) = req.app_data::<web::Data<Pool<Sqlite>>>() else {
+        return Err((ErrorInternalServerError("Database not available"), req));
+    };
 
-            req.extensions_mut()
-                .insert(LoginUser::new(claims.id, claims.username));
+    match auth::authenticate(pool, credentials.token()).await {
+        Ok(user) => {
+            req.attach(vec![user.role.clone()]);
+            req.extensions_mut().insert(user);
 
             Ok(req)
         }
@@ -90,11 +95,25 @@ async fn main() -> std::io::Result<()> {
             error!("Database is not initialized! Init DB first and add admin user.");
             exit(1);
         }
+        if let Err(e) = migrate(&pool, db_path().unwrap()).await {
+            error!("Database migration failed: {e}");
+            exit(1);
+        }
+
         init_config(&pool).await;
+
         let ip_port = conn.split(':').collect::<Vec<&str>>();
         let addr = ip_port[0];
         let port = ip_port[1].parse::<u16>().unwrap();
-        let engine_process = web::Data::new(ProcessControl::new());
+        let engine_process = web::Data::new(Supervisor::new());
+
+        if engine_process.piggyback.load(Ordering::SeqCst) {
+            info!("Run engines in piggyback mode");
+
+            engine_process.autostart(&pool).await;
+        }
+
+        tokio::spawn(text_schedule::run(pool.clone()));
 
         info!("running ffplayout API, listen on http://{conn}");
 
@@ -114,6 +133,12 @@ async fn main() -> std::io::Result<()> {
                 .service(
                     web::scope("/api")
                         .wrap(auth)
+                        .wrap(AuditLog)
+                        .service(logout)
+                        .service(get_api_tokens)
+                        .service(add_api_token)
+                        .service(remove_api_token)
+                        .service(get_audit_log)
                         .service(add_user)
                         .service(get_user)
                         .service(get_user_by_name)
@@ -121,26 +146,44 @@ async fn main() -> std::io::Result<()> {
                         .service(remove_user)
                         .service(get_playout_config)
                         .service(update_playout_config)
+                        .service(export_playout_config)
+                        .service(import_playout_config)
+                        .service(validate_playout_config)
+                        .service(get_config_history)
+                        .service(get_config_version)
+                        .service(restore_config_version)
                         .service(add_preset)
                         .service(get_presets)
                         .service(update_preset)
                         .service(delete_preset)
+                        .service(get_text_schedules)
+                        .service(add_text_schedule)
+                        .service(update_text_schedule)
+                        .service(delete_text_schedule)
                         .service(get_channel)
                         .service(get_all_channels)
                         .service(patch_channel)
                         .service(add_channel)
                         .service(remove_channel)
+                        .service(get_members)
+                        .service(update_member)
+                        .service(remove_member)
                         .service(update_user)
                         .service(send_text_message)
+                        .service(control_logo)
                         .service(control_playout)
                         .service(media_current)
                         .service(media_next)
                         .service(media_last)
                         .service(process_control)
+                        .service(get_process_status)
+                        .service(get_process_logs)
+                        .service(get_processes)
                         .service(get_playlist)
                         .service(save_playlist)
                         .service(gen_playlist)
                         .service(del_playlist)
+                        .service(playlist_bulk)
                         .service(get_log)
                         .service(file_browser)
                         .service(add_dir)
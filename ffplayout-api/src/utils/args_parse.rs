@@ -22,6 +22,9 @@ pub struct Args {
     #[clap(short, long, help = "Initialize Database")]
     pub init: bool,
 
+    #[clap(long, help = "Apply pending database migrations")]
+    pub migrate: bool,
+
     #[clap(short, long, help = "domain name for initialization")]
     pub domain: Option<String>,
 
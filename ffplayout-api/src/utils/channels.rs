@@ -1,5 +1,6 @@
 use std::{fs, path::PathBuf};
 
+use actix_web::web;
 use rand::prelude::*;
 use simplelog::*;
 use sqlx::{Pool, Sqlite};
@@ -7,6 +8,7 @@ use sqlx::{Pool, Sqlite};
 use crate::utils::{
     control::{control_service, ServiceCmd},
     errors::ServiceError,
+    supervisor::Supervisor,
 };
 
 use ffplayout_lib::utils::PlayoutConfig;
@@ -16,6 +18,7 @@ use crate::db::{handles, models::Channel};
 pub async fn create_channel(
     conn: &Pool<Sqlite>,
     target_channel: Channel,
+    engine: Option<web::Data<Supervisor>>,
 ) -> Result<Channel, ServiceError> {
     if !target_channel.service.starts_with("ffplayout@") {
         return Err(ServiceError::BadRequest("Bad service name!".to_string()));
@@ -50,15 +53,25 @@ pub async fn create_channel(
     serde_yaml::to_writer(file, &config).unwrap();
 
     let new_channel = handles::insert_channel(conn, target_channel).await?;
-    control_service(conn, new_channel.id, &ServiceCmd::Enable, None).await?;
+    control_service(conn, new_channel.id, &ServiceCmd::Enable, engine).await?;
 
     Ok(new_channel)
 }
 
-pub async fn delete_channel(conn: &Pool<Sqlite>, id: i32) -> Result<(), ServiceError> {
+pub async fn delete_channel(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    engine: Option<web::Data<Supervisor>>,
+) -> Result<(), ServiceError> {
     let channel = handles::select_channel(conn, &id).await?;
-    control_service(conn, channel.id, &ServiceCmd::Stop, None).await?;
-    control_service(conn, channel.id, &ServiceCmd::Disable, None).await?;
+    // the engine is maybe not running
+    let _ = control_service(conn, channel.id, &ServiceCmd::Stop, engine.clone()).await;
+
+    if let Some(engine) = &engine {
+        engine.remove(channel.id);
+    }
+
+    control_service(conn, channel.id, &ServiceCmd::Disable, engine).await?;
 
     if let Err(e) = fs::remove_file(channel.config_path) {
         error!("{e}");
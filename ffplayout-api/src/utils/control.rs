@@ -1,21 +1,13 @@
-use std::{
-    collections::HashMap,
-    env, fmt,
-    str::FromStr,
-    sync::atomic::{AtomicBool, Ordering},
-};
+use std::{collections::HashMap, fmt, str::FromStr, sync::atomic::Ordering};
 
 use actix_web::web;
 use reqwest::{header::AUTHORIZATION, Client, Response};
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Sqlite};
-use tokio::{
-    process::{Child, Command},
-    sync::Mutex,
-};
+use tokio::process::Command;
 
 use crate::db::handles::select_channel;
-use crate::utils::{errors::ServiceError, playout_config};
+use crate::utils::{errors::ServiceError, playout_config, supervisor::Supervisor};
 use ffplayout_lib::vec_strings;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -29,102 +21,30 @@ pub struct ControlParams {
     pub control: String,
 }
 
+/// Switch a logo layer: `state` is on, off, toggle or auto; `replace` hides another layer.
 #[derive(Debug, Deserialize, Serialize, Clone)]
-struct MediaParams {
-    media: String,
-}
-
-/// ffplayout engine process
-///
-/// When running not on Linux, or with environment variable `PIGGYBACK_MODE=true`,
-/// the engine get startet and controlled from ffpapi
-pub struct ProcessControl {
-    pub engine_child: Mutex<Option<Child>>,
-    pub is_running: AtomicBool,
-    pub piggyback: AtomicBool,
+pub struct LogoParams {
+    pub name: String,
+    #[serde(default = "default_logo_state")]
+    pub state: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub replace: String,
 }
 
-impl ProcessControl {
-    pub fn new() -> Self {
-        let piggyback = if env::consts::OS != "linux" || env::var("PIGGYBACK_MODE").is_ok() {
-            AtomicBool::new(true)
-        } else {
-            AtomicBool::new(false)
-        };
-
-        Self {
-            engine_child: Mutex::new(None),
-            is_running: AtomicBool::new(false),
-            piggyback,
-        }
-    }
+fn default_logo_state() -> String {
+    "toggle".to_string()
 }
 
-impl ProcessControl {
-    pub async fn start(&self) -> Result<String, ServiceError> {
-        #[cfg(not(debug_assertions))]
-        let engine_path = "ffplayout";
-
-        #[cfg(debug_assertions)]
-        let engine_path = "./target/debug/ffplayout";
-
-        match Command::new(engine_path).kill_on_drop(true).spawn() {
-            Ok(proc) => *self.engine_child.lock().await = Some(proc),
-            Err(_) => return Err(ServiceError::InternalServerError),
-        };
-
-        self.is_running.store(true, Ordering::SeqCst);
-
-        Ok("Success".to_string())
-    }
-
-    pub async fn stop(&self) -> Result<String, ServiceError> {
-        if let Some(proc) = self.engine_child.lock().await.as_mut() {
-            if proc.kill().await.is_err() {
-                return Err(ServiceError::InternalServerError);
-            };
-        }
-
-        self.wait().await?;
-        self.is_running.store(false, Ordering::SeqCst);
-
-        Ok("Success".to_string())
-    }
-
-    pub async fn restart(&self) -> Result<String, ServiceError> {
-        self.stop().await?;
-        self.start().await?;
-
-        self.is_running.store(true, Ordering::SeqCst);
-
-        Ok("Success".to_string())
-    }
-
-    /// Wait for process to proper close.
-    /// This prevents orphaned/zombi processes in system
-    pub async fn wait(&self) -> Result<String, ServiceError> {
-        if let Some(proc) = self.engine_child.lock().await.as_mut() {
-            if proc.wait().await.is_err() {
-                return Err(ServiceError::InternalServerError);
-            };
-        }
-
-        Ok("Success".to_string())
-    }
-
-    pub fn status(&self) -> Result<String, ServiceError> {
-        if self.is_running.load(Ordering::SeqCst) {
-            Ok("active".to_string())
-        } else {
-            Ok("not running".to_string())
-        }
-    }
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct LogoControl {
+    control: String,
+    #[serde(flatten)]
+    params: LogoParams,
 }
 
-impl Default for ProcessControl {
-    fn default() -> Self {
-        Self::new()
-    }
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct MediaParams {
+    media: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -286,6 +206,19 @@ pub async fn control_state(
     post_request(conn, id, json_obj).await
 }
 
+pub async fn send_logo(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    params: LogoParams,
+) -> Result<Response, ServiceError> {
+    let json_obj = LogoControl {
+        control: "logo".into(),
+        params,
+    };
+
+    post_request(conn, id, json_obj).await
+}
+
 pub async fn media_info(
     conn: &Pool<Sqlite>,
     id: i32,
@@ -300,31 +233,17 @@ pub async fn control_service(
     conn: &Pool<Sqlite>,
     id: i32,
     command: &ServiceCmd,
-    engine: Option<web::Data<ProcessControl>>,
+    engine: Option<web::Data<Supervisor>>,
 ) -> Result<String, ServiceError> {
     if let Some(en) = engine {
         if en.piggyback.load(Ordering::SeqCst) {
             match command {
-                ServiceCmd::Start => en.start().await,
-                ServiceCmd::Stop => {
-                    if control_state(conn, id, "stop_all").await.is_ok() {
-                        en.stop().await
-                    } else {
-                        Err(ServiceError::NoContent("Nothing to stop".to_string()))
-                    }
-                }
-                ServiceCmd::Restart => {
-                    if control_state(conn, id, "stop_all").await.is_ok() {
-                        en.restart().await
-                    } else {
-                        Err(ServiceError::NoContent("Nothing to restart".to_string()))
-                    }
-                }
-                ServiceCmd::Status => en.status(),
-                _ => Err(ServiceError::Conflict(
-                    "Engine runs in piggyback mode, in this mode this command is not allowed."
-                        .to_string(),
-                )),
+                ServiceCmd::Enable => en.enable(conn, id, true).await,
+                ServiceCmd::Disable => en.enable(conn, id, false).await,
+                ServiceCmd::Start => en.start(conn, id).await,
+                ServiceCmd::Stop => en.stop(conn, id).await,
+                ServiceCmd::Restart => en.restart(conn, id).await,
+                ServiceCmd::Status => Ok(en.status_text(id)),
             }
         } else {
             execute_systemd(conn, id, command).await
@@ -15,6 +15,9 @@ pub enum ServiceError {
     #[display(fmt = "Unauthorized")]
     Unauthorized,
 
+    #[display(fmt = "Forbidden")]
+    Forbidden,
+
     #[display(fmt = "NoContent: {_0}")]
     NoContent(String),
 
@@ -32,6 +35,7 @@ impl ResponseError for ServiceError {
             ServiceError::BadRequest(ref message) => HttpResponse::BadRequest().json(message),
             ServiceError::Conflict(ref message) => HttpResponse::Conflict().json(message),
             ServiceError::Unauthorized => HttpResponse::Unauthorized().json("No Permission!"),
+            ServiceError::Forbidden => HttpResponse::Forbidden().json("No Permission!"),
             ServiceError::NoContent(ref message) => HttpResponse::NoContent().json(message),
             ServiceError::ServiceUnavailable(ref message) => {
                 HttpResponse::ServiceUnavailable().json(message)
@@ -2,10 +2,11 @@ use std::{
     env,
     error::Error,
     fmt,
-    fs::{self, metadata, File},
+    fs::{self, metadata},
     io::{stdin, stdout, Write},
     path::{Path, PathBuf},
     str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use chrono::{format::ParseErrorKind, prelude::*};
@@ -25,15 +26,18 @@ pub mod control;
 pub mod errors;
 pub mod files;
 pub mod playlist;
+pub mod supervisor;
 pub mod system;
+pub mod text_schedule;
 
 use crate::db::{
     db_pool,
-    handles::{db_init, insert_user, select_channel, select_global},
-    models::{Channel, User},
+    handles::{db_init, insert_config, insert_user, select_channel, select_config, select_global},
+    migrations::migrate,
+    models::{Channel, ChannelConfig, LoginUser, User},
 };
 use crate::utils::errors::ServiceError;
-use ffplayout_lib::utils::{time_to_sec, PlayoutConfig};
+use ffplayout_lib::utils::{day_start_to_sec, time_to_sec, utc_to_local, PlayoutConfig, Tz};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum Role {
@@ -97,6 +101,71 @@ impl FromRow<'_, SqliteRow> for Role {
     }
 }
 
+/// Role of a user inside a channel.
+///
+/// Roles are ordered, every role includes the permissions from the roles before.
+/// Global admins (`Role::Admin`) have full access to all channels.
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ChannelRole {
+    /// read playlists, program, logs and media infos
+    Viewer,
+    /// edit playlists, files and text presets
+    Editor,
+    /// control the playout and send text messages
+    Operator,
+    /// edit channel and playout settings, manage channel members
+    ChannelAdmin,
+}
+
+impl ChannelRole {
+    /// Channel role for users, which are created without one.
+    pub fn from_role_id(role_id: Option<i32>) -> Self {
+        match role_id {
+            Some(3) => Self::Viewer,
+            _ => Self::Operator,
+        }
+    }
+}
+
+impl FromStr for ChannelRole {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "viewer" => Ok(Self::Viewer),
+            "editor" => Ok(Self::Editor),
+            "operator" => Ok(Self::Operator),
+            "channel_admin" => Ok(Self::ChannelAdmin),
+            _ => Err(format!("Channel role '{input}' not exists!")),
+        }
+    }
+}
+
+impl fmt::Display for ChannelRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Viewer => write!(f, "viewer"),
+            Self::Editor => write!(f, "editor"),
+            Self::Operator => write!(f, "operator"),
+            Self::ChannelAdmin => write!(f, "channel_admin"),
+        }
+    }
+}
+
+/// Check if the user has at least the given role in the channel.
+///
+/// Users without membership or with a lower role get a forbidden error.
+pub fn channel_access(user: &LoginUser, id: i32, role: ChannelRole) -> Result<(), ServiceError> {
+    match user.channel_role(id) {
+        Some(r) if r >= role => Ok(()),
+        _ => Err(ServiceError::Forbidden),
+    }
+}
+
 #[derive(Debug, sqlx::FromRow)]
 pub struct GlobalSettings {
     pub secret: String,
@@ -121,6 +190,9 @@ impl GlobalSettings {
 
 static INSTANCE: OnceCell<GlobalSettings> = OnceCell::new();
 
+/// Counts the saved channel configs, so that background tasks know when they have to read them again.
+pub static CONFIG_CHANGES: AtomicUsize = AtomicUsize::new(0);
+
 pub async fn init_config(conn: &Pool<Sqlite>) {
     let config = GlobalSettings::new(conn).await;
     INSTANCE.set(config).unwrap();
@@ -181,7 +253,8 @@ pub fn public_path() -> PathBuf {
 pub async fn run_args() -> Result<(), i32> {
     let mut args = ARGS.clone();
 
-    if !args.init && args.listen.is_none() && !args.ask && args.username.is_none() {
+    if !args.init && !args.migrate && args.listen.is_none() && !args.ask && args.username.is_none()
+    {
         error!("Wrong number of arguments! Run ffpapi --help for more information.");
 
         return Err(0);
@@ -195,6 +268,29 @@ pub async fn run_args() -> Result<(), i32> {
         return Err(0);
     }
 
+    if args.migrate {
+        let db_path = db_path().map_err(|e| {
+            error!("{e}");
+            1
+        })?;
+
+        match db_pool().await {
+            Ok(conn) => match migrate(&conn, db_path).await {
+                Ok(version) => info!("Database schema is on version {version}"),
+                Err(e) => {
+                    error!("Database migration failed: {e}");
+                    return Err(1);
+                }
+            },
+            Err(e) => {
+                error!("{e}");
+                return Err(1);
+            }
+        }
+
+        return Err(0);
+    }
+
     if args.ask {
         let mut user = String::new();
         print!("Username: ");
@@ -248,11 +344,18 @@ pub async fn run_args() -> Result<(), i32> {
             password: args.password.unwrap(),
             role_id: Some(1),
             channel_id: Some(1),
+            channel_role: None,
+            disabled: None,
             token: None,
         };
 
         match db_pool().await {
             Ok(conn) => {
+                if let Err(e) = migrate(&conn, db_path().unwrap_or_default()).await {
+                    error!("Database migration failed: {e}");
+                    return Err(1);
+                };
+
                 if let Err(e) = insert_user(&conn, user).await {
                     error!("{e}");
                     return Err(1);
@@ -273,23 +376,87 @@ pub async fn run_args() -> Result<(), i32> {
     Ok(())
 }
 
-pub fn read_playout_config(path: &str) -> Result<PlayoutConfig, Box<dyn Error>> {
-    let file = File::open(path)?;
-    let mut config: PlayoutConfig = serde_yaml::from_reader(file)?;
+/// Parse playout config from YAML content.
+pub fn parse_playout_config(content: &str) -> Result<PlayoutConfig, serde_yaml::Error> {
+    let mut config: PlayoutConfig = serde_yaml::from_str(content)?;
 
-    config.playlist.start_sec = Some(time_to_sec(&config.playlist.day_start));
+    config.playlist.start_sec = Some(day_start_to_sec(
+        &config.playlist.day_start,
+        config.playlist.timezone,
+    ));
     config.playlist.length_sec = Some(time_to_sec(&config.playlist.length));
 
     Ok(config)
 }
 
+/// Import the config file from channel, as new config version.
+pub async fn import_config_file(
+    conn: &Pool<Sqlite>,
+    channel: &Channel,
+    username: &str,
+) -> Result<ChannelConfig, ServiceError> {
+    let content = fs::read_to_string(&channel.config_path)?;
+    parse_playout_config(&content)
+        .map_err(|e| ServiceError::BadRequest(format!("Config file is invalid: {e}")))?;
+
+    let config = insert_config(conn, channel.id, &content, username).await?;
+    CONFIG_CHANGES.fetch_add(1, Ordering::SeqCst);
+
+    info!(
+        "Import config <b><magenta>{}</></b> from channel {}",
+        channel.config_path, channel.id
+    );
+
+    Ok(config)
+}
+
+/// Get the active config version from channel.
+///
+/// Configs are stored in the database, when a channel has no config yet,
+/// its config file gets imported.
+pub async fn active_playout_config(
+    conn: &Pool<Sqlite>,
+    channel: &Channel,
+) -> Result<ChannelConfig, ServiceError> {
+    match select_config(conn, channel.id).await {
+        Ok(config) => Ok(config),
+        Err(sqlx::Error::RowNotFound) => import_config_file(conn, channel, "").await,
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Save YAML config as new version and export it to the channel config file,
+/// so that engines which still read the file get the changes too.
+pub async fn save_playout_config(
+    conn: &Pool<Sqlite>,
+    channel: &Channel,
+    content: &str,
+    username: &str,
+) -> Result<ChannelConfig, ServiceError> {
+    parse_playout_config(content)
+        .map_err(|e| ServiceError::BadRequest(format!("Config is invalid: {e}")))?;
+
+    let config = insert_config(conn, channel.id, content, username).await?;
+    CONFIG_CHANGES.fetch_add(1, Ordering::SeqCst);
+
+    if !channel.config_path.is_empty() {
+        if let Err(e) = fs::write(&channel.config_path, content) {
+            error!("Export config to {} failed: {e}", channel.config_path);
+        }
+    }
+
+    Ok(config)
+}
+
 pub async fn playout_config(
     conn: &Pool<Sqlite>,
     channel_id: &i32,
 ) -> Result<(PlayoutConfig, Channel), ServiceError> {
     if let Ok(channel) = select_channel(conn, channel_id).await {
-        if let Ok(config) = read_playout_config(&channel.config_path.clone()) {
-            return Ok((config, channel));
+        if let Ok(active) = active_playout_config(conn, &channel).await {
+            if let Ok(config) = parse_playout_config(&active.config) {
+                return Ok((config, channel));
+            }
         }
     }
 
@@ -303,7 +470,7 @@ pub async fn read_log_file(
     channel_id: &i32,
     date: &str,
 ) -> Result<String, ServiceError> {
-    if let Ok(channel) = select_channel(conn, channel_id).await {
+    if let Ok((config, _)) = playout_config(conn, channel_id).await {
         let mut date_str = "".to_string();
 
         if !date.is_empty() {
@@ -311,24 +478,22 @@ pub async fn read_log_file(
             date_str.push_str(date);
         }
 
-        if let Ok(config) = read_playout_config(&channel.config_path) {
-            let mut log_path = Path::new(&config.logging.path)
-                .join("ffplayout.log")
-                .display()
-                .to_string();
-            log_path.push_str(&date_str);
+        let mut log_path = Path::new(&config.logging.path)
+            .join("ffplayout.log")
+            .display()
+            .to_string();
+        log_path.push_str(&date_str);
 
-            let file_size = metadata(&log_path)?.len() as f64;
+        let file_size = metadata(&log_path)?.len() as f64;
 
-            let file_content = if file_size > 5000000.0 {
-                error!("Log file to big: {}", sizeof_fmt(file_size));
-                format!("The log file is larger ({}) than the hard limit of 5MB, the probability is very high that something is wrong with the playout. Check this on the server with `less {log_path}`.", sizeof_fmt(file_size))
-            } else {
-                fs::read_to_string(log_path)?
-            };
+        let file_content = if file_size > 5000000.0 {
+            error!("Log file to big: {}", sizeof_fmt(file_size));
+            format!("The log file is larger ({}) than the hard limit of 5MB, the probability is very high that something is wrong with the playout. Check this on the server with `less {log_path}`.", sizeof_fmt(file_size))
+        } else {
+            fs::read_to_string(log_path)?
+        };
 
-            return Ok(file_content);
-        }
+        return Ok(file_content);
     }
 
     Err(ServiceError::NoContent(
@@ -350,20 +515,26 @@ pub fn sizeof_fmt(mut num: f64) -> String {
     format!("{num:.1}Yi{suffix}")
 }
 
-pub fn local_utc_offset() -> i32 {
-    let mut offset = Local::now().format("%:z").to_string();
-    let operator = offset.remove(0);
-    let mut utc_offset = 0;
-
-    if let Some((r, f)) = offset.split_once(':') {
-        utc_offset = r.parse::<i32>().unwrap_or(0) * 60 + f.parse::<i32>().unwrap_or(0);
-
-        if operator == '-' && utc_offset > 0 {
-            utc_offset = -utc_offset;
-        }
-    }
+/// Get UTC offset in minutes, from the given timezone or from system timezone.
+pub fn utc_offset(timezone: Option<Tz>) -> i32 {
+    utc_to_local(timezone, &Utc::now())
+        .offset()
+        .local_minus_utc()
+        / 60
+}
 
-    utc_offset
+/// Set timezone and UTC offset from the channel config.
+///
+/// When the config has no timezone, the system timezone is used.
+pub async fn set_channel_timezone(conn: &Pool<Sqlite>, channel: &mut Channel) {
+    let timezone = select_config(conn, channel.id)
+        .await
+        .ok()
+        .and_then(|c| parse_playout_config(&c.config).ok())
+        .and_then(|c| c.playlist.timezone);
+
+    channel.timezone = timezone.map(|tz| tz.name().to_string()).unwrap_or_default();
+    channel.utc_offset = utc_offset(timezone);
 }
 
 pub fn naive_date_time_from_str<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
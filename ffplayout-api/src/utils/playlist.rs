@@ -1,11 +1,16 @@
-use std::{fs, path::PathBuf};
+use std::{fs, io::ErrorKind, path::PathBuf};
 
+use chrono::NaiveDate;
 use simplelog::*;
 use sqlx::{Pool, Sqlite};
+use tokio::task;
 
 use crate::utils::{errors::ServiceError, files::norm_abs_path, playout_config};
 use ffplayout_lib::utils::{
-    generate_playlist as playlist_generator, json_reader, json_writer, JsonPlaylist, PlayoutConfig,
+    bulk_playlists, generate_playlist as playlist_generator, json_reader,
+    json_serializer::{find_playlist, playlist_first_day},
+    json_writer, try_date_range, BulkOperation, BulkResult, ExistsPolicy, JsonPlaylist,
+    PlayoutConfig,
 };
 
 pub async fn read_playlist(
@@ -28,6 +33,30 @@ pub async fn read_playlist(
     }
 }
 
+/// Read the playlist which plays on the given date, with the first day from it.
+///
+/// Multi day playlists are also found on their following days.
+pub async fn read_playlist_on(
+    config: &PlayoutConfig,
+    date: NaiveDate,
+) -> Result<(JsonPlaylist, NaiveDate), ServiceError> {
+    let config = config.clone();
+
+    task::spawn_blocking(move || {
+        let path = find_playlist(&config, date);
+
+        match json_reader(&PathBuf::from(&path)) {
+            Ok(playlist) => {
+                let first_day = playlist_first_day(&config, &playlist, date);
+
+                Ok((playlist, first_day))
+            }
+            Err(e) => Err(ServiceError::NoContent(format!("{path}: {e}"))),
+        }
+    })
+    .await?
+}
+
 pub async fn write_playlist(
     conn: &Pool<Sqlite>,
     id: i32,
@@ -140,3 +169,27 @@ pub async fn delete_playlist(
         Ok(format!("No playlist to delete on: {date}"))
     }
 }
+
+pub async fn bulk_playlist(
+    conn: &Pool<Sqlite>,
+    id: i32,
+    operation: BulkOperation,
+    range: Vec<String>,
+    policy: ExistsPolicy,
+    dry_run: bool,
+) -> Result<Vec<BulkResult>, ServiceError> {
+    let (config, _) = playout_config(conn, &id).await?;
+    let dates = try_date_range(&range).map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+
+    match bulk_playlists(&config, &operation, &dates, policy, dry_run) {
+        Ok(results) => Ok(results),
+        Err(e) if e.kind() == ErrorKind::InvalidInput => {
+            Err(ServiceError::BadRequest(e.to_string()))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(ServiceError::NoContent(e.to_string())),
+        Err(e) => {
+            error!("{e}");
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
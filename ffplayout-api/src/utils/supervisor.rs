@@ -0,0 +1,522 @@
+/// Engine Supervisor
+///
+/// When running not on Linux, or with environment variable `PIGGYBACK_MODE=true`,
+/// ffpapi runs one engine process per channel, instead of controlling them over systemd.
+/// Crashed engines get restarted with increasing delay. Output and exit codes are kept,
+/// to show them in the process status.
+use std::{
+    collections::{HashMap, VecDeque},
+    env, fs, io,
+    path::Path,
+    process::{ExitStatus, Stdio},
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use regex::Regex;
+use serde::Serialize;
+use simplelog::*;
+use sqlx::{Pool, Sqlite};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::{Child, Command},
+    sync::watch,
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+
+use crate::db::handles;
+use crate::utils::{active_playout_config, control::control_state, errors::ServiceError};
+
+// Lines from the engine output, which are kept per channel
+const LOG_LINES: usize = 1000;
+// Exit records, which are kept per channel
+const EXIT_HISTORY: usize = 20;
+// Delay before the first restart
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+// The restart delay doubles on every crash, up to this limit
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// Engines which run longer, start again with the shortest delay after a crash
+const STABLE_RUNTIME: Duration = Duration::from_secs(60);
+// Time the supervisor waits for the engine task, when stopping
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessState {
+    Running,
+    Restarting,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessExit {
+    pub code: Option<i32>,
+    pub message: String,
+    pub started_at: String,
+    pub stopped_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessStatus {
+    pub channel_id: i32,
+    pub state: ProcessState,
+    pub pid: Option<u32>,
+    pub started_at: Option<String>,
+    pub restarts: u32,
+    pub exits: VecDeque<ProcessExit>,
+}
+
+impl ProcessStatus {
+    fn new(channel_id: i32) -> Self {
+        Self {
+            channel_id,
+            state: ProcessState::Stopped,
+            pid: None,
+            started_at: None,
+            restarts: 0,
+            exits: VecDeque::new(),
+        }
+    }
+}
+
+struct EngineProcess {
+    status: Mutex<ProcessStatus>,
+    logs: Mutex<VecDeque<String>>,
+    stop_sender: Mutex<Option<watch::Sender<bool>>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl EngineProcess {
+    fn new(channel_id: i32) -> Self {
+        Self {
+            status: Mutex::new(ProcessStatus::new(channel_id)),
+            logs: Mutex::new(VecDeque::new()),
+            stop_sender: Mutex::new(None),
+            task: Mutex::new(None),
+        }
+    }
+
+    fn push_log(&self, line: String) {
+        let mut logs = self.logs.lock().unwrap();
+
+        if logs.len() >= LOG_LINES {
+            logs.pop_front();
+        }
+
+        logs.push_back(line);
+    }
+
+    fn set_state(&self, state: ProcessState, pid: Option<u32>) {
+        let mut status = self.status.lock().unwrap();
+
+        status.state = state;
+        status.pid = pid;
+    }
+
+    fn add_exit(&self, exit: ProcessExit) {
+        let mut status = self.status.lock().unwrap();
+
+        if status.exits.len() >= EXIT_HISTORY {
+            status.exits.pop_front();
+        }
+
+        status.exits.push_back(exit);
+    }
+}
+
+/// Supervisor for all channel engines.
+pub struct Supervisor {
+    pub piggyback: AtomicBool,
+    engines: Mutex<HashMap<i32, Arc<EngineProcess>>>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        let piggyback = if env::consts::OS != "linux" || env::var("PIGGYBACK_MODE").is_ok() {
+            AtomicBool::new(true)
+        } else {
+            AtomicBool::new(false)
+        };
+
+        Self {
+            piggyback,
+            engines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn engine(&self, id: i32) -> Option<Arc<EngineProcess>> {
+        self.engines.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Start the engines from all channels, which have autostart enabled.
+    pub async fn autostart(&self, conn: &Pool<Sqlite>) {
+        let channels = match handles::select_all_channels(conn).await {
+            Ok(channels) => channels,
+            Err(e) => {
+                error!("Autostart engines failed: {e}");
+                return;
+            }
+        };
+
+        for channel in channels.into_iter().filter(|c| c.autostart) {
+            if let Err(e) = self.start(conn, channel.id).await {
+                error!("Autostart engine from channel {}: {e}", channel.id);
+            }
+        }
+    }
+
+    pub async fn start(&self, conn: &Pool<Sqlite>, id: i32) -> Result<String, ServiceError> {
+        let channel = handles::select_channel(conn, &id).await?;
+
+        // the engine reads the config file, export it when it only exists in the database
+        if !Path::new(&channel.config_path).is_file() {
+            let config = active_playout_config(conn, &channel).await.map_err(|_| {
+                ServiceError::NoContent(format!("Config {} not found", channel.config_path))
+            })?;
+
+            fs::write(&channel.config_path, config.config)?;
+        }
+
+        let engine = self
+            .engines
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_insert_with(|| Arc::new(EngineProcess::new(id)))
+            .clone();
+
+        let mut stop_sender = engine.stop_sender.lock().unwrap();
+
+        if stop_sender.is_some() {
+            return Err(ServiceError::Conflict(
+                "Engine is already running".to_string(),
+            ));
+        }
+
+        let (sender, receiver) = watch::channel(false);
+        *stop_sender = Some(sender);
+        drop(stop_sender);
+
+        engine.set_state(ProcessState::Running, None);
+        engine.status.lock().unwrap().restarts = 0;
+
+        let task = tokio::spawn(run_engine(
+            engine.clone(),
+            channel.config_path,
+            receiver,
+            spawn_engine,
+        ));
+        *engine.task.lock().unwrap() = Some(task);
+
+        Ok("Success".to_string())
+    }
+
+    pub async fn stop(&self, conn: &Pool<Sqlite>, id: i32) -> Result<String, ServiceError> {
+        let Some(engine) = self.engine(id) else {
+            return Err(ServiceError::NoContent("Nothing to stop".to_string()));
+        };
+
+        let Some(sender) = engine.stop_sender.lock().unwrap().take() else {
+            return Err(ServiceError::NoContent("Nothing to stop".to_string()));
+        };
+
+        let task = engine.task.lock().unwrap().take();
+
+        // let the engine stop its ffmpeg processes first, the engine itself gets killed afterwards
+        let _ = control_state(conn, id, "stop_all").await;
+        let _ = sender.send(true);
+
+        if let Some(task) = task {
+            if timeout(STOP_TIMEOUT, task).await.is_err() {
+                error!("Stop engine from channel {id} timed out");
+            }
+        }
+
+        Ok("Success".to_string())
+    }
+
+    pub async fn restart(&self, conn: &Pool<Sqlite>, id: i32) -> Result<String, ServiceError> {
+        if self.state(id) != ProcessState::Stopped {
+            self.stop(conn, id).await?;
+        }
+
+        self.start(conn, id).await
+    }
+
+    /// Enable or disable autostart from channel engine.
+    pub async fn enable(
+        &self,
+        conn: &Pool<Sqlite>,
+        id: i32,
+        autostart: bool,
+    ) -> Result<String, ServiceError> {
+        handles::update_channel_autostart(conn, id, autostart).await?;
+
+        Ok("Success".to_string())
+    }
+
+    /// Forget the engine from a deleted channel, with its output and exit history.
+    pub fn remove(&self, id: i32) {
+        self.engines.lock().unwrap().remove(&id);
+    }
+
+    pub fn state(&self, id: i32) -> ProcessState {
+        self.engine(id)
+            .map(|e| e.status.lock().unwrap().state)
+            .unwrap_or(ProcessState::Stopped)
+    }
+
+    /// Get the state in the same form as `systemctl is-active`.
+    pub fn status_text(&self, id: i32) -> String {
+        match self.state(id) {
+            ProcessState::Running => "active",
+            ProcessState::Restarting => "activating",
+            ProcessState::Stopped => "not running",
+        }
+        .to_string()
+    }
+
+    pub fn status(&self, id: i32) -> ProcessStatus {
+        self.engine(id)
+            .map(|e| e.status.lock().unwrap().clone())
+            .unwrap_or_else(|| ProcessStatus::new(id))
+    }
+
+    /// Get the last lines from the engine output.
+    pub fn logs(&self, id: i32, lines: usize) -> Vec<String> {
+        let Some(engine) = self.engine(id) else {
+            return vec![];
+        };
+
+        let logs = engine.logs.lock().unwrap();
+        let skip = logs.len().saturating_sub(lines);
+
+        logs.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn timestamp() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn spawn_engine(config_path: &str) -> io::Result<Child> {
+    #[cfg(not(debug_assertions))]
+    let engine_path = "ffplayout";
+
+    #[cfg(debug_assertions)]
+    let engine_path = "./target/debug/ffplayout";
+
+    Command::new(engine_path)
+        .args(["-c", config_path])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+}
+
+/// Keep the engine output, without terminal colors.
+fn capture_output<R>(engine: Arc<EngineProcess>, reader: R)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let re = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            engine.push_log(re.replace_all(&line, "").to_string());
+        }
+    });
+}
+
+/// Delay before the next restart, from the last delay and the runtime of the crashed engine.
+fn restart_delay(last: Option<Duration>, runtime: Duration) -> Duration {
+    match last {
+        Some(delay) if runtime <= STABLE_RUNTIME => (delay * 2).min(MAX_BACKOFF),
+        _ => MIN_BACKOFF,
+    }
+}
+
+fn exit_info(status: io::Result<ExitStatus>) -> (Option<i32>, String) {
+    match status {
+        Ok(status) => (status.code(), status.to_string()),
+        Err(e) => (None, e.to_string()),
+    }
+}
+
+/// Run the engine and restart it, until it gets stopped.
+///
+/// `spawn` starts the engine process with the config path.
+async fn run_engine<F>(
+    engine: Arc<EngineProcess>,
+    config_path: String,
+    mut stop: watch::Receiver<bool>,
+    spawn: F,
+) where
+    F: Fn(&str) -> io::Result<Child>,
+{
+    let id = engine.status.lock().unwrap().channel_id;
+    let mut backoff = None;
+
+    loop {
+        let started = Instant::now();
+        let started_at = timestamp();
+
+        let (code, message) = match spawn(&config_path) {
+            Ok(mut child) => {
+                if let Some(stdout) = child.stdout.take() {
+                    capture_output(engine.clone(), stdout);
+                }
+
+                if let Some(stderr) = child.stderr.take() {
+                    capture_output(engine.clone(), stderr);
+                }
+
+                engine.set_state(ProcessState::Running, child.id());
+                engine.status.lock().unwrap().started_at = Some(started_at.clone());
+
+                info!("Start engine from channel {id}: <b><magenta>{config_path}</></b>");
+
+                tokio::select! {
+                    status = child.wait() => exit_info(status),
+                    _ = stop.changed() => {
+                        let _ = child.kill().await;
+
+                        exit_info(child.wait().await)
+                    }
+                }
+            }
+            Err(e) => (None, format!("Start engine failed: {e}")),
+        };
+
+        engine.add_exit(ProcessExit {
+            code,
+            message: message.clone(),
+            started_at,
+            stopped_at: timestamp(),
+        });
+
+        // the sender is taken, when the engine gets stopped
+        if *stop.borrow() || engine.stop_sender.lock().unwrap().is_none() {
+            info!("Engine from channel {id} stopped");
+            engine.set_state(ProcessState::Stopped, None);
+            break;
+        }
+
+        let delay = restart_delay(backoff, started.elapsed());
+        backoff = Some(delay);
+
+        error!("Engine from channel {id} exited ({message}), restart in {delay:?}");
+
+        engine.set_state(ProcessState::Restarting, None);
+        engine.status.lock().unwrap().restarts += 1;
+
+        tokio::select! {
+            _ = sleep(delay) => (),
+            _ = stop.changed() => {
+                info!("Engine from channel {id} stopped");
+                engine.set_state(ProcessState::Stopped, None);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_engine_has_no_history() {
+        let supervisor = Supervisor::new();
+        let engine = Arc::new(EngineProcess::new(1));
+
+        engine.push_log("engine output".to_string());
+        engine.add_exit(ProcessExit {
+            code: Some(1),
+            message: "exit status: 1".to_string(),
+            started_at: timestamp(),
+            stopped_at: timestamp(),
+        });
+        supervisor.engines.lock().unwrap().insert(1, engine);
+
+        assert_eq!(supervisor.status(1).exits.len(), 1);
+
+        supervisor.remove(1);
+
+        assert!(supervisor.status(1).exits.is_empty());
+        assert!(supervisor.logs(1, 10).is_empty());
+    }
+
+    #[test]
+    fn restart_delay_doubles_until_stable() {
+        let short = Duration::from_secs(5);
+        let mut delay = None;
+        let mut schedule = vec![];
+
+        for _ in 0..8 {
+            let next = restart_delay(delay, short);
+            schedule.push(next.as_secs());
+            delay = Some(next);
+        }
+
+        assert_eq!(schedule, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(restart_delay(delay, STABLE_RUNTIME * 2), MIN_BACKOFF);
+    }
+
+    #[test]
+    fn exit_history_is_limited() {
+        let engine = EngineProcess::new(1);
+
+        for code in 0..EXIT_HISTORY as i32 + 5 {
+            engine.add_exit(ProcessExit {
+                code: Some(code),
+                message: format!("exit status: {code}"),
+                started_at: timestamp(),
+                stopped_at: timestamp(),
+            });
+        }
+
+        let exits = engine.status.lock().unwrap().exits.clone();
+
+        assert_eq!(exits.len(), EXIT_HISTORY);
+        assert_eq!(exits.front().unwrap().code, Some(5));
+        assert_eq!(exits.back().unwrap().code, Some(EXIT_HISTORY as i32 + 4));
+    }
+
+    #[actix_web::test]
+    async fn failed_start_gets_restarted_until_stopped() {
+        let engine = Arc::new(EngineProcess::new(1));
+        let (sender, receiver) = watch::channel(false);
+        *engine.stop_sender.lock().unwrap() = Some(sender.clone());
+
+        let task = tokio::spawn(run_engine(
+            engine.clone(),
+            "ffplayout.yml".to_string(),
+            receiver,
+            |_| Err(io::Error::other("no engine")),
+        ));
+
+        while engine.status.lock().unwrap().state != ProcessState::Restarting {
+            sleep(Duration::from_millis(10)).await;
+        }
+
+        sender.send(true).unwrap();
+        task.await.unwrap();
+
+        let status = engine.status.lock().unwrap().clone();
+
+        assert_eq!(status.state, ProcessState::Stopped);
+        assert_eq!(status.restarts, 1);
+        assert_eq!(status.exits.len(), 1);
+        assert_eq!(status.exits[0].message, "Start engine failed: no engine");
+    }
+}
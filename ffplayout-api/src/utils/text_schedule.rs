@@ -0,0 +1,490 @@
+/// Text Schedules
+///
+/// Send text presets automatically to the engines, for tickers and straps which run unattended.
+/// Every second the enabled schedules are checked against the current time in the channel timezone.
+/// When more than one schedule is active in a channel text layer, the newest one wins.
+use std::{
+    collections::HashMap,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use simplelog::*;
+use sqlx::{Pool, Sqlite};
+use tokio::time::interval;
+
+use crate::db::{
+    handles::{select_enabled_text_schedules, select_preset},
+    models::{TextPreset, TextSchedule},
+};
+use crate::utils::{control::send_message, errors::ServiceError, playout_config, CONFIG_CHANGES};
+use ffplayout_lib::utils::{local_now, Tz};
+
+/// State from the schedule which is currently running in a channel.
+struct Running {
+    schedule_id: i32,
+    index: usize,
+    next_switch: Instant,
+    failed: bool,
+}
+
+impl Running {
+    fn new(schedule_id: i32) -> Self {
+        Self {
+            schedule_id,
+            index: 0,
+            next_switch: Instant::now(),
+            failed: false,
+        }
+    }
+}
+
+fn parse_date_time(value: &str) -> Option<NaiveDateTime> {
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(value.trim(), f).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    ["%H:%M:%S", "%H:%M"]
+        .iter()
+        .find_map(|f| NaiveTime::parse_from_str(value.trim(), f).ok())
+}
+
+fn day_list(days: &str) -> Vec<u32> {
+    days.split(',')
+        .filter_map(|d| d.trim().parse().ok())
+        .collect()
+}
+
+/// Check the schedule values, before they get saved.
+pub async fn validate(conn: &Pool<Sqlite>, schedule: &TextSchedule) -> Result<(), ServiceError> {
+    if schedule.preset_ids().is_empty() {
+        return Err(ServiceError::BadRequest(
+            "Schedule needs at least one preset".to_string(),
+        ));
+    }
+
+    for id in schedule.preset_ids() {
+        match select_preset(conn, &id).await {
+            Ok(preset) if preset.channel_id == schedule.channel_id => (),
+            _ => {
+                return Err(ServiceError::BadRequest(format!(
+                    "Preset {id} not found in channel {}",
+                    schedule.channel_id
+                )))
+            }
+        }
+    }
+
+    for value in [&schedule.start_at, &schedule.end_at] {
+        if !value.is_empty() && parse_date_time(value).is_none() {
+            return Err(ServiceError::BadRequest(format!(
+                "Date \"{value}\" is not in format YYYY-MM-DD hh:mm:ss"
+            )));
+        }
+    }
+
+    for value in [&schedule.from_time, &schedule.to_time] {
+        if !value.is_empty() && parse_time(value).is_none() {
+            return Err(ServiceError::BadRequest(format!(
+                "Time \"{value}\" is not in format hh:mm:ss"
+            )));
+        }
+    }
+
+    if day_list(&schedule.days)
+        .iter()
+        .any(|d| !(1..=7).contains(d))
+    {
+        return Err(ServiceError::BadRequest(
+            "Days must be between 1 (Monday) and 7 (Sunday)".to_string(),
+        ));
+    }
+
+    if schedule.dwell < 1 || schedule.crawl_speed < 1 {
+        return Err(ServiceError::BadRequest(
+            "Dwell time and crawl speed must be greater than 0".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check if the schedule is active at the given local time.
+///
+/// When `to_time` is before `from_time`, the time window goes over midnight
+/// and belongs to the day on which it starts.
+pub fn is_active(schedule: &TextSchedule, now: NaiveDateTime) -> bool {
+    if parse_date_time(&schedule.start_at).is_some_and(|start| now < start)
+        || parse_date_time(&schedule.end_at).is_some_and(|end| now >= end)
+    {
+        return false;
+    }
+
+    let time = now.time();
+    let mut day = now.date();
+
+    let in_window = match (
+        parse_time(&schedule.from_time),
+        parse_time(&schedule.to_time),
+    ) {
+        (Some(from), Some(to)) if to <= from => {
+            if time < to {
+                day = day.pred_opt().unwrap_or(day);
+            }
+
+            time >= from || time < to
+        }
+        (from, to) => from.is_none_or(|f| time >= f) && to.is_none_or(|t| time < t),
+    };
+
+    let days = day_list(&schedule.days);
+
+    in_window && (days.is_empty() || days.contains(&day.weekday().number_from_monday()))
+}
+
+/// Create the text message for the engine from a preset.
+///
+/// In crawl mode the x position moves with the stream time from the right to the left border.
+pub fn preset_message(
+    preset: &TextPreset,
+    crawl_speed: Option<i64>,
+    layer: &str,
+) -> HashMap<String, String> {
+    let x = match crawl_speed {
+        Some(speed) => format!("w-mod(t*{speed},w+tw)"),
+        None => preset.x.clone(),
+    };
+    let layer = if layer.is_empty() {
+        &preset.layer
+    } else {
+        layer
+    };
+
+    let mut message = HashMap::from([
+        ("text".to_string(), preset.text.clone()),
+        ("x".to_string(), x),
+        ("y".to_string(), preset.y.clone()),
+        ("fontsize".to_string(), preset.fontsize.clone()),
+        ("line_spacing".to_string(), preset.line_spacing.clone()),
+        ("fontcolor".to_string(), preset.fontcolor.clone()),
+        ("box".to_string(), preset.r#box.clone()),
+        ("boxcolor".to_string(), preset.boxcolor.clone()),
+        ("boxborderw".to_string(), preset.boxborderw.clone()),
+        ("alpha".to_string(), preset.alpha.clone()),
+    ]);
+
+    if !layer.is_empty() {
+        message.insert("layer".to_string(), layer.to_string());
+    }
+
+    message
+}
+
+async fn send(
+    conn: &Pool<Sqlite>,
+    channel_id: i32,
+    message: HashMap<String, String>,
+) -> Result<(), ServiceError> {
+    let response = send_message(conn, channel_id, message).await?;
+
+    if !response.status().is_success() {
+        return Err(ServiceError::ServiceUnavailable(
+            response.text().await.unwrap_or_default(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Send the next preset from the schedule, when the dwell time is over.
+async fn switch_preset(conn: &Pool<Sqlite>, schedule: &TextSchedule, state: &mut Running) {
+    if Instant::now() < state.next_switch {
+        return;
+    }
+
+    if state.index == 0 {
+        info!(
+            "Start text schedule <b><magenta>{}</></b> in channel {}",
+            schedule.name, schedule.channel_id
+        );
+    }
+
+    let presets = schedule.preset_ids();
+    let index = state.index % presets.len();
+
+    // the message is sent again after every dwell time, also with only one preset,
+    // so the text comes back after an engine restart
+    state.index = index + 1;
+    state.next_switch = Instant::now() + Duration::from_secs(schedule.dwell as u64);
+
+    let preset = match select_preset(conn, &presets[index]).await {
+        Ok(preset) if preset.channel_id == schedule.channel_id => preset,
+        _ => {
+            error!(
+                "Text schedule <b><magenta>{}</></b>: preset {} not found",
+                schedule.name, presets[index]
+            );
+            return;
+        }
+    };
+
+    let crawl_speed = schedule.crawl.then_some(schedule.crawl_speed);
+
+    match send(
+        conn,
+        schedule.channel_id,
+        preset_message(&preset, crawl_speed, &schedule.layer),
+    )
+    .await
+    {
+        Ok(_) => {
+            debug!(
+                "Text schedule <b><magenta>{}</></b>: send preset <yellow>{}</>",
+                schedule.name, preset.name
+            );
+            state.failed = false;
+        }
+        Err(e) => {
+            // log only the first error, the engine is maybe not running
+            if !state.failed {
+                error!("Text schedule <b><magenta>{}</></b>: {e}", schedule.name);
+            }
+
+            state.failed = true;
+        }
+    }
+}
+
+/// Remove the text from the channel layer, after the schedule ended.
+async fn clear_text(conn: &Pool<Sqlite>, channel_id: i32, layer: &str) {
+    let mut message = HashMap::from([("text".to_string(), String::new())]);
+
+    if !layer.is_empty() {
+        message.insert("layer".to_string(), layer.to_string());
+    }
+
+    info!("Text schedule in channel {channel_id} ended");
+
+    if let Err(e) = send(conn, channel_id, message).await {
+        error!("Clear text in channel {channel_id}: {e}");
+    }
+}
+
+/// Run the text schedules from all channels, until ffpapi stops.
+///
+/// Schedules run per channel and text layer, so a ticker and a strap in different layers can run together.
+pub async fn run(conn: Pool<Sqlite>) {
+    let mut running: HashMap<(i32, String), Running> = HashMap::new();
+    let mut timezones: HashMap<i32, Option<Tz>> = HashMap::new();
+    let mut config_changes = usize::MAX;
+    let mut ticker = interval(Duration::from_secs(1));
+
+    loop {
+        ticker.tick().await;
+
+        // read the channel timezones again, only after a config was saved
+        let changes = CONFIG_CHANGES.load(Ordering::SeqCst);
+
+        if changes != config_changes {
+            timezones.clear();
+            config_changes = changes;
+        }
+
+        let schedules = match select_enabled_text_schedules(&conn).await {
+            Ok(schedules) => schedules,
+            Err(e) => {
+                error!("Read text schedules: {e}");
+                continue;
+            }
+        };
+
+        let mut layers: Vec<(i32, String)> = schedules
+            .iter()
+            .map(|s| (s.channel_id, s.layer.clone()))
+            .collect();
+        layers.dedup();
+
+        for (channel_id, layer) in layers {
+            let timezone = match timezones.get(&channel_id) {
+                Some(timezone) => *timezone,
+                None => match playout_config(&conn, &channel_id).await {
+                    Ok((config, _)) => {
+                        timezones.insert(channel_id, config.playlist.timezone);
+
+                        config.playlist.timezone
+                    }
+                    Err(_) => None,
+                },
+            };
+            let now = local_now(timezone);
+
+            let key = (channel_id, layer);
+
+            let Some(schedule) = schedules
+                .iter()
+                .rfind(|s| s.channel_id == channel_id && s.layer == key.1 && is_active(s, now))
+            else {
+                if running.remove(&key).is_some() {
+                    clear_text(&conn, channel_id, &key.1).await;
+                }
+
+                continue;
+            };
+
+            let state = running
+                .entry(key)
+                .or_insert_with(|| Running::new(schedule.id));
+
+            if state.schedule_id != schedule.id {
+                *state = Running::new(schedule.id);
+            }
+
+            switch_preset(&conn, schedule, state).await;
+        }
+
+        // layers without enabled schedules
+        let ended: Vec<(i32, String)> = running
+            .keys()
+            .filter(|(id, layer)| {
+                !schedules
+                    .iter()
+                    .any(|s| s.channel_id == *id && s.layer == *layer)
+            })
+            .cloned()
+            .collect();
+
+        for key in ended {
+            running.remove(&key);
+            clear_text(&conn, key.0, &key.1).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::db::{handles::insert_preset, migrations::migrate};
+
+    fn schedule(values: serde_json::Value) -> TextSchedule {
+        let mut schedule = json!({"channel_id": 1, "name": "test", "presets": [1]});
+        schedule
+            .as_object_mut()
+            .unwrap()
+            .extend(values.as_object().unwrap().clone());
+
+        serde_json::from_value(schedule).unwrap()
+    }
+
+    fn preset(channel_id: i32, layer: &str) -> TextPreset {
+        serde_json::from_value(json!({
+            "channel_id": channel_id,
+            "name": format!("preset {channel_id}"),
+            "text": "Breaking News",
+            "x": "10",
+            "y": "h-40",
+            "fontsize": 24,
+            "line_spacing": 4,
+            "fontcolor": "white",
+            "box": "1",
+            "boxcolor": "black@0.5",
+            "boxborderw": 4,
+            "alpha": 1,
+            "layer": layer,
+        }))
+        .unwrap()
+    }
+
+    fn at(value: &str) -> NaiveDateTime {
+        parse_date_time(value).unwrap()
+    }
+
+    #[test]
+    fn window_over_midnight_belongs_to_start_day() {
+        // 2024-01-01 is a Monday
+        let schedule = schedule(json!({"days": [1], "from_time": "22:00", "to_time": "02:00"}));
+
+        assert!(is_active(&schedule, at("2024-01-01 22:00:00")));
+        assert!(is_active(&schedule, at("2024-01-02 01:59:59")));
+        assert!(!is_active(&schedule, at("2024-01-01 01:00:00")));
+        assert!(!is_active(&schedule, at("2024-01-02 02:00:00")));
+        assert!(!is_active(&schedule, at("2024-01-02 23:00:00")));
+        assert!(!is_active(&schedule, at("2024-01-01 21:59:59")));
+    }
+
+    #[test]
+    fn start_and_end_limit_the_schedule() {
+        let schedule = schedule(json!({"start_at": "2024-01-01 10:00:00", "end_at": "2024-01-02"}));
+
+        assert!(!is_active(&schedule, at("2024-01-01 09:59:59")));
+        assert!(is_active(&schedule, at("2024-01-01 10:00:00")));
+        assert!(is_active(&schedule, at("2024-01-01 23:59:59")));
+        assert!(!is_active(&schedule, at("2024-01-02 00:00:00")));
+    }
+
+    #[test]
+    fn preset_message_with_crawl_and_layer() {
+        let message = preset_message(&preset(1, "ticker"), None, "");
+
+        assert_eq!(message["x"], "10");
+        assert_eq!(message["layer"], "ticker");
+        assert_eq!(message["text"], "Breaking News");
+
+        let message = preset_message(&preset(1, "ticker"), Some(50), "strap");
+
+        assert_eq!(message["x"], "w-mod(t*50,w+tw)");
+        assert_eq!(message["layer"], "strap");
+
+        assert!(!preset_message(&preset(1, ""), None, "").contains_key("layer"));
+    }
+
+    #[actix_web::test]
+    async fn validate_schedule_values() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        migrate(&pool, "").await.unwrap();
+
+        for name in ["Channel 1", "Channel 2"] {
+            sqlx::query("INSERT INTO channels (name, preview_url, config_path, extra_extensions, service) VALUES($1, '', '', '', $1)")
+                .bind(name)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        insert_preset(&pool, preset(1, "")).await.unwrap();
+        insert_preset(&pool, preset(2, "")).await.unwrap();
+
+        assert!(validate(&pool, &schedule(json!({}))).await.is_ok());
+
+        for values in [
+            json!({"presets": []}),
+            json!({"presets": [2]}),
+            json!({"presets": [3]}),
+            json!({"start_at": "01.01.2024"}),
+            json!({"to_time": "25:00"}),
+            json!({"days": [0, 8]}),
+            json!({"dwell": 0}),
+            json!({"crawl_speed": 0}),
+        ] {
+            assert!(
+                validate(&pool, &schedule(values.clone())).await.is_err(),
+                "{values} is valid"
+            );
+        }
+    }
+}
@@ -0,0 +1,265 @@
+/// Multi Channel Mode
+///
+/// Run multiple channels in one engine process, with: ffplayout --channels <CONFIG>...
+///
+/// Every channel has its own player, RPC server, status file and process control.
+/// Logging, mail and the advanced config are shared, the logging settings are taken
+/// from the first channel config. Log lines are marked with the channel name.
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use simplelog::*;
+
+use crate::{
+    output::{player, write_hls},
+    rpc::run_server,
+    utils::{get_config, status_file, Args, ConfigReloader},
+};
+use ffplayout_lib::utils::{
+    errors::ProcError, folder::fill_filler_list, spawn_thread, test_tcp_port, validate_ffmpeg,
+    OutputMode::*, PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl,
+};
+
+// The restart delay doubles on every failure, up to this limit
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+// Channels which run longer, restart again with the shortest delay
+const STABLE_RUNTIME: Duration = Duration::from_secs(60);
+
+pub struct Channel {
+    pub name: String,
+    pub args: Args,
+    pub config: PlayoutConfig,
+    pub play_control: PlayerControl,
+    pub playout_stat: PlayoutStatus,
+    pub proc_control: ProcessControl,
+}
+
+/// Get the arguments for one channel, other arguments are used from all channels.
+pub fn channel_args(args: &Args, config_path: &Path) -> Args {
+    let mut channel_args = args.clone();
+    channel_args.channels = None;
+    channel_args.config = Some(config_path.to_path_buf());
+
+    channel_args
+}
+
+/// Channel name from config file name.
+pub fn channel_name(config_path: &Path) -> String {
+    config_path
+        .file_stem()
+        .unwrap_or(config_path.as_os_str())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Read and validate the configs from all channels.
+pub fn load_channels(args: &Args, paths: &[PathBuf]) -> Result<Vec<Channel>, ProcError> {
+    let mut channels: Vec<Channel> = vec![];
+    let mut stat_files = HashSet::new();
+    let mut rpc_addresses = HashSet::new();
+
+    for path in paths {
+        let name = channel_name(path);
+
+        if !path.is_file() {
+            return Err(ProcError::Custom(format!(
+                "Config from channel \"{name}\" not found: {}",
+                path.display()
+            )));
+        }
+
+        if channels.iter().any(|c| c.name == name) {
+            return Err(ProcError::Custom(format!(
+                "Channel name \"{name}\" is used twice, config files need unique names"
+            )));
+        }
+
+        let channel_args = channel_args(args, path);
+        let mut config = get_config(channel_args.clone())?;
+
+        validate_ffmpeg(&mut config).map_err(|e| ProcError::Custom(format!("{name}: {e}")))?;
+
+        if !matches!(config.processing.audio_channels, 2 | 4 | 6 | 8) {
+            return Err(ProcError::Custom(format!(
+                "{name}: encoding {} channel(s) is not allowed. Only 2, 4, 6 and 8 channels are supported!",
+                config.processing.audio_channels
+            )));
+        }
+
+        if config.rpc_server.enable && !rpc_addresses.insert(config.rpc_server.address.clone()) {
+            return Err(ProcError::Custom(format!(
+                "{name}: RPC address {} is used from another channel",
+                config.rpc_server.address
+            )));
+        }
+
+        // channels without own status file would overwrite their states
+        if !stat_files.insert(config.general.stat_file.clone()) {
+            config.general.stat_file = format!("{}_{name}", config.general.stat_file);
+            stat_files.insert(config.general.stat_file.clone());
+        }
+
+        config.logging.channel_names = true;
+
+        let proc_control = ProcessControl::new();
+        proc_control.exit_on_error.store(false, Ordering::SeqCst);
+
+        channels.push(Channel {
+            name,
+            args: channel_args,
+            config,
+            play_control: PlayerControl::new(),
+            playout_stat: PlayoutStatus::new(),
+            proc_control,
+        });
+    }
+
+    Ok(channels)
+}
+
+/// Run one channel, until the playout is done.
+///
+/// After an unrecoverable error, the channel gets restarted with increasing delay.
+fn run_channel(channel: Channel, reloader: ConfigReloader) {
+    let config = channel.config;
+    let play_control = channel.play_control;
+    let playout_stat = channel.playout_stat;
+    let proc_control = channel.proc_control;
+
+    if config.rpc_server.enable {
+        if !test_tcp_port(&config.rpc_server.address) {
+            error!("Channel not started, RPC address is in use");
+            return;
+        }
+
+        let rpc_config = config.clone();
+        let rpc_play_ctl = play_control.clone();
+        let rpc_play_stat = playout_stat.clone();
+        let rpc_proc_ctl = proc_control.clone();
+
+        spawn_thread(move || {
+            run_server(
+                rpc_config,
+                rpc_play_ctl,
+                rpc_play_stat,
+                rpc_proc_ctl,
+                reloader,
+            )
+        });
+    }
+
+    if let Err(e) = status_file(&config.general.stat_file, &playout_stat) {
+        error!("Channel not started: {e}");
+        return;
+    }
+
+    debug!(
+        "Use config: <b><magenta>{}</></b>",
+        config.general.config_path
+    );
+
+    let filler_config = config.clone();
+    let filler_play_ctl = play_control.clone();
+
+    // Fill filler list, can also be a single file.
+    spawn_thread(move || {
+        fill_filler_list(&filler_config, Some(filler_play_ctl));
+    });
+
+    let mut delay = Duration::from_secs(1);
+
+    loop {
+        let started = Instant::now();
+
+        match config.out.mode {
+            HLS => write_hls(
+                &config,
+                play_control.clone(),
+                playout_stat.clone(),
+                proc_control.clone(),
+            ),
+            _ => player(
+                &config,
+                &play_control,
+                playout_stat.clone(),
+                proc_control.clone(),
+            ),
+        }
+
+        if !proc_control.is_failed.load(Ordering::SeqCst) {
+            break;
+        }
+
+        if started.elapsed() > STABLE_RUNTIME {
+            delay = Duration::from_secs(1);
+        }
+
+        error!("Playout failed, restart channel in {delay:?}");
+
+        sleep(delay);
+        delay = (delay * 2).min(MAX_RESTART_DELAY);
+
+        proc_control.reset();
+        playout_stat.list_init.store(true, Ordering::SeqCst);
+    }
+
+    info!("Playout done...");
+}
+
+/// Started channel, with its thread and config reloader.
+pub struct RunningChannel {
+    pub name: String,
+    pub reloader: ConfigReloader,
+    pub thread: JoinHandle<()>,
+}
+
+/// Start all channels, every channel runs in a thread with the channel name.
+pub fn start_channels(channels: Vec<Channel>) -> Vec<RunningChannel> {
+    let mut running = vec![];
+
+    for channel in channels {
+        let name = channel.name.clone();
+        let reloader = ConfigReloader::new(&channel.args, &channel.config, &channel.play_control);
+        let channel_reloader = reloader.clone();
+
+        info!("Start channel <b><magenta>{name}</></b>");
+
+        match thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || run_channel(channel, channel_reloader))
+        {
+            Ok(thread) => running.push(RunningChannel {
+                name,
+                reloader,
+                thread,
+            }),
+            Err(e) => error!("Start channel <b><magenta>{name}</></b> failed: {e}"),
+        }
+    }
+
+    running
+}
+
+/// Reload the configs from all channels.
+///
+/// Each reload runs in a thread with the channel name, to mark the log lines.
+pub fn reload_channels(reloaders: &[(String, ConfigReloader)]) {
+    for (name, reloader) in reloaders {
+        let reloader = reloader.clone();
+
+        let reload = thread::Builder::new().name(name.clone()).spawn(move || {
+            if let Err(e) = reloader.reload() {
+                error!("{e}");
+            }
+        });
+
+        if let Ok(handle) = reload {
+            let _ = handle.join();
+        }
+    }
+}
@@ -1,8 +1,7 @@
 use std::{
     io::{BufRead, BufReader, Error, Read},
-    process::{exit, ChildStderr, Command, Stdio},
+    process::{ChildStderr, Command, Stdio},
     sync::atomic::Ordering,
-    thread,
 };
 
 use crossbeam_channel::Sender;
@@ -11,8 +10,8 @@ use simplelog::*;
 use crate::utils::{log_line, valid_stream};
 use ffplayout_lib::{
     utils::{
-        controller::ProcessUnit::*, test_tcp_port, Media, PlayoutConfig, ProcessControl,
-        FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS,
+        controller::ProcessUnit::*, decoder_input_args, spawn_thread, test_tcp_port, Media,
+        PlayoutConfig, ProcessControl, FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS,
     },
     vec_strings, ADVANCED_CONFIG,
 };
@@ -58,44 +57,50 @@ pub fn ingest_server(
     proc_control: ProcessControl,
 ) -> Result<(), Error> {
     let mut buffer: [u8; 65088] = [0; 65088];
-    let mut server_cmd = vec_strings!["-hide_banner", "-nostats", "-v", "level+info"];
+    let mut server_prefix = vec_strings!["-hide_banner", "-nostats", "-v", "level+info"];
     let stream_input = config.ingest.input_cmd.clone().unwrap();
     let mut dummy_media = Media::new(0, "Live Stream", false);
     dummy_media.unit = Ingest;
-    dummy_media.add_filter(&config, &None);
 
-    if let Some(ingest_input_cmd) = &ADVANCED_CONFIG.ingest.input_cmd {
-        server_cmd.append(&mut ingest_input_cmd.clone());
-    }
-
-    server_cmd.append(&mut stream_input.clone());
+    server_prefix.append(&mut decoder_input_args(
+        &dummy_media,
+        &config,
+        &ADVANCED_CONFIG.ingest.input_cmd,
+    ));
 
-    if let Some(mut filter) = dummy_media.filter {
-        server_cmd.append(&mut filter.cmd());
-        server_cmd.append(&mut filter.map());
-    }
-
-    if let Some(mut cmd) = config.processing.cmd {
-        server_cmd.append(&mut cmd);
-    }
+    server_prefix.append(&mut stream_input.clone());
 
     let mut is_running;
 
     if let Some(url) = stream_input.iter().find(|s| s.contains("://")) {
         if !test_tcp_port(url) {
-            proc_control.stop_all();
-            exit(1);
+            proc_control.fail();
+
+            return Ok(());
         }
 
         info!("Start ingest server, listening on: <b><magenta>{url}</></b>",);
     };
 
-    debug!(
-        "Server CMD: <bright-blue>\"ffmpeg {}\"</>",
-        server_cmd.join(" ")
-    );
-
     while !proc_control.is_terminated.load(Ordering::SeqCst) {
+        // the filter is created new on every start, to get the current logo layer states
+        let mut server_cmd = server_prefix.clone();
+        dummy_media.add_filter(&config, &None);
+
+        if let Some(mut filter) = dummy_media.filter.clone() {
+            server_cmd.append(&mut filter.cmd());
+            server_cmd.append(&mut filter.map());
+        }
+
+        if let Some(mut cmd) = config.processing.cmd.clone() {
+            server_cmd.append(&mut cmd);
+        }
+
+        debug!(
+            "Server CMD: <bright-blue>\"ffmpeg {}\"</>",
+            server_cmd.join(" ")
+        );
+
         let proc_ctl = proc_control.clone();
         let level = config.logging.ingest_level.clone().unwrap();
         let ignore = config.logging.ignore_lines.clone();
@@ -114,7 +119,7 @@ pub fn ingest_server(
         let mut ingest_reader = BufReader::new(server_proc.stdout.take().unwrap());
         let server_err = BufReader::new(server_proc.stderr.take().unwrap());
         let error_reader_thread =
-            thread::spawn(move || server_monitor(&level, ignore, server_err, proc_ctl));
+            spawn_thread(move || server_monitor(&level, ignore, server_err, proc_ctl));
 
         *proc_control.server_term.lock().unwrap() = Some(server_proc);
         is_running = false;
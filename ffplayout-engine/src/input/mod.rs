@@ -1,11 +1,8 @@
-use std::{
-    sync::{atomic::AtomicBool, Arc},
-    thread,
-};
+use std::sync::{atomic::AtomicBool, Arc};
 
 use simplelog::*;
 
-use ffplayout_lib::utils::{Media, PlayoutConfig, PlayoutStatus, ProcessMode::*};
+use ffplayout_lib::utils::{spawn_thread, Media, PlayoutConfig, PlayoutStatus, ProcessMode::*};
 
 pub mod folder;
 pub mod ingest;
@@ -37,7 +34,7 @@ pub fn source_generator(
             let node_clone = folder_source.player_control.current_list.clone();
 
             // Spawn a thread to monitor folder for file changes.
-            thread::spawn(move || watchman(config_clone, is_terminated.clone(), node_clone));
+            spawn_thread(move || watchman(config_clone, is_terminated.clone(), node_clone));
 
             Box::new(folder_source) as Box<dyn Iterator<Item = Media>>
         }
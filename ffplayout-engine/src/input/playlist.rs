@@ -7,15 +7,19 @@ use std::{
     },
 };
 
+use chrono::Days;
 use serde_json::json;
 use simplelog::*;
 
 use ffplayout_lib::utils::{
     controller::PlayerControl,
+    days_between,
+    folder::best_fit_filler,
     gen_dummy, get_delta, is_close, is_remote,
     json_serializer::{read_json, set_defaults},
-    loop_filler, loop_image, modified_time, seek_and_length, time_in_seconds, JsonPlaylist, Media,
-    MediaProbe, PlayoutConfig, PlayoutStatus, IMAGE_FORMAT,
+    loop_filler, loop_image, modified_time, playlist_date, playlist_days, seek_and_length,
+    target_length, time_in_seconds, JsonPlaylist, Media, MediaProbe, PlayoutConfig, PlayoutStatus,
+    IMAGE_FORMAT,
 };
 
 /// Struct for current playlist.
@@ -60,6 +64,28 @@ impl CurrentProgram {
         }
     }
 
+    // Take over config changes, which got reloaded while playing.
+    // A new playlist path forces a new init, to read the playlist from there.
+    fn check_config_reload(&mut self) {
+        let Some(config) = self.player_control.config_reload.lock().unwrap().take() else {
+            return;
+        };
+
+        let new_playlist = self.config.playlist.path != config.playlist.path;
+
+        self.config.apply_reload(&config);
+
+        if new_playlist {
+            info!(
+                "Read playlists from: <b><magenta>{:?}</></b>",
+                self.config.playlist.path
+            );
+
+            self.json_playlist.path = None;
+            self.playout_stat.list_init.store(true, Ordering::SeqCst);
+        }
+    }
+
     // Check if there is no current playlist or file got updated,
     // and when is so load/reload it.
     fn load_or_update_playlist(&mut self, seek: bool) {
@@ -107,9 +133,47 @@ impl CurrentProgram {
         }
     }
 
+    // A multi day playlist goes on over the day change,
+    // so only the clip begins have to move to the new day.
+    // Returns true, when the running day is not the first one from the playlist.
+    fn next_playlist_day(&mut self) -> bool {
+        let Some(first_day) = self.config.playlist.first_day else {
+            return false;
+        };
+
+        let date = playlist_date(&self.config);
+
+        if date <= first_day || date >= first_day + Days::new(playlist_days(&self.config)) {
+            return false;
+        }
+
+        let start_sec = self.start_sec - days_between(&self.config, first_day, date);
+        let shift = self.json_playlist.start_sec.unwrap_or(start_sec) - start_sec;
+
+        if shift > 0.0 {
+            info!(
+                "Playlist goes on with day <yellow>{}</>",
+                (date - first_day).num_days() + 1
+            );
+
+            self.json_playlist.start_sec = Some(start_sec);
+            set_defaults(&mut self.json_playlist);
+
+            for node in self.player_control.current_list.lock().unwrap().iter_mut() {
+                node.begin = node.begin.map(|b| b - shift);
+            }
+
+            self.current_node.begin = self.current_node.begin.map(|b| b - shift);
+            self.set_status(self.json_playlist.date.clone());
+        }
+
+        true
+    }
+
     // Check if day is past and it is time for a new playlist.
     fn check_for_playlist(&mut self, seek: bool) -> bool {
-        let (delta, total_delta) = get_delta(&self.config, &time_in_seconds());
+        let goes_on = self.next_playlist_day();
+        let (delta, total_delta) = get_delta(&self.config, &time_in_seconds(&self.config));
         let mut next = false;
 
         let duration = if self.current_node.duration >= self.current_node.out {
@@ -127,19 +191,23 @@ impl CurrentProgram {
         let mut next_start =
             self.current_node.begin.unwrap_or_default() - self.start_sec + duration + delta;
 
+        // a full day has 23 or 25 hours on DST changes,
+        // and a multi day playlist ends after its last day
+        let end_sec = target_length(&self.config);
+
         if self.player_control.current_index.load(Ordering::SeqCst)
             == self.player_control.current_list.lock().unwrap().len() - 1
         {
             next_start += self.config.general.stop_threshold;
         }
 
-        trace!("next_start: {next_start}, end_sec: {}", self.end_sec);
+        trace!("next_start: {next_start}, end_sec: {end_sec}");
 
         // Check if we over the target length or we are close to it, if so we load the next playlist.
         if !self.config.playlist.infinit
-            && (next_start >= self.end_sec
+            && (next_start >= end_sec
                 || is_close(total_delta, 0.0, 2.0)
-                || is_close(total_delta, self.end_sec, 2.0))
+                || (!goes_on && is_close(total_delta, end_sec, 2.0)))
         {
             trace!("get next day");
             next = true;
@@ -205,7 +273,7 @@ impl CurrentProgram {
     // Get current time and when we are before start time,
     // we add full seconds of a day to it.
     fn get_current_time(&mut self) -> f64 {
-        let mut time_sec = time_in_seconds();
+        let mut time_sec = time_in_seconds(&self.config);
 
         if time_sec < self.start_sec {
             time_sec += 86400.0 // self.config.playlist.length_sec.unwrap();
@@ -305,12 +373,19 @@ impl CurrentProgram {
         // Fill end from playlist
         let index = self.player_control.current_index.load(Ordering::SeqCst);
         let mut media = Media::new(index, "", false);
-        media.begin = Some(time_in_seconds());
+        media.begin = Some(time_in_seconds(&self.config));
         media.duration = total_delta;
         media.out = total_delta;
 
         self.last_next_ad(&mut media);
 
+        // Add placeholder first, so that best fit fillers can be appended behind it.
+        self.player_control
+            .current_list
+            .lock()
+            .unwrap()
+            .push(media.clone());
+
         self.current_node = gen_source(
             &self.config,
             media,
@@ -319,11 +394,15 @@ impl CurrentProgram {
             0,
         );
 
-        self.player_control
+        if let Some(item) = self
+            .player_control
             .current_list
             .lock()
             .unwrap()
-            .push(self.current_node.clone());
+            .get_mut(index)
+        {
+            *item = self.current_node.clone();
+        }
 
         self.current_node.last_ad = self.last_node_ad;
         self.current_node
@@ -337,7 +416,7 @@ impl CurrentProgram {
     fn recalculate_begin(&mut self, extend: bool) {
         debug!("Infinit playlist reaches end, recalculate clip begins.");
 
-        let mut time_sec = time_in_seconds();
+        let mut time_sec = time_in_seconds(&self.config);
 
         if extend {
             time_sec = self.start_sec + self.json_playlist.length.unwrap();
@@ -354,6 +433,7 @@ impl Iterator for CurrentProgram {
     type Item = Media;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.check_config_reload();
         self.last_json_path = self.json_playlist.path.clone();
         self.last_node_ad = self.current_node.last_ad;
         self.check_for_playlist(self.playout_stat.list_init.load(Ordering::SeqCst));
@@ -371,7 +451,7 @@ impl Iterator for CurrentProgram {
                 // so we fill the gap with a dummy.
                 trace!("Init clip is no filler");
 
-                let mut current_time = time_in_seconds();
+                let mut current_time = time_in_seconds(&self.config);
                 let (_, total_delta) = get_delta(&self.config, &current_time);
 
                 if self.start_sec > current_time {
@@ -583,6 +663,70 @@ fn duplicate_for_seek_and_loop(node: &mut Media, player_control: &PlayerControl)
     }
 }
 
+/// Replace the node with a best fit combination of filler clips.
+///
+/// The first filler takes the place from the node, the others are inserted behind it.
+/// This works only when the node is part of the current list.
+fn insert_best_fit(
+    config: &PlayoutConfig,
+    node: &mut Media,
+    filler_list: &[Media],
+    duration: f64,
+    player_control: &PlayerControl,
+) -> bool {
+    let index = node.index.unwrap_or_default();
+    let mut nodes = player_control.current_list.lock().unwrap();
+
+    if index >= nodes.len() || nodes[index].source != node.source {
+        return false;
+    }
+
+    let last_used = player_control.last_fillers.lock().unwrap().clone();
+    let mut fillers = best_fit_filler(config, filler_list, duration, &last_used);
+
+    if fillers.is_empty() {
+        return false;
+    }
+
+    *player_control.last_fillers.lock().unwrap() =
+        fillers.iter().map(|f| f.source.clone()).collect();
+
+    let item = &nodes[index];
+    let mut begin = item.begin.unwrap_or_default() + item.out - item.seek - duration;
+    let first = fillers.remove(0);
+
+    node.begin = Some(begin);
+    node.source = first.source;
+    node.seek = 0.0;
+    node.out = first.out;
+    node.duration = first.duration;
+    node.probe = first.probe;
+    begin += node.out;
+
+    let mut fill_nodes = vec![node.clone()];
+
+    for mut filler in fillers {
+        filler.begin = Some(begin);
+        filler.process = Some(true);
+        begin += filler.out;
+
+        fill_nodes.push(filler);
+    }
+
+    debug!(
+        "Fill <yellow>{duration:.3}</> seconds with <yellow>{}</> best fit filler clips",
+        fill_nodes.len()
+    );
+
+    nodes.splice(index..=index, fill_nodes);
+
+    for (i, item) in nodes.iter_mut().enumerate() {
+        item.index = Some(i);
+    }
+
+    true
+}
+
 /// Generate the source CMD, or when clip not exist, get a dummy.
 pub fn gen_source(
     config: &PlayoutConfig,
@@ -651,7 +795,12 @@ pub fn gen_source(
         // Set list_init to true, to stay in sync.
         playout_stat.list_init.store(true, Ordering::SeqCst);
 
-        if config.storage.filler.is_dir() && !filler_list.is_empty() {
+        if config.storage.best_fit
+            && config.storage.filler.is_dir()
+            && insert_best_fit(config, &mut node, &filler_list, duration, player_control)
+        {
+            node.cmd = Some(seek_and_length(&mut node));
+        } else if config.storage.filler.is_dir() && !filler_list.is_empty() {
             let filler_index = player_control.filler_index.fetch_add(1, Ordering::SeqCst);
             let mut filler_media = filler_list[filler_index].clone();
 
@@ -2,72 +2,38 @@ use std::{
     fs::{self, File},
     path::PathBuf,
     process::exit,
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     thread,
 };
 
 #[cfg(debug_assertions)]
 use chrono::prelude::*;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
+#[cfg(not(windows))]
+use signal_hook::{consts::SIGHUP, iterator::Signals};
 use simplelog::*;
 
 use ffplayout::{
+    channels::{channel_args, channel_name, load_channels, reload_channels, start_channels},
     output::{player, write_hls},
     rpc::run_server,
-    utils::{arg_parse::get_args, get_config},
+    utils::{
+        arg_parse::get_args, check_config_source, get_config, status_file, Args, ConfigReloader,
+    },
 };
 
 use ffplayout_lib::utils::{
-    errors::ProcError, folder::fill_filler_list, generate_playlist, get_date, import::import_file,
-    init_logging, is_remote, send_mail, test_tcp_port, validate_ffmpeg, validate_playlist,
-    JsonPlaylist, OutputMode::*, PlayerControl, PlayoutStatus, ProcessControl,
+    bulk_playlists, errors::ProcError, folder::fill_filler_list, generate_playlist, get_date,
+    get_date_range, import::import_file, init_logging, is_remote, send_mail, test_tcp_port,
+    validate_ffmpeg, validate_playlist, BulkOperation, JsonPlaylist, OutputMode::*, PlayerControl,
+    PlayoutConfig, PlayoutStatus, ProcessControl,
 };
 
-#[cfg(debug_assertions)]
-use ffplayout::utils::Args;
-
 #[cfg(debug_assertions)]
 use ffplayout_lib::utils::{mock_time, time_now};
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
-
-#[derive(Serialize, Deserialize)]
-struct StatusData {
-    time_shift: f64,
-    date: String,
-}
-
-/// Here we create a status file in temp folder.
-/// We need this for reading/saving program status.
-/// For example when we skip a playing file,
-/// we save the time difference, so we stay in sync.
-///
-/// When file not exists we create it, and when it exists we get its values.
-fn status_file(stat_file: &str, playout_stat: &PlayoutStatus) -> Result<(), ProcError> {
-    debug!("Start ffplayout v{VERSION}, status file path: <b><magenta>{stat_file}</></b>");
-
-    if !PathBuf::from(stat_file).exists() {
-        let data = json!({
-            "time_shift": 0.0,
-            "date": String::new(),
-        });
-
-        let json: String = serde_json::to_string(&data)?;
-        if let Err(e) = fs::write(stat_file, json) {
-            error!("Unable to write to status file <b><magenta>{stat_file}</></b>: {e}");
-        };
-    } else {
-        let stat_file = File::options().read(true).write(false).open(stat_file)?;
-        let data: StatusData = serde_json::from_reader(stat_file)?;
-
-        *playout_stat.time_shift.lock().unwrap() = data.time_shift;
-        *playout_stat.date.lock().unwrap() = data.date;
-    }
-
-    Ok(())
-}
-
 /// Set fake time for debugging.
 /// When no time is given, we use the current time.
 /// When a time is given, we use this time instead.
@@ -81,6 +47,139 @@ fn fake_time(args: &Args) {
     }
 }
 
+/// Get bulk playlist operation from command line arguments.
+fn bulk_operation(args: &Args) -> Option<BulkOperation> {
+    if let Some(source) = &args.copy {
+        Some(BulkOperation::Copy {
+            source: source.clone(),
+            weekly: args.weekly,
+        })
+    } else if let Some(source) = &args.repeat {
+        Some(BulkOperation::Repeat {
+            source: source.clone(),
+        })
+    } else if let Some(days) = args.shift {
+        Some(BulkOperation::Shift { days })
+    } else if args.delete_range {
+        Some(BulkOperation::Delete)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(windows))]
+fn reload_on_hangup<F>(reload: F) -> Result<(), ProcError>
+where
+    F: Fn() + Send + 'static,
+{
+    let mut signals = Signals::new([SIGHUP])?;
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            info!("Got SIGHUP, reload config");
+
+            reload();
+        }
+    });
+
+    Ok(())
+}
+
+/// Check config and print the result, return true when the config is valid.
+fn print_config_check(args: &Args) -> Result<bool, ProcError> {
+    let check = check_config_source(args, true)?;
+
+    for warning in &check.warnings {
+        println!("[warning] {warning}");
+    }
+
+    for error in &check.errors {
+        eprintln!("[error] {error}");
+    }
+
+    if !check.is_valid() {
+        return Ok(false);
+    }
+
+    println!(
+        "Config is valid!\n\nDecoder: ffmpeg {}",
+        check.decoder_cmd.join(" ")
+    );
+
+    if !check.encoder_cmd.is_empty() {
+        println!("Encoder: ffmpeg {}", check.encoder_cmd.join(" "));
+    }
+
+    Ok(true)
+}
+
+/// Try to create logging folder, if not exist.
+fn create_log_folder(config: &PlayoutConfig) {
+    if config.logging.log_to_file
+        && !config.logging.path.is_dir()
+        && !config.logging.path.ends_with(".log")
+    {
+        if let Err(e) = fs::create_dir_all(&config.logging.path) {
+            eprintln!("Logging path not exists! {e}");
+
+            exit(1);
+        }
+    }
+}
+
+/// Run multiple channels in one process, the logging is shared between them.
+fn run_channels(args: &Args, paths: &[PathBuf]) -> Result<(), ProcError> {
+    let mut config = get_config(channel_args(args, &paths[0]))?;
+    config.logging.channel_names = true;
+    let proc_control = ProcessControl::new();
+    let messages = Arc::new(Mutex::new(Vec::new()));
+
+    create_log_folder(&config);
+
+    let logging = init_logging(&config, Some(proc_control.clone()), Some(messages.clone()));
+    CombinedLogger::init(logging)?;
+
+    let channels = match load_channels(args, paths) {
+        Ok(channels) => channels,
+        Err(e) => {
+            error!("{e}");
+            exit(1);
+        }
+    };
+
+    let running = start_channels(channels);
+
+    // reload all channel configs on SIGHUP
+    #[cfg(not(windows))]
+    {
+        let reloaders: Vec<(String, ConfigReloader)> = running
+            .iter()
+            .map(|c| (c.name.clone(), c.reloader.clone()))
+            .collect();
+
+        reload_on_hangup(move || reload_channels(&reloaders))?;
+    }
+
+    for channel in running {
+        if channel.thread.join().is_err() {
+            error!("Channel <b><magenta>{}</></b> crashed", channel.name);
+        }
+    }
+
+    info!("All channels done...");
+
+    // stop mail queue
+    proc_control.is_terminated.store(true, Ordering::SeqCst);
+
+    let msg = messages.lock().unwrap();
+
+    if !msg.is_empty() {
+        send_mail(&config, msg.join("\n"));
+    }
+
+    Ok(())
+}
+
 /// Main function.
 /// Here we check the command line arguments and start the player.
 /// We also start a JSON RPC server if enabled.
@@ -91,6 +190,31 @@ fn main() -> Result<(), ProcError> {
     #[cfg(debug_assertions)]
     fake_time(&args);
 
+    if args.check_config {
+        let valid = match &args.channels {
+            Some(paths) => {
+                let mut valid = true;
+
+                for path in paths {
+                    println!("Channel {}:", channel_name(path));
+
+                    valid &= print_config_check(&channel_args(&args, path))?;
+
+                    println!();
+                }
+
+                valid
+            }
+            None => print_config_check(&args)?,
+        };
+
+        exit(if valid { 0 } else { 1 });
+    }
+
+    if let Some(paths) = &args.channels {
+        return run_channels(&args, paths);
+    }
+
     let mut config = get_config(args.clone())?;
     let play_control = PlayerControl::new();
     let playout_stat = PlayoutStatus::new();
@@ -102,21 +226,40 @@ fn main() -> Result<(), ProcError> {
     let proc_ctl2 = proc_control.clone();
     let messages = Arc::new(Mutex::new(Vec::new()));
 
-    // try to create logging folder, if not exist
-    if config.logging.log_to_file
-        && !config.logging.path.is_dir()
-        && !config.logging.path.ends_with(".log")
-    {
-        if let Err(e) = fs::create_dir_all(&config.logging.path) {
-            eprintln!("Logging path not exists! {e}");
+    create_log_folder(&config);
+
+    let logging = init_logging(&config, Some(proc_ctl1), Some(messages.clone()));
+    CombinedLogger::init(logging)?;
 
+    if let Some(operation) = bulk_operation(&args) {
+        let Some(range) = &args.range else {
+            error!("Bulk playlist operations needs --range parameter!");
             exit(1);
+        };
+
+        // copy, repeat, shift or delete playlists in given date range
+        let dates = get_date_range(range);
+        let policy = args.exists.unwrap_or_default();
+
+        match bulk_playlists(&config, &operation, &dates, policy, args.dry_run) {
+            Ok(results) => {
+                for result in results {
+                    if args.dry_run {
+                        info!("<yellow>[dry run]</> {result}");
+                    } else {
+                        info!("{result}");
+                    }
+                }
+
+                exit(0);
+            }
+            Err(e) => {
+                error!("{e}");
+                exit(1);
+            }
         }
     }
 
-    let logging = init_logging(&config, Some(proc_ctl1), Some(messages.clone()));
-    CombinedLogger::init(logging)?;
-
     if let Err(e) = validate_ffmpeg(&mut config) {
         error!("{e}");
         exit(1);
@@ -124,6 +267,7 @@ fn main() -> Result<(), ProcError> {
 
     let config_clone1 = config.clone();
     let config_clone2 = config.clone();
+    let reloader = ConfigReloader::new(&args, &config, &play_control);
 
     if !matches!(config.processing.audio_channels, 2 | 4 | 6 | 8) {
         error!(
@@ -165,7 +309,7 @@ fn main() -> Result<(), ProcError> {
         let play_ctl3 = play_control.clone();
         let mut playlist_path = config.playlist.path.clone();
         let start_sec = config.playlist.start_sec.unwrap();
-        let date = get_date(false, start_sec, false);
+        let date = get_date(config.playlist.timezone, false, start_sec, false);
 
         if playlist_path.is_dir() || is_remote(&playlist_path.to_string_lossy()) {
             let d: Vec<&str> = date.split('-').collect();
@@ -200,9 +344,21 @@ fn main() -> Result<(), ProcError> {
             exit(1)
         }
 
-        thread::spawn(move || run_server(config_clone1, play_ctl1, play_stat, proc_ctl2));
+        let rpc_reloader = reloader.clone();
+
+        thread::spawn(move || {
+            run_server(config_clone1, play_ctl1, play_stat, proc_ctl2, rpc_reloader)
+        });
     }
 
+    // reload config on SIGHUP, like: kill -HUP <PID>
+    #[cfg(not(windows))]
+    reload_on_hangup(move || {
+        if let Err(e) = reloader.reload() {
+            error!("{e}");
+        }
+    })?;
+
     status_file(&config.general.stat_file, &playout_stat)?;
 
     debug!(
@@ -226,7 +382,7 @@ fn main() -> Result<(), ProcError> {
 
     let msg = messages.lock().unwrap();
 
-    if msg.len() > 0 {
+    if !msg.is_empty() {
         send_mail(&config, msg.join("\n"));
     }
 
@@ -19,20 +19,21 @@ out:
 
 use std::{
     io::{BufRead, BufReader, Error},
-    process::{exit, Command, Stdio},
+    process::{Command, Stdio},
     sync::atomic::Ordering,
-    thread::{self, sleep},
+    thread::sleep,
     time::Duration,
 };
 
 use simplelog::*;
 
 use crate::input::source_generator;
-use crate::utils::{log_line, prepare_output_cmd, task_runner, valid_stream};
+use crate::utils::{log_line, prepare_output_cmd, secondary_events, task_runner, valid_stream};
 use ffplayout_lib::{
     utils::{
-        controller::ProcessUnit::*, get_delta, sec_to_time, stderr_reader, test_tcp_port, Media,
-        PlayerControl, PlayoutConfig, PlayoutStatus, ProcessControl,
+        controller::ProcessUnit::*, decoder_input_args, get_delta, sec_to_time, spawn_thread,
+        stderr_reader, test_tcp_port, Media, PlayerControl, PlayoutConfig, PlayoutStatus,
+        ProcessControl,
     },
     vec_strings, ADVANCED_CONFIG,
 };
@@ -45,23 +46,33 @@ fn ingest_to_hls_server(
 ) -> Result<(), Error> {
     let playlist_init = playout_stat.list_init;
 
-    let mut server_prefix = vec_strings!["-hide_banner", "-nostats", "-threads", "4", "-hwaccel", "cuvid", "-c:v", "h264_cuvid", "-fix_sub_duration", "-drop_second_field", "true", "-v", "level+info"];
+    let mut server_prefix = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-threads",
+        "4",
+        "-fix_sub_duration",
+        "-v",
+        "level+info"
+    ];
     let stream_input = config.ingest.input_cmd.clone().unwrap();
     let mut dummy_media = Media::new(0, "Live Stream", false);
     dummy_media.unit = Ingest;
 
-    if let Some(ingest_input_cmd) = &ADVANCED_CONFIG.ingest.input_cmd {
-        server_prefix.append(&mut ingest_input_cmd.clone());
-    }
-
+    server_prefix.append(&mut decoder_input_args(
+        &dummy_media,
+        &config,
+        &ADVANCED_CONFIG.ingest.input_cmd,
+    ));
     server_prefix.append(&mut stream_input.clone());
 
     let mut is_running;
 
     if let Some(url) = stream_input.iter().find(|s| s.contains("://")) {
         if !test_tcp_port(url) {
-            proc_control.stop_all();
-            exit(1);
+            proc_control.fail();
+
+            return Ok(());
         }
 
         info!("Start ingest server, listening on: <b><magenta>{url}</></b>");
@@ -161,7 +172,7 @@ pub fn write_hls(
 
     // spawn a thread for ffmpeg ingest server and create a channel for package sending
     if config.ingest.enable {
-        thread::spawn(move || ingest_to_hls_server(config_clone, play_stat, proc_control_c));
+        spawn_thread(move || ingest_to_hls_server(config_clone, play_stat, proc_control_c));
     }
 
     for node in get_source {
@@ -178,11 +189,14 @@ pub fn write_hls(
         }
 
         info!(
-            "Play for <yellow>{}</>: <b><magenta>{}</></b>",
+            "Play for <yellow>{}</>: <b><magenta>{}</></b> (<yellow>{}</>)",
             sec_to_time(node.out - node.seek),
-            node.source
+            node.source,
+            node.get_title(config)
         );
 
+        secondary_events::start(config, &node, &player_control, &play_stat2, &proc_control);
+
         if config.task.enable {
             if config.task.path.is_file() {
                 let task_config = config.clone();
@@ -190,7 +204,7 @@ pub fn write_hls(
                 let server_running = proc_control.server_is_running.load(Ordering::SeqCst);
                 let stat = play_stat2.clone();
 
-                thread::spawn(move || {
+                spawn_thread(move || {
                     task_runner::run(task_config, task_node, stat, server_running)
                 });
             } else {
@@ -203,9 +217,11 @@ pub fn write_hls(
 
         let mut enc_prefix = vec_strings!["-hide_banner", "-nostats", "-v", &ff_log_format];
 
-        if let Some(encoder_input_cmd) = &ADVANCED_CONFIG.encoder.input_cmd {
-            enc_prefix.append(&mut encoder_input_cmd.clone());
-        }
+        enc_prefix.append(&mut decoder_input_args(
+            &node,
+            config,
+            &ADVANCED_CONFIG.encoder.input_cmd,
+        ));
 
         let mut read_rate = 1.0;
 
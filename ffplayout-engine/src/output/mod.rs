@@ -2,7 +2,7 @@ use std::{
     io::{prelude::*, BufReader, BufWriter, Read},
     process::{Command, Stdio},
     sync::atomic::Ordering,
-    thread::{self, sleep},
+    thread::sleep,
     time::Duration,
 };
 
@@ -17,13 +17,13 @@ mod stream;
 pub use hls::write_hls;
 
 use crate::input::{ingest_server, source_generator};
-use crate::utils::task_runner;
+use crate::utils::{secondary_events, task_runner};
 
 use ffplayout_lib::vec_strings;
 use ffplayout_lib::{
     utils::{
-        sec_to_time, stderr_reader, OutputMode::*, PlayerControl, PlayoutConfig, PlayoutStatus,
-        ProcessControl, ProcessUnit::*,
+        decoder_input_args, sec_to_time, spawn_thread, stderr_reader, OutputMode::*, PlayerControl,
+        PlayoutConfig, PlayoutStatus, ProcessControl, ProcessUnit::*,
     },
     ADVANCED_CONFIG,
 };
@@ -75,7 +75,7 @@ pub fn player(
 
     // spawn a thread to log ffmpeg output error messages
     let error_encoder_thread =
-        thread::spawn(move || stderr_reader(enc_err, ignore_enc, Encoder, enc_p_ctl));
+        spawn_thread(move || stderr_reader(enc_err, ignore_enc, Encoder, enc_p_ctl));
 
     let proc_control_c = proc_control.clone();
     let mut ingest_receiver = None;
@@ -84,7 +84,7 @@ pub fn player(
     if config.ingest.enable {
         let (ingest_sender, rx) = bounded(96);
         ingest_receiver = Some(rx);
-        thread::spawn(move || ingest_server(config_clone, ingest_sender, proc_control_c));
+        spawn_thread(move || ingest_server(config_clone, ingest_sender, proc_control_c));
     }
 
     'source_iter: for node in node_sources {
@@ -121,12 +121,15 @@ pub fn player(
         };
 
         info!(
-            "Play for <yellow>{}</>{c_index}: <b><magenta>{}  {}</></b>",
+            "Play for <yellow>{}</>{c_index}: <b><magenta>{}  {}</></b> (<yellow>{}</>)",
             sec_to_time(node.out - node.seek),
             node.source,
-            node.audio
+            node.audio,
+            node.get_title(config)
         );
 
+        secondary_events::start(config, &node, play_control, &play_stat, &proc_control);
+
         if config.task.enable {
             if config.task.path.is_file() {
                 let task_config = config.clone();
@@ -134,7 +137,7 @@ pub fn player(
                 let server_running = proc_control.server_is_running.load(Ordering::SeqCst);
                 let stat = play_stat.clone();
 
-                thread::spawn(move || {
+                spawn_thread(move || {
                     task_runner::run(task_config, task_node, stat, server_running)
                 });
             } else {
@@ -147,10 +150,11 @@ pub fn player(
 
         let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-v", &ff_log_format];
 
-        if let Some(decoder_input_cmd) = &ADVANCED_CONFIG.decoder.input_cmd {
-            dec_cmd.append(&mut decoder_input_cmd.clone());
-        }
-
+        dec_cmd.append(&mut decoder_input_args(
+            &node,
+            config,
+            &ADVANCED_CONFIG.decoder.input_cmd,
+        ));
         dec_cmd.append(&mut cmd);
 
         if let Some(mut filter) = node.filter {
@@ -188,7 +192,7 @@ pub fn player(
         let dec_p_ctl = proc_control.clone();
 
         let error_decoder_thread =
-            thread::spawn(move || stderr_reader(dec_err, ignore_dec, Decoder, dec_p_ctl));
+            spawn_thread(move || stderr_reader(dec_err, ignore_dec, Decoder, dec_p_ctl));
 
         loop {
             // when server is running, read from it
@@ -1,5 +1,5 @@
 mod server;
 mod zmq_cmd;
 
-pub use server::run_server;
+pub use server::{run_server, text_from_json};
 pub use zmq_cmd::zmq_send;
@@ -1,4 +1,4 @@
-use std::{fmt, sync::atomic::Ordering};
+use std::{fmt, sync::atomic::Ordering, thread, time::Duration};
 
 use regex::Regex;
 extern crate serde;
@@ -8,7 +8,7 @@ extern crate tiny_http;
 use futures::executor::block_on;
 use serde::{
     de::{self, Visitor},
-    Deserialize, Serialize,
+    Deserialize,
 };
 use serde_json::{json, Map};
 use simplelog::*;
@@ -17,10 +17,13 @@ use std::io::{Cursor, Error as IoError};
 use tiny_http::{Header, Method, Request, Response, Server};
 
 use crate::rpc::zmq_send;
-use crate::utils::{get_data_map, get_media_map};
-use ffplayout_lib::utils::{
-    get_delta, write_status, Ingest, OutputMode::*, PlayerControl, PlayoutConfig, PlayoutStatus,
-    ProcessControl,
+use crate::utils::{get_data_map, get_media_map, ConfigReloader};
+use ffplayout_lib::{
+    filter::v_drawtext,
+    utils::{
+        get_delta, write_status, Ingest, OutputMode::*, PlayerControl, PlayoutConfig,
+        PlayoutStatus, ProcessControl,
+    },
 };
 
 #[derive(Default, Deserialize, Clone, Debug)]
@@ -42,6 +45,7 @@ struct TextFilter {
     boxcolor: Option<String>,
     #[serde(default, deserialize_with = "deserialize_number_or_string")]
     boxborderw: Option<String>,
+    layer: Option<String>,
 }
 
 /// Deserialize number or string
@@ -143,16 +147,20 @@ impl fmt::Display for TextFilter {
     }
 }
 
-/// Covert JSON string to ffmpeg filter command.
-fn filter_from_json(raw_text: serde_json::Value) -> String {
+/// Covert JSON string to zmq target from the text layer and ffmpeg filter command.
+pub fn text_from_json(
+    config: &PlayoutConfig,
+    raw_text: serde_json::Value,
+) -> Result<(String, String), String> {
     let filter: TextFilter = serde_json::from_value(raw_text).unwrap_or_default();
 
-    filter.to_string()
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct ResponseData {
-    message: String,
+    match config.text.layer_target(filter.layer.as_deref()) {
+        Some(target) => Ok((target, filter.to_string())),
+        None => Err(format!(
+            "Text layer '{}' not found!",
+            filter.layer.unwrap_or_default()
+        )),
+    }
 }
 
 /// Read the request body and convert it to a string
@@ -224,7 +232,7 @@ fn control_back(
 
             data_map.insert("operation".to_string(), json!("move_to_last"));
             data_map.insert("shifted_seconds".to_string(), json!(delta));
-            data_map.insert("media".to_string(), get_media_map(media));
+            data_map.insert("media".to_string(), get_media_map(config, media));
 
             return json_response(data_map);
         }
@@ -273,7 +281,7 @@ fn control_next(
 
             data_map.insert("operation".to_string(), json!("move_to_next"));
             data_map.insert("shifted_seconds".to_string(), json!(delta));
-            data_map.insert("media".to_string(), get_media_map(media));
+            data_map.insert("media".to_string(), get_media_map(config, media));
 
             return json_response(data_map);
         }
@@ -329,6 +337,25 @@ fn control_stop(proc: &ProcessControl) -> Response<Cursor<Vec<u8>>> {
     json_response(data_map)
 }
 
+/// control playout: reload config from file, or from ffpapi
+fn control_reload(reloader: &ConfigReloader) -> Response<Cursor<Vec<u8>>> {
+    match reloader.reload() {
+        Ok(changes) => {
+            let mut data_map = Map::new();
+            data_map.insert("operation".to_string(), json!("reload_config"));
+            data_map.insert("applied".to_string(), json!(changes.applied));
+            data_map.insert("restart".to_string(), json!(changes.restart));
+
+            json_response(data_map)
+        }
+        Err(e) => {
+            error!("{e}");
+
+            error_response(&e.to_string(), 400)
+        }
+    }
+}
+
 /// control playout: create text filter for ffmpeg
 fn control_text(
     data: HashMap<String, serde_json::Value>,
@@ -337,18 +364,21 @@ fn control_text(
     proc: &ProcessControl,
 ) -> Response<Cursor<Vec<u8>>> {
     if data.contains_key("message") {
-        let filter = filter_from_json(data["message"].clone());
-        debug!("Got drawtext command: <bright-blue>\"{filter}\"</>");
+        let (target, filter) = match text_from_json(config, data["message"].clone()) {
+            Ok(text) => text,
+            Err(e) => return error_response(&e, 400),
+        };
+        debug!("Got drawtext command: <bright-blue>\"{target} {filter}\"</>");
         let mut data_map = Map::new();
 
         if !filter.is_empty() && config.text.zmq_stream_socket.is_some() {
-            if let Some(clips_filter) = playout_stat.chain.clone() {
-                *clips_filter.lock().unwrap() = vec![filter.clone()];
+            if let Some(clips_filter) = &playout_stat.chain {
+                v_drawtext::store_text(clips_filter, &target, &filter);
             }
 
             if config.out.mode == HLS {
                 if proc.server_is_running.load(Ordering::SeqCst) {
-                    let filter_server = format!("drawtext@dyntext reinit {filter}");
+                    let filter_server = format!("{target} reinit {filter}");
 
                     if let Ok(reply) = block_on(zmq_send(
                         &filter_server,
@@ -363,7 +393,7 @@ fn control_text(
             }
 
             if config.out.mode != HLS || !proc.server_is_running.load(Ordering::SeqCst) {
-                let filter_stream = format!("drawtext@dyntext reinit {filter}");
+                let filter_stream = format!("{target} reinit {filter}");
 
                 if let Ok(reply) = block_on(zmq_send(
                     &filter_stream,
@@ -379,6 +409,132 @@ fn control_text(
     error_response("text message missing!", 400)
 }
 
+/// Send a filter command to the logo layers from the decoder.
+///
+/// The decoder is not always listening, for example while a live ingest is running,
+/// so the command waits only a short time for an answer.
+fn logo_command(cmd: String, socket: String) -> Option<String> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+
+    thread::spawn(move || {
+        let reply = block_on(zmq_send(&cmd, &socket)).map_err(|e| e.to_string());
+        let _ = sender.send(reply);
+    });
+
+    match receiver.recv_timeout(Duration::from_secs(2)) {
+        Ok(Ok(reply)) => Some(reply),
+        Ok(Err(e)) => {
+            debug!("Logo command failed: {e}");
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// control playout: show, hide, toggle or swap logo layers
+///
+/// The state is kept for the next clips, until it is set back to `auto`.
+fn control_logo(
+    data: HashMap<String, serde_json::Value>,
+    config: &PlayoutConfig,
+    reloader: &ConfigReloader,
+) -> Response<Cursor<Vec<u8>>> {
+    let Some(name) = data.get("name").and_then(|n| n.as_str()) else {
+        return error_response("logo name missing!", 400);
+    };
+    let state = data
+        .get("state")
+        .and_then(|s| s.as_str())
+        .unwrap_or("toggle");
+    let replace = data
+        .get("replace")
+        .and_then(|r| r.as_str())
+        .filter(|r| !r.is_empty());
+    let layers = reloader.logo_layers();
+
+    for layer_name in [Some(name), replace].into_iter().flatten() {
+        if !layers.iter().any(|l| l.name == layer_name) {
+            return error_response(&format!("Logo layer '{layer_name}' not found!"), 400);
+        }
+    }
+
+    let mut changes = vec![];
+
+    {
+        let mut overrides = config.processing.logo_overrides.lock().unwrap();
+
+        let visible = match state {
+            "on" => Some(true),
+            "off" => Some(false),
+            "toggle" => {
+                let enable = layers.iter().any(|l| l.name == name && l.enable);
+                Some(!overrides.get(name).copied().unwrap_or(enable))
+            }
+            "auto" => None,
+            _ => return error_response(&format!("Logo state '{state}' is not supported!"), 400),
+        };
+
+        match visible {
+            Some(v) => {
+                overrides.insert(name.to_string(), v);
+                changes.push((name.to_string(), v));
+            }
+            None => {
+                overrides.remove(name);
+            }
+        }
+
+        if let Some(replace) = replace.filter(|r| *r != name) {
+            overrides.insert(replace.to_string(), false);
+            changes.push((replace.to_string(), false));
+        }
+    }
+
+    info!(
+        "Switch logo layer <b><magenta>{name}</></b> to <yellow>{state}</>{}",
+        replace
+            .map(|r| format!(", hide <b><magenta>{r}</></b>"))
+            .unwrap_or_default()
+    );
+
+    let mut data_map = Map::new();
+    let mut message = "state is applied with the next clip".to_string();
+
+    if let Some(socket) = &config.processing.logo_zmq_socket {
+        for (layer, visible) in changes {
+            let cmd = format!(
+                "colorchannelmixer@logo_{layer} enable {}",
+                u8::from(!visible)
+            );
+            debug!("Send logo command: <bright-blue>\"{cmd}\"</>");
+
+            if let Some(reply) = logo_command(cmd, socket.clone()) {
+                message = reply;
+            }
+        }
+    }
+
+    let overrides = config.processing.logo_overrides.lock().unwrap();
+    let states: Map<String, serde_json::Value> = layers
+        .iter()
+        .map(|l| {
+            let state = match overrides.get(&l.name) {
+                Some(true) => "on",
+                Some(false) => "off",
+                None => "auto",
+            };
+
+            (l.name.clone(), json!(state))
+        })
+        .collect();
+
+    data_map.insert("operation".to_string(), json!("logo"));
+    data_map.insert("message".to_string(), json!(message));
+    data_map.insert("layers".to_string(), json!(states));
+
+    json_response(data_map)
+}
+
 /// media info: get infos about current clip
 fn media_current(
     config: &PlayoutConfig,
@@ -448,6 +604,7 @@ fn build_response(
     play_control: &PlayerControl,
     playout_stat: &PlayoutStatus,
     proc_control: &ProcessControl,
+    reloader: &ConfigReloader,
 ) {
     if let Ok(body) = read_request_body(&mut request) {
         if let Ok(data) = serde_json::from_str::<HashMap<String, serde_json::Value>>(&body) {
@@ -475,10 +632,16 @@ fn build_response(
                     "stop_all" => {
                         let _ = request.respond(control_stop(proc_control));
                     }
+                    "reload" => {
+                        let _ = request.respond(control_reload(reloader));
+                    }
                     "text" => {
                         let _ =
                             request.respond(control_text(data, config, playout_stat, proc_control));
                     }
+                    "logo" => {
+                        let _ = request.respond(control_logo(data, config, reloader));
+                    }
                     _ => (),
                 }
             } else if let Some(media_value) = data.get("media").and_then(|m| m.as_str()) {
@@ -520,6 +683,7 @@ fn handle_request(
     play_control: &PlayerControl,
     playout_stat: &PlayoutStatus,
     proc_control: &ProcessControl,
+    reloader: &ConfigReloader,
 ) {
     // Check Authorization-Header
     match request
@@ -532,7 +696,14 @@ fn handle_request(
 
             if auth_value == config.rpc_server.authorization {
                 // create and send response
-                build_response(request, config, play_control, playout_stat, proc_control)
+                build_response(
+                    request,
+                    config,
+                    play_control,
+                    playout_stat,
+                    proc_control,
+                    reloader,
+                )
             } else {
                 let _ = request.respond(error_response("Unauthorized", 401));
             }
@@ -551,11 +722,13 @@ fn handle_request(
 /// - jump to next clip
 /// - get last clip
 /// - reset player state to original clip
+/// - reload config
 pub fn run_server(
     config: PlayoutConfig,
     play_control: PlayerControl,
     playout_stat: PlayoutStatus,
     proc_control: ProcessControl,
+    reloader: ConfigReloader,
 ) {
     let addr = config.rpc_server.address.clone();
 
@@ -571,6 +744,7 @@ pub fn run_server(
                 &play_control,
                 &playout_stat,
                 &proc_control,
+                &reloader,
             ),
             _ => {
                 // Method not allowed
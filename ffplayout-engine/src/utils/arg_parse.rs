@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
-use ffplayout_lib::utils::{OutputMode, ProcessMode};
+use ffplayout_lib::utils::{ExistsPolicy, OutputMode, ProcessMode};
 
 #[derive(Parser, Debug, Clone)]
 #[clap(version,
@@ -17,6 +17,27 @@ pub struct Args {
     #[clap(short, long, help = "File path to ffplayout.yml")]
     pub config: Option<PathBuf>,
 
+    #[clap(
+        long,
+        help = "Get config from ffpapi, like: http://127.0.0.1:8787/api/playout/config/1/export",
+        conflicts_with_all = ["channel", "config"],
+    )]
+    pub config_url: Option<String>,
+
+    #[clap(
+        long,
+        help = "Run multiple channels in one process, with their config files",
+        num_args = 1..,
+        conflicts_with_all = ["channel", "config", "config_url"],
+    )]
+    pub channels: Option<Vec<PathBuf>>,
+
+    #[clap(
+        long,
+        help = "Check config, paths and ffmpeg commands, without starting the playout"
+    )]
+    pub check_config: bool,
+
     #[clap(short, long, help = "File path for logging")]
     pub log: Option<PathBuf>,
 
@@ -31,6 +52,23 @@ pub struct Args {
     #[clap(long, help = "fake date time, for debugging")]
     pub fake_time: Option<String>,
 
+    #[clap(
+        long,
+        help = "Copy playlist from date to --range",
+        value_name = "YYYY-MM-DD",
+        conflicts_with_all = ["repeat", "shift", "delete_range"],
+    )]
+    pub copy: Option<String>,
+
+    #[clap(long, help = "Only copy to dates with the same weekday")]
+    pub weekly: bool,
+
+    #[clap(long, help = "Delete all playlists in --range")]
+    pub delete_range: bool,
+
+    #[clap(long, help = "Only print what bulk playlist operations would do")]
+    pub dry_run: bool,
+
     #[clap(short, long, help = "Play folder content")]
     pub folder: Option<PathBuf>,
 
@@ -49,6 +87,18 @@ pub struct Args {
     )]
     pub import: Option<PathBuf>,
 
+    #[clap(long, help = "Policy for existing playlists: skip, overwrite")]
+    pub exists: Option<ExistsPolicy>,
+
+    #[clap(
+        long,
+        help = "Generate playlists with exact length, within this tolerance in seconds",
+        value_name = "TOLERANCE",
+        num_args = 0..=1,
+        default_missing_value = "1.0"
+    )]
+    pub exact_length: Option<f64>,
+
     #[clap(short, long, help = "Loop playlist infinitely")]
     pub infinit: bool,
 
@@ -71,6 +121,29 @@ pub struct Args {
     #[clap(short, long, help = "Path to playlist, or playlist root folder.")]
     pub playlist: Option<PathBuf>,
 
+    #[clap(
+        long,
+        help = "Target dates for bulk playlist operations, like: 2022-01-01 - 2022-01-10",
+        num_args = 1..,
+    )]
+    pub range: Option<Vec<String>>,
+
+    #[clap(
+        long,
+        help = "Repeat the week, which starts at this date, over --range",
+        value_name = "YYYY-MM-DD",
+        conflicts_with_all = ["shift", "delete_range"],
+    )]
+    pub repeat: Option<String>,
+
+    #[clap(
+        long,
+        help = "Move playlists in --range by given days",
+        allow_hyphen_values = true,
+        conflicts_with = "delete_range"
+    )]
+    pub shift: Option<i64>,
+
     #[clap(
         short,
         long,
@@ -1,28 +1,60 @@
 use std::{
-    fs::File,
+    env,
+    fs::{self, File},
     path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Mutex},
 };
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use simplelog::*;
 
 pub mod arg_parse;
+pub mod secondary_events;
 pub mod task_runner;
 
 pub use arg_parse::Args;
+pub use ffplayout_lib::utils::prepare_output_cmd;
+
 use ffplayout_lib::{
-    filter::Filters,
     utils::{
-        config::Template, errors::ProcError, parse_log_level_filter, sec_to_time, time_in_seconds,
-        time_to_sec, Media, OutputMode::*, PlayoutConfig, PlayoutStatus, ProcessMode::*,
+        check_config, config::Template, day_start_to_sec, errors::ProcError,
+        folder::fill_filler_list, parse_log_level_filter, sec_to_time, time_in_seconds,
+        time_to_sec, ConfigChanges, ConfigCheck, LogoLayer, Media, OutputMode::*, PlayerControl,
+        PlayoutConfig, PlayoutStatus, ProcessMode::*,
     },
     vec_strings,
 };
 
-/// Read command line arguments, and override the config with them.
-pub fn get_config(args: Args) -> Result<PlayoutConfig, ProcError> {
-    let cfg_path = match args.channel {
+/// Get the channel config content from ffpapi.
+///
+/// The API token is read from the environment variable `FFPLAYOUT_API_TOKEN`.
+fn fetch_config(url: &str) -> Result<String, ProcError> {
+    let mut request = reqwest::blocking::Client::new().get(url);
+
+    if let Ok(token) = env::var("FFPLAYOUT_API_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+
+    let content = request
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.text())
+        .map_err(|e| ProcError::Custom(format!("Get config from {url} failed: {e}")))?;
+
+    if content.trim().is_empty() {
+        return Err(ProcError::Custom(format!(
+            "Get config from {url} failed: channel has no config"
+        )));
+    }
+
+    Ok(content)
+}
+
+/// Get config path from channel name, or from the config argument.
+fn config_path(args: &Args) -> Result<Option<PathBuf>, ProcError> {
+    match &args.channel {
         Some(c) => {
             let path = PathBuf::from(format!("/etc/ffplayout/{c}.yml"));
 
@@ -32,17 +64,162 @@ pub fn get_config(args: Args) -> Result<PlayoutConfig, ProcError> {
                 )));
             }
 
-            Some(path)
+            Ok(Some(path))
+        }
+        None => Ok(args.config.clone()),
+    }
+}
+
+/// Read the config content, from ffpapi or from file, and check it without starting the playout.
+pub fn check_config_source(args: &Args, check_ffmpeg: bool) -> Result<ConfigCheck, ProcError> {
+    let content = match &args.config_url {
+        Some(url) => fetch_config(url)?,
+        None => {
+            let path = PlayoutConfig::config_path(config_path(args)?);
+
+            fs::read_to_string(&path).map_err(|e| {
+                ProcError::Custom(format!("Read config {} failed: {e}", path.display()))
+            })?
         }
-        None => args.config,
     };
 
-    let mut config = PlayoutConfig::new(cfg_path);
+    Ok(check_config(&content, check_ffmpeg))
+}
+
+/// Config Reloader
+///
+/// Read the config again, on SIGHUP or RPC request. Reloadable changes
+/// are handed over to the source iterator and get used from the next clip on.
+#[derive(Clone)]
+pub struct ConfigReloader {
+    args: Args,
+    config: Arc<Mutex<PlayoutConfig>>,
+    player_control: PlayerControl,
+}
+
+impl ConfigReloader {
+    pub fn new(args: &Args, config: &PlayoutConfig, player_control: &PlayerControl) -> Self {
+        Self {
+            args: args.clone(),
+            config: Arc::new(Mutex::new(config.clone())),
+            player_control: player_control.clone(),
+        }
+    }
+
+    pub fn reload(&self) -> Result<ConfigChanges, ProcError> {
+        let check = check_config_source(&self.args, false)?;
+
+        if !check.is_valid() {
+            let errors: Vec<String> = check.errors.iter().map(|e| e.to_string()).collect();
+
+            return Err(ProcError::Custom(format!(
+                "Config is invalid, keep running config: {}",
+                errors.join(", ")
+            )));
+        }
+
+        let mut new_config = get_config(self.args.clone())?;
+        let mut config = self.config.lock().unwrap();
+
+        // the status file can be set per channel at start, it is not part of the reload
+        new_config.general.stat_file = config.general.stat_file.clone();
+        let changes = config.changes(&new_config);
+
+        if changes.applied.iter().any(|c| c.starts_with("storage.")) {
+            fill_filler_list(&new_config, Some(self.player_control.clone()));
+            self.player_control.filler_index.store(0, Ordering::SeqCst);
+        }
+
+        config.apply_reload(&new_config);
+
+        if !changes.applied.is_empty() {
+            info!(
+                "Reload config, apply: <yellow>{}</>",
+                changes.applied.join(", ")
+            );
+
+            *self.player_control.config_reload.lock().unwrap() = Some(new_config);
+        } else {
+            info!("Reload config, nothing to apply");
+        }
+
+        if !changes.restart.is_empty() {
+            warn!(
+                "Changes need a restart: <yellow>{}</>",
+                changes.restart.join(", ")
+            );
+        }
+
+        Ok(changes)
+    }
+
+    /// Logo layers from the running config, they can change with a reload.
+    pub fn logo_layers(&self) -> Vec<LogoLayer> {
+        self.config.lock().unwrap().processing.logo_layers.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StatusData {
+    time_shift: f64,
+    date: String,
+}
+
+/// Here we create a status file in temp folder.
+/// We need this for reading/saving program status.
+/// For example when we skip a playing file,
+/// we save the time difference, so we stay in sync.
+///
+/// When file not exists we create it, and when it exists we get its values.
+pub fn status_file(stat_file: &str, playout_stat: &PlayoutStatus) -> Result<(), ProcError> {
+    debug!(
+        "Start ffplayout v{}, status file path: <b><magenta>{stat_file}</></b>",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    if !PathBuf::from(stat_file).exists() {
+        let data = json!({
+            "time_shift": 0.0,
+            "date": String::new(),
+        });
+
+        let json: String = serde_json::to_string(&data)?;
+        if let Err(e) = fs::write(stat_file, json) {
+            error!("Unable to write to status file <b><magenta>{stat_file}</></b>: {e}");
+        };
+    } else {
+        let stat_file = File::options().read(true).write(false).open(stat_file)?;
+        let data: StatusData = serde_json::from_reader(stat_file)?;
+
+        *playout_stat.time_shift.lock().unwrap() = data.time_shift;
+        *playout_stat.date.lock().unwrap() = data.date;
+    }
+
+    Ok(())
+}
+
+/// Read command line arguments, and override the config with them.
+pub fn get_config(args: Args) -> Result<PlayoutConfig, ProcError> {
+    let cfg_path = config_path(&args)?;
+
+    let mut config = match &args.config_url {
+        Some(url) => {
+            let content = fetch_config(url)?;
+
+            PlayoutConfig::from_yaml(&content, url)
+                .map_err(|e| ProcError::Custom(format!("Config from {url} is invalid: {e}")))?
+        }
+        None => PlayoutConfig::new(cfg_path),
+    };
 
     if let Some(gen) = args.generate {
         config.general.generate = Some(gen);
     }
 
+    if let Some(tolerance) = args.exact_length {
+        config.general.exact_length = Some(tolerance.abs());
+    }
+
     if args.validate {
         config.general.validate = true;
     }
@@ -55,7 +232,7 @@ pub fn get_config(args: Args) -> Result<PlayoutConfig, ProcError> {
 
         let mut template: Template = serde_json::from_reader(f)?;
 
-        template.sources.sort_by(|d1, d2| d1.start.cmp(&d2.start));
+        template.sources.sort_by_key(|d| d.start);
 
         config.general.template = Some(template);
     }
@@ -89,7 +266,7 @@ pub fn get_config(args: Args) -> Result<PlayoutConfig, ProcError> {
 
     if let Some(start) = args.start {
         config.playlist.day_start = start.clone();
-        config.playlist.start_sec = Some(time_to_sec(&start));
+        config.playlist.start_sec = Some(day_start_to_sec(&start, config.playlist.timezone));
     }
 
     if let Some(length) = args.length {
@@ -167,82 +344,15 @@ pub fn valid_stream(msg: &str) -> bool {
     false
 }
 
-/// Prepare output parameters
-///
-/// Seek for multiple outputs and add mapping for it.
-pub fn prepare_output_cmd(
-    config: &PlayoutConfig,
-    mut cmd: Vec<String>,
-    filters: &Option<Filters>,
-) -> Vec<String> {
-    let mut output_params = config.out.clone().output_cmd.unwrap();
-    let mut new_params = vec![];
-    let mut count = 0;
-    let re_v = Regex::new(r"\[?0:v(:0)?\]?").unwrap();
-
-    if let Some(mut filter) = filters.clone() {
-        for (i, param) in output_params.iter().enumerate() {
-            if filter.video_out_link.len() > count && re_v.is_match(param) {
-                // replace mapping with link from filter struct
-                new_params.push(filter.video_out_link[count].clone());
-            } else {
-                new_params.push(param.clone());
-            }
-
-            // Check if parameter is a output
-            if i > 0
-                && !param.starts_with('-')
-                && !output_params[i - 1].starts_with('-')
-                && i < output_params.len() - 1
-            {
-                count += 1;
-
-                if filter.video_out_link.len() > count
-                    && !output_params.contains(&"-map".to_string())
-                {
-                    new_params.append(&mut vec_strings![
-                        "-map",
-                        filter.video_out_link[count].clone()
-                    ]);
-
-                    for i in 0..config.processing.audio_tracks {
-                        new_params.append(&mut vec_strings!["-map", format!("0:a:{i}")]);
-                    }
-                }
-            }
-        }
-
-        output_params = new_params;
-
-        cmd.append(&mut filter.cmd());
-
-        // add mapping at the begin, if needed
-        if !filter.map().iter().all(|item| output_params.contains(item))
-            && filter.output_chain.is_empty()
-            && filter.video_out_link.is_empty()
-        {
-            cmd.append(&mut filter.map())
-        } else if &output_params[0] != "-map" && !filter.video_out_link.is_empty() {
-            cmd.append(&mut vec_strings!["-map", filter.video_out_link[0].clone()]);
-
-            for i in 0..config.processing.audio_tracks {
-                cmd.append(&mut vec_strings!["-map", format!("0:a:{i}")]);
-            }
-        }
-    }
-
-    cmd.append(&mut output_params);
-
-    cmd
-}
-
 /// map media struct to json object
-pub fn get_media_map(media: Media) -> Value {
+pub fn get_media_map(config: &PlayoutConfig, media: Media) -> Value {
     json!({
         "seek": media.seek,
         "out": media.out,
         "duration": media.duration,
         "category": media.category,
+        "title": media.get_title(config),
+        "description": media.get_description(config),
         "source": media.source,
     })
 }
@@ -255,7 +365,7 @@ pub fn get_data_map(
     server_is_running: bool,
 ) -> Map<String, Value> {
     let mut data_map = Map::new();
-    let current_time = time_in_seconds();
+    let current_time = time_in_seconds(config);
     let shift = *playout_stat.time_shift.lock().unwrap();
     let begin = media.begin.unwrap_or(0.0) - shift;
 
@@ -273,7 +383,7 @@ pub fn get_data_map(
         data_map.insert("remaining_sec".to_string(), json!(remaining_time));
     }
 
-    data_map.insert("current_media".to_string(), get_media_map(media));
+    data_map.insert("current_media".to_string(), get_media_map(config, media));
 
     data_map
 }
@@ -0,0 +1,219 @@
+use std::{
+    sync::atomic::Ordering,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use futures::executor::block_on;
+use simplelog::*;
+
+use crate::rpc::{text_from_json, zmq_send};
+use ffplayout_lib::{
+    filter::v_drawtext,
+    utils::{
+        spawn_thread, Media, OutputMode::*, PlayerControl, PlayoutConfig, PlayoutStatus,
+        ProcessControl, SecondaryEvent,
+    },
+};
+
+struct TimedEvent {
+    start: f64,
+    end: f64,
+    event: SecondaryEvent,
+    active: bool,
+    done: bool,
+}
+
+/// Send command to the zmq socket, from the text filter.
+fn send_command(config: &PlayoutConfig, proc_control: &ProcessControl, command: &str) {
+    let socket = if config.out.mode == HLS && proc_control.server_is_running.load(Ordering::SeqCst)
+    {
+        config.text.zmq_server_socket.clone()
+    } else {
+        config.text.zmq_stream_socket.clone()
+    };
+
+    match socket {
+        Some(socket) => {
+            debug!("Secondary event: <bright-blue>\"{command}\"</>");
+
+            if let Err(e) = block_on(zmq_send(command, &socket)) {
+                error!("Secondary event failed: {e}");
+            }
+        }
+        None => error!(
+            "Secondary events need <yellow>add_text</> without <yellow>text_from_filename</>!"
+        ),
+    }
+}
+
+/// Text which is on screen in the layer, when no event text is active.
+fn base_text(config: &PlayoutConfig, playout_stat: &PlayoutStatus, target: &str) -> String {
+    if let Some(text) = v_drawtext::stored_text(&playout_stat.chain, target) {
+        return text;
+    }
+
+    if let Some(layer) = config
+        .text
+        .layers
+        .iter()
+        .find(|l| target == format!("drawtext@{}", l.name))
+    {
+        return v_drawtext::layer_cmd(config, layer);
+    }
+
+    "text=''".to_string()
+}
+
+/// Get the zmq target from the text layer and the filter command from the event text.
+fn event_text(config: &PlayoutConfig, event: &SecondaryEvent) -> Option<(String, String)> {
+    text_from_json(config, event.text.clone()?).ok()
+}
+
+fn start_event(config: &PlayoutConfig, proc_control: &ProcessControl, event: &SecondaryEvent) {
+    if let Some(text) = &event.text {
+        match text_from_json(config, text.clone()) {
+            Ok((target, filter)) => {
+                send_command(config, proc_control, &format!("{target} reinit {filter}"))
+            }
+            Err(e) => error!("Secondary event: {e}"),
+        }
+    }
+
+    if !event.command.is_empty() {
+        send_command(config, proc_control, &event.command);
+    }
+}
+
+fn end_event(
+    config: &PlayoutConfig,
+    playout_stat: &PlayoutStatus,
+    proc_control: &ProcessControl,
+    events: &[TimedEvent],
+    index: usize,
+) {
+    let event = &events[index].event;
+
+    if let Some((target, _)) = event_text(config, event) {
+        // Show the last text event in this layer which is still running, or go back to the normal text.
+        let filter = events
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(i, e)| *i != index && e.active)
+            .find_map(|(_, e)| {
+                event_text(config, &e.event)
+                    .filter(|(t, _)| *t == target)
+                    .map(|(_, f)| f)
+            })
+            .unwrap_or_else(|| base_text(config, playout_stat, &target));
+
+        send_command(config, proc_control, &format!("{target} reinit {filter}"));
+    }
+
+    if !event.end_command.is_empty() {
+        send_command(config, proc_control, &event.end_command);
+    }
+}
+
+/// Run secondary events from the node, while it is the playing clip.
+///
+/// When the clip starts with a seek, the events are shifted, and events which are
+/// still running after a clip change (next/back, live ingest) are ended.
+fn run(
+    config: PlayoutConfig,
+    node: Media,
+    clip_id: usize,
+    player_control: PlayerControl,
+    playout_stat: PlayoutStatus,
+    proc_control: ProcessControl,
+) {
+    let timer = Instant::now();
+    let in_point = player_control
+        .current_list
+        .lock()
+        .unwrap()
+        .get(node.index.unwrap_or_default())
+        .filter(|n| n.source == node.source)
+        .map(|n| n.seek)
+        .unwrap_or(node.seek);
+    let played = node.seek - in_point;
+    let length = node.out - node.seek;
+    let mut events: Vec<TimedEvent> = node
+        .secondary_events
+        .into_iter()
+        .map(|event| TimedEvent {
+            start: (event.offset - played).max(0.0),
+            end: (event.offset + event.duration - played).min(length),
+            event,
+            active: false,
+            done: false,
+        })
+        .filter(|e| e.end > e.start)
+        .collect();
+
+    events.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    while events.iter().any(|e| !e.done) {
+        if player_control.clip_count.load(Ordering::SeqCst) != clip_id
+            || proc_control.server_is_running.load(Ordering::SeqCst)
+            || proc_control.is_terminated.load(Ordering::SeqCst)
+        {
+            for i in 0..events.len() {
+                if events[i].active {
+                    end_event(&config, &playout_stat, &proc_control, &events, i);
+                    events[i].active = false;
+                }
+            }
+
+            return;
+        }
+
+        let elapsed = timer.elapsed().as_secs_f64();
+
+        for i in 0..events.len() {
+            if !events[i].done && !events[i].active && events[i].start <= elapsed {
+                start_event(&config, &proc_control, &events[i].event);
+                events[i].active = true;
+            }
+
+            if events[i].active && events[i].end <= elapsed {
+                end_event(&config, &playout_stat, &proc_control, &events, i);
+                events[i].active = false;
+                events[i].done = true;
+            }
+        }
+
+        sleep(Duration::from_millis(100));
+    }
+}
+
+/// Count the new clip and start a thread for its secondary events.
+pub fn start(
+    config: &PlayoutConfig,
+    node: &Media,
+    player_control: &PlayerControl,
+    playout_stat: &PlayoutStatus,
+    proc_control: &ProcessControl,
+) {
+    let clip_id = player_control.clip_count.fetch_add(1, Ordering::SeqCst) + 1;
+
+    if !node.secondary_events.is_empty() {
+        let config = config.clone();
+        let node = node.clone();
+        let player_control = player_control.clone();
+        let playout_stat = playout_stat.clone();
+        let proc_control = proc_control.clone();
+
+        spawn_thread(move || {
+            run(
+                config,
+                node,
+                clip_id,
+                player_control,
+                playout_stat,
+                proc_control,
+            )
+        });
+    }
+}
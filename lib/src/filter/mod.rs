@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fmt,
     path::Path,
     sync::{Arc, Mutex},
@@ -11,8 +12,9 @@ mod custom;
 pub mod v_drawtext;
 
 use crate::utils::{
-    controller::ProcessUnit::*, custom_format, fps_calc, is_close, Media, OutputMode::*,
-    PlayoutConfig,
+    advanced_config::HwAccel, controller::ProcessUnit::*, custom_format, fps_calc, hw_decodable,
+    is_close, loudness, subtitle_pass, time_in_seconds, time_to_sec, ChannelMap, ColorInfo,
+    FitMode, LogoLayer, Media, OutputMode::*, PlayoutConfig,
 };
 use crate::ADVANCED_CONFIG;
 
@@ -45,6 +47,9 @@ pub struct Filters {
     pub audio_out_link: Vec<String>,
     pub video_out_link: Vec<String>,
     pub output_map: Vec<String>,
+    pub subtitle_map: Vec<String>,
+    /// Input audio stream for the output track, when it is not the same index.
+    pub audio_streams: HashMap<i32, i32>,
     config: PlayoutConfig,
     audio_position: i32,
     video_position: i32,
@@ -63,6 +68,8 @@ impl Filters {
             audio_out_link: vec![],
             video_out_link: vec![],
             output_map: vec![],
+            subtitle_map: vec![],
+            audio_streams: HashMap::new(),
             config,
             audio_position,
             video_position: 0,
@@ -72,6 +79,11 @@ impl Filters {
     }
 
     pub fn add_filter(&mut self, filter: &str, track_nr: i32, filter_type: FilterType) {
+        let stream_nr = match filter_type {
+            Audio => self.audio_stream(track_nr),
+            Video => track_nr,
+        };
+
         let (map, chain, position, last) = match filter_type {
             Audio => (
                 &mut self.audio_map,
@@ -102,7 +114,7 @@ impl Filters {
                 chain.push_str(&format!("{sep}{filter}"));
             } else {
                 chain.push_str(&format!(
-                    "{sep}[{position}:{filter_type}:{track_nr}]{filter}",
+                    "{sep}[{position}:{filter_type}:{stream_nr}]{filter}",
                 ));
             }
 
@@ -117,6 +129,14 @@ impl Filters {
         }
     }
 
+    /// Input audio stream index for the output track.
+    pub fn audio_stream(&self, track_nr: i32) -> i32 {
+        self.audio_streams
+            .get(&track_nr)
+            .copied()
+            .unwrap_or(track_nr)
+    }
+
     pub fn cmd(&mut self) -> Vec<String> {
         if !self.output_chain.is_empty() {
             return self.output_chain.clone();
@@ -165,7 +185,7 @@ impl Filters {
 
         if self.audio_last == -1 {
             for i in 0..self.config.processing.audio_tracks {
-                let a_map = format!("{}:a:{i}", self.audio_position);
+                let a_map = format!("{}:a:{}", self.audio_position, self.audio_stream(i));
 
                 if !o_map.contains(&a_map) {
                     o_map.append(&mut vec_strings!["-map", a_map]);
@@ -183,12 +203,82 @@ impl Default for Filters {
     }
 }
 
+/// Run a CPU filter with the acceleration profile, the frames get downloaded from the GPU and uploaded again.
+///
+/// `download` must be the pixel format of the GPU frames, `upload` is the format for the next filters.
+fn hw_transfer(filter: &str, download: &str, upload: &str, config: &PlayoutConfig) -> String {
+    match config.general.hw_accel {
+        HwAccel::None => filter.to_string(),
+        HwAccel::Cuda | HwAccel::Vaapi => {
+            format!("hwdownload,format={download},{filter},format={upload},hwupload")
+        }
+        HwAccel::Qsv => format!(
+            "hwdownload,format={download},{filter},format={upload},hwupload=extra_hw_frames=64,format=qsv"
+        ),
+    }
+}
+
+/// Wrap CPU filters before the scaler, the decoder delivers nv12 frames.
+///
+/// The built-in CPU video filters of the decoder chain go through here, or through `hw_wrap_scaled`
+/// after the scaler. Filters from the advanced config are used as they are and must fit to the
+/// acceleration profile. fps, fade and tpad are optional, see `skip_cpu_filter`.
+fn hw_wrap(filter: &str, config: &PlayoutConfig) -> String {
+    hw_transfer(filter, "nv12", "nv12", config)
+}
+
+/// Wrap CPU filters after the scaler, scale_npp delivers yuv420p frames.
+fn hw_wrap_scaled(filter: &str, config: &PlayoutConfig) -> String {
+    let format = match config.general.hw_accel {
+        HwAccel::Cuda => "yuv420p",
+        _ => "nv12",
+    };
+
+    hw_transfer(filter, format, format, config)
+}
+
+/// fps, fade and tpad are not needed for a valid output, with an acceleration profile they
+/// only run when `acceleration_cpu_filters` is on, because every one of them costs a GPU round trip.
+fn skip_cpu_filter(config: &PlayoutConfig) -> bool {
+    config.general.hw_accel != HwAccel::None && !config.general.hw_cpu_filters
+}
+
+/// Scale filter for the acceleration profile, to the channel size.
+fn hw_scale(keep_aspect: bool, config: &PlayoutConfig) -> String {
+    let (w, h) = (config.processing.width, config.processing.height);
+    let aspect = match keep_aspect {
+        true => ":force_original_aspect_ratio=decrease",
+        false => "",
+    };
+
+    match config.general.hw_accel {
+        HwAccel::None => format!("scale={w}:{h}{aspect}"),
+        HwAccel::Cuda => {
+            format!("scale_npp=format=yuv420p,scale_npp={w}:{h}:interp_algo=super{aspect}")
+        }
+        HwAccel::Vaapi => format!("scale_vaapi=w={w}:h={h}:format=nv12{aspect}"),
+        // scale_qsv can not keep the aspect
+        HwAccel::Qsv if keep_aspect => hw_wrap(&format!("scale={w}:{h}{aspect}"), config),
+        HwAccel::Qsv => format!("scale_qsv=w={w}:h={h}"),
+    }
+}
+
+/// Upload and overlay filter for logos, with the acceleration profile.
+fn hw_overlay(config: &PlayoutConfig) -> (&str, &str) {
+    match config.general.hw_accel {
+        HwAccel::None => ("", "overlay"),
+        HwAccel::Cuda => (",hwupload_cuda", "overlay_cuda"),
+        HwAccel::Vaapi => (",hwupload", "overlay_vaapi"),
+        HwAccel::Qsv => (",hwupload=extra_hw_frames=64,format=qsv", "overlay_qsv"),
+    }
+}
+
 fn deinterlace(field_order: &Option<String>, chain: &mut Filters) {
     if let Some(order) = field_order {
         if order != "progressive" {
             let deinterlace = match &ADVANCED_CONFIG.decoder.filters.deinterlace {
                 Some(deinterlace) => deinterlace.clone(),
-                None => "yadif=0:-1:0".to_string(),
+                None => hw_wrap("yadif=0:-1:0", &chain.config),
             };
 
             chain.add_filter(&deinterlace, 0, Video);
@@ -196,6 +286,35 @@ fn deinterlace(field_order: &Option<String>, chain: &mut Filters) {
     }
 }
 
+/// Tone map HDR (PQ, HLG) to SDR, and convert other color primaries to BT.709.
+///
+/// Without the zscale filter in ffmpeg, HDR sources are played untouched.
+fn colorspace(color: &ColorInfo, chain: &mut Filters, config: &PlayoutConfig) {
+    let filter = if color.is_hdr() {
+        match &ADVANCED_CONFIG.decoder.filters.tonemap {
+            Some(tonemap) => tonemap.clone(),
+            None if !config.general.ffmpeg_filters.iter().any(|f| f == "zscale") => return,
+            // HDR sources have 10 bit
+            None => hw_transfer("zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p", "p010le", "nv12", config),
+        }
+    } else {
+        match color.color_primaries.as_deref() {
+            Some(primaries @ ("bt470m" | "bt470bg" | "smpte170m" | "smpte240m" | "bt2020")) => {
+                match &ADVANCED_CONFIG.decoder.filters.colorspace {
+                    Some(colorspace) => custom_format(colorspace, &[primaries]),
+                    None => hw_wrap(
+                        &format!("colorspace=all=bt709:iall={primaries}:fast=1"),
+                        config,
+                    ),
+                }
+            }
+            _ => return,
+        }
+    };
+
+    chain.add_filter(&filter, 0, Video)
+}
+
 fn pad(aspect: f64, chain: &mut Filters, v_stream: &ffprobe::Stream, config: &PlayoutConfig) {
     if !is_close(aspect, config.processing.aspect, 0.03) {
         let mut scale = String::new();
@@ -226,9 +345,12 @@ fn pad(aspect: f64, chain: &mut Filters, v_stream: &ffprobe::Stream, config: &Pl
                     &config.processing.height.to_string(),
                 ],
             ),
-            None => format!(
-                "{}hwdownload,pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black,setdar=16:9,setsar=1:1,hwupload",
-                scale, config.processing.width, config.processing.height
+            None => hw_wrap(
+                &format!(
+                    "{}pad={}:{}:(ow-iw)/2:(oh-ih)/2:color=black,setdar=16:9,setsar=1:1",
+                    scale, config.processing.width, config.processing.height
+                ),
+                config,
             ),
         };
 
@@ -236,14 +358,66 @@ fn pad(aspect: f64, chain: &mut Filters, v_stream: &ffprobe::Stream, config: &Pl
     }
 }
 
+/// Fit the picture in the target aspect, with the fit mode from the clip or the channel.
+fn fit(
+    node: &Media,
+    aspect: f64,
+    chain: &mut Filters,
+    v_stream: &ffprobe::Stream,
+    config: &PlayoutConfig,
+) {
+    let target = config.processing.aspect;
+
+    let filter = match node.fit.unwrap_or(config.processing.fit) {
+        _ if is_close(aspect, target, 0.03) => return,
+        FitMode::Pad => return pad(aspect, chain, v_stream, config),
+        FitMode::Stretch => return,
+        FitMode::Crop => match &ADVANCED_CONFIG.decoder.filters.crop_video {
+            Some(crop) => custom_format(crop, &[target]),
+            None => hw_wrap(
+                &format!("crop=w='min(iw,ih*{target}/sar)':h='min(ih,iw*sar/{target})'"),
+                config,
+            ),
+        },
+        // only material which is wider than 4:3 gets a center cut
+        FitMode::CenterCut if aspect < 1.4 => return pad(aspect, chain, v_stream, config),
+        FitMode::CenterCut => match &ADVANCED_CONFIG.decoder.filters.center_cut {
+            Some(cut) => custom_format(cut, &[target]),
+            None if is_close(target, 4.0 / 3.0, 0.03) => {
+                hw_wrap("crop=w='min(iw,ih*4/3/sar)':h=ih", config)
+            }
+            None => hw_wrap(
+                &format!("crop=w='min(iw,ih*4/3/sar)':h=ih,pad=w='ih*{target}/sar':h=ih:x=(ow-iw)/2:y=0:color=black"),
+                config,
+            ),
+        },
+        FitMode::Blur => match &ADVANCED_CONFIG.decoder.filters.blur_fill {
+            Some(blur) => custom_format(
+                blur,
+                &[&config.processing.width, &config.processing.height],
+            ),
+            None => hw_wrap(
+                &format!(
+                    "split[vfit_fg][vfit_bg];[vfit_bg]scale={0}:{1},boxblur=20:5,setsar=1[vfit_b];[vfit_fg]scale={0}:{1}:force_original_aspect_ratio=decrease,setsar=1[vfit_f];[vfit_b][vfit_f]overlay=(W-w)/2:(H-h)/2",
+                    config.processing.width, config.processing.height
+                ),
+                config,
+            ),
+        },
+    };
+
+    chain.add_filter(&filter, 0, Video)
+}
+
 fn fps(fps: f64, chain: &mut Filters, config: &PlayoutConfig) {
     if fps != config.processing.fps {
         let fps_filter = match &ADVANCED_CONFIG.decoder.filters.fps {
             Some(fps) => custom_format(fps, &[&config.processing.fps]),
-            None => format!("fps={}", config.processing.fps),
+            None if skip_cpu_filter(config) => return,
+            None => hw_wrap(&format!("fps={}", config.processing.fps), config),
         };
 
-        //chain.add_filter(&fps_filter, 0, Video)
+        chain.add_filter(&fps_filter, 0, Video)
     }
 }
 
@@ -251,24 +425,35 @@ fn scale(
     width: Option<i64>,
     height: Option<i64>,
     aspect: f64,
+    stretch: bool,
     chain: &mut Filters,
     config: &PlayoutConfig,
 ) {
     // width: i64, height: i64
     if let (Some(w), Some(h)) = (width, height) {
-        if w != config.processing.width || h != config.processing.height {
+        if stretch && !is_close(aspect, config.processing.aspect, 0.03) {
             let scale = match &ADVANCED_CONFIG.decoder.filters.scale {
                 Some(scale) => custom_format(
                     scale,
                     &[&config.processing.width, &config.processing.height],
                 ),
-                None => format!(
-                    "scale_npp=format=yuv420p,scale_npp={}:{}:interp_algo=super:force_original_aspect_ratio=decrease",
-                    config.processing.width, config.processing.height
+                None => hw_scale(false, config),
+            };
+
+            chain.add_filter(&format!("{scale},setsar=1"), 0, Video);
+        } else if w != config.processing.width || h != config.processing.height {
+            let scale = match &ADVANCED_CONFIG.decoder.filters.scale {
+                Some(scale) => custom_format(
+                    scale,
+                    &[&config.processing.width, &config.processing.height],
                 ),
+                None => hw_scale(true, config),
             };
 
             chain.add_filter(&scale, 0, Video);
+        } else if config.general.hw_accel == HwAccel::Cuda {
+            // keep the frame format from scale_npp
+            chain.add_filter("scale_npp=format=yuv420p", 0, Video);
         } else {
             chain.add_filter("null", 0, Video);
         }
@@ -279,7 +464,7 @@ fn scale(
                 None => format!("setdar=dar={}", config.processing.aspect),
             };
 
-            //chain.add_filter(&dar, 0, Video);
+            chain.add_filter(&dar, 0, Video);
         }
     } else {
         let scale = match &ADVANCED_CONFIG.decoder.filters.scale {
@@ -287,10 +472,7 @@ fn scale(
                 scale,
                 &[&config.processing.width, &config.processing.height],
             ),
-            None => format!(
-                "scale_npp=format=yuv420p,scale_npp={}:{}:interp_algo=super:force_original_aspect_ratio=decrease",
-                config.processing.width, config.processing.height
-            ),
+            None => hw_scale(true, config),
         };
         chain.add_filter(&scale, 0, Video);
 
@@ -299,7 +481,7 @@ fn scale(
             None => format!("setdar=dar={}", config.processing.aspect),
         };
 
-        //chain.add_filter(&dar, 0, Video);
+        chain.add_filter(&dar, 0, Video);
     }
 }
 
@@ -324,9 +506,13 @@ fn fade(node: &mut Media, chain: &mut Filters, nr: i32, filter_type: FilterType)
             }
         } else if let Some(fade) = &ADVANCED_CONFIG.decoder.filters.fade_in {
             fade_in = custom_format(fade, &[t]);
+        } else if skip_cpu_filter(&chain.config) {
+            return;
+        } else {
+            fade_in = hw_wrap_scaled(&fade_in, &chain.config);
         };
 
-        //chain.add_filter(&fade_in, nr, filter_type);
+        chain.add_filter(&fade_in, nr, filter_type);
     }
 
     if (node.out != node.duration && node.out - node.seek > 1.0) || fade_audio {
@@ -338,9 +524,13 @@ fn fade(node: &mut Media, chain: &mut Filters, nr: i32, filter_type: FilterType)
             }
         } else if let Some(fade) = &ADVANCED_CONFIG.decoder.filters.fade_out {
             fade_out = custom_format(fade, &[t]);
+        } else if skip_cpu_filter(&chain.config) {
+            return;
+        } else {
+            fade_out = hw_wrap_scaled(&fade_out, &chain.config);
         };
 
-        //chain.add_filter(&fade_out, nr, filter_type);
+        chain.add_filter(&fade_out, nr, filter_type);
     }
 }
 
@@ -395,17 +585,118 @@ fn overlay(node: &mut Media, chain: &mut Filters, config: &PlayoutConfig) {
 
                 logo_chain.push_str(&custom_format(overlay, &[&config.processing.logo_position]))
             }
-            None => logo_chain.push_str(&format!(
-                "hwupload_cuda[l];[v][l]overlay_cuda={}:shortest=1",
-                //",hwupload_cuda[l];[v][l]overlay_cuda=main_w-overlay_w-33:33"
-                config.processing.logo_position
-            )),
+            None => {
+                let (upload, overlay) = hw_overlay(config);
+
+                if logo_chain.ends_with(',') {
+                    logo_chain.pop();
+                }
+
+                logo_chain.push_str(&format!(
+                    "{upload}[l];[v][l]{overlay}={}:shortest=1",
+                    config.processing.logo_position
+                ))
+            }
         };
 
         chain.add_filter(&logo_chain, 0, Video);
     }
 }
 
+/// Build the timeline expression, which hides a logo layer.
+///
+/// The switch from RPC wins over the clip and category rules, the daily time window
+/// is calculated from the clip start, because `t` starts with every clip at zero.
+fn logo_hide_expr(layer: &LogoLayer, node: &Media, config: &PlayoutConfig) -> String {
+    let state = config
+        .processing
+        .logo_overrides
+        .lock()
+        .unwrap()
+        .get(&layer.name)
+        .copied();
+
+    let visible = state.or(node.logos.get(&layer.name).copied()).unwrap_or(
+        layer.enable
+            && (layer.categories.is_empty() || layer.categories.contains(&node.category))
+            && !layer.hide_categories.contains(&node.category),
+    );
+
+    if !visible {
+        return "1".to_string();
+    }
+
+    if state.is_some() || (layer.from_time.is_empty() && layer.to_time.is_empty()) {
+        return "0".to_string();
+    }
+
+    let begin = node.begin.unwrap_or_else(|| time_in_seconds(config));
+    let now = format!("mod(t+{begin:.3},86400)");
+    let from = (!layer.from_time.is_empty()).then(|| time_to_sec(&layer.from_time));
+    let to = (!layer.to_time.is_empty()).then(|| time_to_sec(&layer.to_time));
+
+    let window = match (from, to) {
+        (Some(f), Some(t)) if t <= f => format!("gte({now},{f})+lt({now},{t})"),
+        (Some(f), Some(t)) => format!("gte({now},{f})*lt({now},{t})"),
+        (Some(f), None) => format!("gte({now},{f})"),
+        (None, Some(t)) => format!("lt({now},{t})"),
+        (None, None) => unreachable!(),
+    };
+
+    format!("not({window})")
+}
+
+/// Add the logo layers over the main logo.
+///
+/// All layers with an existing file are in the filter graph, also when they are hidden,
+/// so they can be switched on air. Only the playlist decoder gets the zmq socket for this.
+fn logo_layers(node: &mut Media, chain: &mut Filters, config: &PlayoutConfig) {
+    let mut zmq_socket = match node.unit {
+        Decoder => config.processing.logo_zmq_socket.clone(),
+        _ => None,
+    };
+
+    for (i, layer) in config
+        .processing
+        .logo_layers
+        .iter()
+        .filter(|l| Path::new(&l.path).is_file())
+        .enumerate()
+    {
+        let mut logo_chain = format!(
+            "null[vlogo{i}];movie={}:loop=0,setpts=N/(FRAME_RATE*TB),",
+            layer.path.replace('\\', "/").replace(':', "\\\\:")
+        );
+
+        if let Some(socket) = zmq_socket.take() {
+            logo_chain.push_str(&format!(
+                "zmq=b=tcp\\\\://'{}',",
+                socket.replace(':', "\\:")
+            ));
+        }
+
+        logo_chain.push_str(&format!(
+            "format=rgba,colorchannelmixer=aa={},colorchannelmixer@logo_{}=aa=0:enable='{}'",
+            layer.opacity,
+            layer.name,
+            logo_hide_expr(layer, node, config)
+        ));
+
+        if !layer.scale.is_empty() {
+            logo_chain.push_str(&format!(",scale=iw*{s}:ih*{s}", s = layer.scale));
+        }
+
+        let (upload, overlay) = hw_overlay(config);
+
+        logo_chain.push_str(&format!(
+            "{upload}[llogo{i}];[vlogo{i}][llogo{i}]{overlay}={}:shortest=1",
+            layer.position
+        ));
+
+        chain.add_filter(&logo_chain, 0, Video);
+    }
+}
+
 fn extend_video(node: &mut Media, chain: &mut Filters) {
     if let Some(video_duration) = node
         .probe
@@ -419,14 +710,67 @@ fn extend_video(node: &mut Media, chain: &mut Filters) {
 
             let tpad = match &ADVANCED_CONFIG.decoder.filters.tpad {
                 Some(pad) => custom_format(pad, &[duration]),
-                None => format!("tpad=stop_mode=add:stop_duration={duration}"),
+                None if skip_cpu_filter(&chain.config) => return,
+                None => hw_wrap_scaled(
+                    &format!("tpad=stop_mode=add:stop_duration={duration}"),
+                    &chain.config,
+                ),
             };
 
-            //chain.add_filter(&tpad, 0, Video)
+            chain.add_filter(&tpad, 0, Video)
         }
     }
 }
 
+/// Burn subtitles in the picture, from a subtitle file or an embedded subtitle stream.
+///
+/// The timestamps are shifted by the seek value, so the subtitles stay in sync with trimmed clips.
+fn burn_subtitle(node: &Media, chain: &mut Filters, config: &PlayoutConfig) {
+    if node.subtitle.is_empty() || subtitle_pass(config) {
+        return;
+    }
+
+    let (file, stream) = match node.subtitle_index() {
+        Some(index) => (node.source.clone(), format!(":si={index}")),
+        None if Path::new(&node.subtitle).is_file() => (node.subtitle.clone(), String::new()),
+        None => {
+            error!("Subtitle <b><magenta>{}</></b> not exists!", node.subtitle);
+            return;
+        }
+    };
+
+    let file = file.replace('\\', "/").replace(':', "\\\\:");
+    let mut filter = format!("subtitles={file}{stream}");
+
+    if node.seek > 0.0 {
+        filter = format!("setpts=PTS+{0}/TB,{filter},setpts=PTS-{0}/TB", node.seek);
+    }
+
+    chain.add_filter(&hw_wrap_scaled(&filter, config), 0, Video);
+}
+
+/// Map the subtitle stream, for passing it to the HLS output.
+fn map_subtitle(node: &Media, chain: &mut Filters, config: &PlayoutConfig) {
+    if node.unit != Decoder || !subtitle_pass(config) {
+        return;
+    }
+
+    let map = match node.subtitle_index() {
+        Some(index) => format!("0:s:{index}?"),
+        None => {
+            // subtitle file is always the last input
+            let inputs = node
+                .cmd
+                .as_ref()
+                .map_or(0, |c| c.iter().filter(|p| *p == "-i").count());
+
+            format!("{}:s:0", inputs.saturating_sub(1))
+        }
+    };
+
+    chain.subtitle_map = vec_strings!["-map", map];
+}
+
 /// add drawtext filter for lower thirds messages
 fn add_text(
     node: &mut Media,
@@ -437,7 +781,12 @@ fn add_text(
     if config.text.add_text
         && (config.text.text_from_filename || config.out.mode == HLS || node.unit == Encoder)
     {
-        let filter = v_drawtext::filter_node(config, Some(node), filter_chain);
+        let mut filter = v_drawtext::filter_node(config, Some(node), filter_chain);
+
+        // drawtext has no GPU variant
+        if node.unit != Encoder && !filter.is_empty() {
+            filter = hw_wrap_scaled(&filter, config);
+        }
 
         chain.add_filter(&filter, 0, Video);
     }
@@ -456,6 +805,32 @@ fn add_audio(node: &Media, chain: &mut Filters, nr: i32) {
 }
 
 fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32) {
+    let delay = node.audio_options.delay;
+
+    if delay > 0.0 {
+        chain.add_filter(
+            &format!(
+                "adelay=delays={}:all=1,atrim=end={}",
+                (delay * 1000.0).round(),
+                node.out - node.seek
+            ),
+            nr,
+            Audio,
+        );
+    } else if delay < 0.0 {
+        chain.add_filter(
+            &format!(
+                "atrim=start={},asetpts=PTS-STARTPTS,apad=whole_dur={}",
+                delay.abs(),
+                node.out - node.seek
+            ),
+            nr,
+            Audio,
+        );
+
+        return;
+    }
+
     if !Path::new(&node.audio).is_file() {
         if let Some(audio_duration) = node
             .probe
@@ -477,7 +852,108 @@ fn extend_audio(node: &mut Media, chain: &mut Filters, nr: i32) {
     }
 }
 
-fn audio_volume(chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
+/// Output audio tracks.
+fn audio_tracks(config: &PlayoutConfig) -> Vec<i32> {
+    if config.processing.audio_track_index == -1 {
+        (0..config.processing.audio_tracks).collect()
+    } else {
+        vec![config.processing.audio_track_index]
+    }
+}
+
+/// Audio streams from the clip, which are played on the output tracks.
+pub fn audio_streams(node: &Media, config: &PlayoutConfig) -> Vec<i32> {
+    let mut chain = Filters::new(config.clone(), 0);
+    let tracks = audio_tracks(config);
+
+    audio_language(node, &mut chain, &tracks);
+
+    tracks.iter().map(|t| chain.audio_stream(*t)).collect()
+}
+
+/// Select the audio streams by language tag, in the order from the clip audio options.
+fn audio_language(node: &Media, chain: &mut Filters, track_indexes: &[i32]) {
+    let streams = if Path::new(&node.audio).is_file() {
+        node.probe_audio.as_ref()
+    } else {
+        node.probe.as_ref()
+    }
+    .map(|p| p.audio_streams.as_slice())
+    .unwrap_or_default();
+
+    for (track, language) in track_indexes.iter().zip(&node.audio_options.language) {
+        match streams.iter().position(|s| {
+            s.tags
+                .as_ref()
+                .and_then(|t| t.language.as_ref())
+                .is_some_and(|l| l.eq_ignore_ascii_case(language))
+        }) {
+            Some(index) => {
+                chain.audio_streams.insert(*track, index as i32);
+            }
+            None => warn!(
+                "No audio track with language <yellow>{language}</> in <b><magenta>{}</></b>",
+                node.source
+            ),
+        }
+    }
+}
+
+fn channel_map(node: &Media, chain: &mut Filters, nr: i32) {
+    let pan = match node.audio_options.channels {
+        Some(ChannelMap::Downmix) => "aformat=channel_layouts=stereo",
+        Some(ChannelMap::Left) => "pan=stereo|c0=c0|c1=c0",
+        Some(ChannelMap::Right) => "pan=stereo|c0=c1|c1=c1",
+        Some(ChannelMap::Mono) => "pan=stereo|c0=0.5*c0+0.5*c1|c1=0.5*c0+0.5*c1",
+        Some(ChannelMap::Swap) => "pan=stereo|c0=c1|c1=c0",
+        None => return,
+    };
+
+    chain.add_filter(pan, nr, Audio);
+}
+
+fn audio_volume(node: &Media, chain: &mut Filters, config: &PlayoutConfig, nr: i32) {
+    if node.audio_options.gain != 0.0 {
+        let gain = format!("{}dB", node.audio_options.gain);
+        let volume = match &ADVANCED_CONFIG.decoder.filters.volume {
+            Some(volume) => custom_format(volume, &[gain]),
+            None => format!("volume={gain}"),
+        };
+
+        chain.add_filter(&volume, nr, Audio)
+    }
+
+    if config.processing.normalize_loudness && node.unit == Decoder {
+        let stream = chain.audio_stream(nr);
+
+        match node
+            .loudness
+            .get(&stream)
+            .copied()
+            .or_else(|| loudness::cached(config, loudness::audio_source(node), stream))
+        {
+            Some(l) => {
+                let gain = l.gain(
+                    config.processing.loudness_target,
+                    config.processing.loudness_true_peak,
+                );
+                let volume = match &ADVANCED_CONFIG.decoder.filters.volume {
+                    Some(volume) => custom_format(volume, &[format!("{gain}dB")]),
+                    None => format!("volume={gain}dB"),
+                };
+
+                chain.add_filter(&volume, nr, Audio)
+            }
+            None if nr == 0 && !node.source.contains("color=c=") => {
+                debug!(
+                    "No loudness measurement from <b><magenta>{}</></b>",
+                    node.source
+                )
+            }
+            None => (),
+        }
+    }
+
     if config.processing.volume != 1.0 {
         let volume = match &ADVANCED_CONFIG.decoder.filters.volume {
             Some(volume) => custom_format(volume, &[config.processing.volume]),
@@ -590,6 +1066,11 @@ pub fn filter_chains(
     }
 
     if !config.processing.audio_only && !config.processing.copy_video {
+        // images and generated clips are decoded on the CPU
+        if config.general.hw_accel != HwAccel::None && !hw_decodable(node) {
+            filters.add_filter(&format!("format=nv12{}", hw_overlay(config).0), 0, Video);
+        }
+
         if let Some(probe) = node.probe.as_ref() {
             if Path::new(&node.audio).is_file() {
                 filters.audio_position = 1;
@@ -600,12 +1081,14 @@ pub fn filter_chains(
                 let frame_per_sec = fps_calc(&v_stream.r_frame_rate, 1.0);
 
                 deinterlace(&v_stream.field_order, &mut filters);
-                pad(aspect, &mut filters, v_stream, config);
+                colorspace(&probe.color, &mut filters, config);
+                fit(node, aspect, &mut filters, v_stream, config);
                 fps(frame_per_sec, &mut filters, config);
                 scale(
                     v_stream.width,
                     v_stream.height,
                     aspect,
+                    node.fit.unwrap_or(config.processing.fit) == FitMode::Stretch,
                     &mut filters,
                     config,
                 );
@@ -614,12 +1097,14 @@ pub fn filter_chains(
             extend_video(node, &mut filters);
         } else {
             fps(0.0, &mut filters, config);
-            scale(None, None, 1.0, &mut filters, config);
+            scale(None, None, 1.0, false, &mut filters, config);
         }
 
+        burn_subtitle(node, &mut filters, config);
         add_text(node, &mut filters, config, filter_chain);
         fade(node, &mut filters, 0, Video);
         overlay(node, &mut filters, config);
+        logo_layers(node, &mut filters, config);
     }
 
     let (proc_vf, proc_af) = if node.unit == Ingest {
@@ -635,22 +1120,16 @@ pub fn filter_chains(
         custom(&list_vf, &mut filters, 0, Video);
     }
 
-    let mut audio_indexes = vec![];
+    let audio_indexes = audio_tracks(config);
 
-    if config.processing.audio_track_index == -1 {
-        for i in 0..config.processing.audio_tracks {
-            audio_indexes.push(i)
-        }
-    } else {
-        audio_indexes.push(config.processing.audio_track_index)
-    }
+    audio_language(node, &mut filters, &audio_indexes);
 
     if !config.processing.copy_audio {
         for i in audio_indexes {
             if node
                 .probe
                 .as_ref()
-                .and_then(|p| p.audio_streams.get(i as usize))
+                .and_then(|p| p.audio_streams.get(filters.audio_stream(i) as usize))
                 .is_some()
                 || Path::new(&node.audio).is_file()
             {
@@ -670,8 +1149,9 @@ pub fn filter_chains(
             // is important for split filter in HLS mode
             filters.add_filter("anull", i, Audio);
 
+            channel_map(node, &mut filters, i);
             fade(node, &mut filters, i, Audio);
-            audio_volume(&mut filters, config, i);
+            audio_volume(node, &mut filters, config, i);
 
             custom(&proc_af, &mut filters, i, Audio);
             custom(&list_af, &mut filters, i, Audio);
@@ -680,6 +1160,8 @@ pub fn filter_chains(
         error!("Setting 'audio_track_index' other than '-1' is not allowed in audio copy mode!")
     }
 
+    map_subtitle(node, &mut filters, config);
+
     if config.out.mode == HLS {
         if let Some(f) = config.out.output_filter.clone() {
             process_output_filters(config, &mut filters, &f)
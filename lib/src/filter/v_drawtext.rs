@@ -1,25 +1,64 @@
 use std::{
-    ffi::OsStr,
     path::Path,
     sync::{Arc, Mutex},
 };
 
-use regex::Regex;
-
-use crate::utils::{controller::ProcessUnit::*, custom_format, Media, PlayoutConfig};
+use crate::utils::{
+    controller::ProcessUnit::*, custom_format, Media, PlayoutConfig, TextLayer, DEFAULT_TEXT_LAYER,
+};
 use crate::ADVANCED_CONFIG;
 
+fn font(config: &PlayoutConfig) -> String {
+    if Path::new(&config.text.fontfile).is_file() {
+        return format!(":fontfile='{}'", config.text.fontfile);
+    }
+
+    String::new()
+}
+
+/// Drawtext parameters from a text layer, with its start text.
+///
+/// The text is used as it is, so it can contain escaped drawtext expansions.
+pub fn layer_cmd(config: &PlayoutConfig, layer: &TextLayer) -> String {
+    let mut cmd = format!("text='{}'", layer.text);
+
+    if !layer.style.is_empty() {
+        cmd.push_str(&format!(":{}", layer.style));
+    }
+
+    cmd.push_str(&font(config));
+
+    cmd
+}
+
+/// Remember the last text command of a layer, so that the next clips start with it.
+pub fn store_text(filter_chain: &Arc<Mutex<Vec<String>>>, target: &str, filter: &str) {
+    let prefix = format!("{target}=");
+    let mut chain = filter_chain.lock().unwrap();
+
+    chain.retain(|l| !l.starts_with(&prefix));
+    chain.push(format!("{prefix}{filter}"));
+}
+
+/// Last text command which was sent to the layer, `target` is the drawtext instance.
+pub fn stored_text(filter_chain: &Option<Arc<Mutex<Vec<String>>>>, target: &str) -> Option<String> {
+    let prefix = format!("{target}=");
+
+    filter_chain
+        .as_ref()?
+        .lock()
+        .unwrap()
+        .iter()
+        .find_map(|l| l.strip_prefix(&prefix).map(|f| f.to_string()))
+}
+
 pub fn filter_node(
     config: &PlayoutConfig,
     node: Option<&Media>,
     filter_chain: &Option<Arc<Mutex<Vec<String>>>>,
 ) -> String {
     let mut filter = String::new();
-    let mut font = String::new();
-
-    if Path::new(&config.text.fontfile).is_file() {
-        font = format!(":fontfile='{}'", config.text.fontfile)
-    }
+    let font = font(config);
 
     let zmq_socket = match node.map(|n| n.unit) {
         Some(Ingest) => config.text.zmq_server_socket.clone(),
@@ -27,18 +66,7 @@ pub fn filter_node(
     };
 
     if config.text.text_from_filename && node.is_some() {
-        let source = node.unwrap_or(&Media::new(0, "", false)).source.clone();
-        let text = match Regex::new(&config.text.regex)
-            .ok()
-            .and_then(|r| r.captures(&source))
-        {
-            Some(t) => t[1].to_string(),
-            None => Path::new(&source)
-                .file_stem()
-                .unwrap_or_else(|| OsStr::new(&source))
-                .to_string_lossy()
-                .to_string(),
-        };
+        let text = node.unwrap_or(&Media::new(0, "", false)).get_title(config);
 
         let escaped_text = text
             .replace('\'', "'\\\\\\''")
@@ -50,13 +78,8 @@ pub fn filter_node(
             None => format!("drawtext=text='{escaped_text}':{}{font}", config.text.style),
         };
     } else if let Some(socket) = zmq_socket {
-        let mut filter_cmd = format!("text=''{font}");
-
-        if let Some(chain) = filter_chain {
-            if let Some(link) = chain.lock().unwrap().iter().find(|&l| l.contains("text")) {
-                filter_cmd = link.to_string();
-            }
-        }
+        let filter_cmd = stored_text(filter_chain, &format!("drawtext@{DEFAULT_TEXT_LAYER}"))
+            .unwrap_or(format!("text=''{font}"));
 
         filter = match &ADVANCED_CONFIG.decoder.filters.drawtext_from_zmq {
             Some(drawtext) => custom_format(drawtext, &[&socket.replace(':', "\\:"), &filter_cmd]),
@@ -65,6 +88,14 @@ pub fn filter_node(
                 socket.replace(':', "\\:")
             ),
         };
+
+        for layer in &config.text.layers {
+            let target = format!("drawtext@{}", layer.name);
+            let layer_text =
+                stored_text(filter_chain, &target).unwrap_or_else(|| layer_cmd(config, layer));
+
+            filter.push_str(&format!(",{target}={layer_text}"));
+        }
     }
 
     filter
@@ -12,5 +12,9 @@ pub mod utils;
 use utils::advanced_config::AdvancedConfig;
 
 lazy_static! {
+    /// Advanced settings, read once per process.
+    ///
+    /// All channels of an engine which runs with `--channels` share these settings,
+    /// so they use the same decoder and filter settings, acceleration profile and device.
     pub static ref ADVANCED_CONFIG: Arc<AdvancedConfig> = Arc::new(AdvancedConfig::new());
 }
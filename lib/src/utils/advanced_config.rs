@@ -10,11 +10,39 @@ use shlex::split;
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct AdvancedConfig {
     pub help: Option<String>,
+    #[serde(default)]
+    pub acceleration: HwAccel,
+    pub acceleration_device: Option<String>,
+    #[serde(default)]
+    pub acceleration_cpu_filters: bool,
     pub decoder: DecoderConfig,
     pub encoder: EncoderConfig,
     pub ingest: IngestConfig,
 }
 
+/// Hardware acceleration profile, for decoding and the video filters.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HwAccel {
+    None,
+    #[default]
+    Cuda,
+    Vaapi,
+    Qsv,
+}
+
+impl HwAccel {
+    /// Name from the ffmpeg hwaccel list.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::None => "none",
+            Self::Cuda => "cuda",
+            Self::Vaapi => "vaapi",
+            Self::Qsv => "qsv",
+        }
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct DecoderConfig {
     pub input_param: Option<String>,
@@ -43,9 +71,14 @@ pub struct IngestConfig {
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Filters {
     pub deinterlace: Option<String>,
+    pub tonemap: Option<String>,
+    pub colorspace: Option<String>,
     pub pad_scale_w: Option<String>,
     pub pad_scale_h: Option<String>,
     pub pad_video: Option<String>,
+    pub crop_video: Option<String>,
+    pub center_cut: Option<String>,
+    pub blur_fill: Option<String>,
     pub fps: Option<String>,
     pub scale: Option<String>,
     pub set_dar: Option<String>,
@@ -80,10 +113,7 @@ impl AdvancedConfig {
         }
 
         if let Ok(f) = File::open(&config_path) {
-            config = match serde_yaml::from_reader(f) {
-                Ok(yaml) => yaml,
-                Err(_) => AdvancedConfig::default(),
-            };
+            config = serde_yaml::from_reader(f).unwrap_or_default();
 
             if let Some(input_parm) = &config.decoder.input_param {
                 config.decoder.input_cmd = split(input_parm);
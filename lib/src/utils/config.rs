@@ -1,22 +1,31 @@
 use std::{
+    collections::HashMap,
     env, fmt,
     fs::File,
     path::{Path, PathBuf},
     process,
     str::FromStr,
+    sync::{Arc, Mutex},
 };
 
-use chrono::NaiveTime;
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
 use log::LevelFilter;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 use shlex::split;
 
 use crate::ADVANCED_CONFIG;
 
 use super::vec_strings;
-use crate::utils::{free_tcp_socket, home_dir, time_to_sec, OutputMode::*};
+use crate::utils::{
+    advanced_config::HwAccel, day_start_to_sec, free_tcp_socket, home_dir, time_to_sec,
+    OutputMode::*,
+};
 
 pub const DUMMY_LEN: f64 = 60.0;
+/// Text layer, which gets the messages without layer name
+pub const DEFAULT_TEXT_LAYER: &str = "dyntext";
 pub const IMAGE_FORMAT: [&str; 21] = [
     "bmp", "dds", "dpx", "exr", "gif", "hdr", "j2k", "jpg", "jpeg", "pcx", "pfm", "pgm", "phm",
     "png", "psd", "ppm", "sgi", "svg", "tga", "tif", "webp",
@@ -45,6 +54,29 @@ pub const FFMPEG_UNRECOVERABLE_ERRORS: [&str; 5] = [
     "Error while decoding stream #0:0: Invalid data found when processing input",
 ];
 
+/// Config fields, which can be changed while the playout is running.
+/// They get used from the next clip on, all other changes need a restart.
+pub const RELOAD_FIELDS: [&str; 18] = [
+    "processing.add_logo",
+    "processing.logo",
+    "processing.logo_scale",
+    "processing.logo_opacity",
+    "processing.logo_position",
+    "processing.logo_layers",
+    "processing.volume",
+    "processing.custom_filter",
+    "text.fontfile",
+    "text.style",
+    "text.regex",
+    "text.title_from_tags",
+    "storage.path",
+    "storage.filler",
+    "storage.extensions",
+    "storage.shuffle",
+    "storage.best_fit",
+    "playlist.path",
+];
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum OutputMode {
@@ -75,6 +107,31 @@ pub enum ProcessMode {
     Playlist,
 }
 
+/// Subtitles get burned in the picture, or passed as subtitle stream (only in HLS mode).
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleMode {
+    #[default]
+    Burn,
+    Pass,
+}
+
+/// How the picture fits in the target size, when the aspect ratio is different.
+///
+/// `pad` adds black bars, `crop` cuts the picture to fill the frame, `stretch` scales without
+/// keeping the aspect, `center_cut` cuts the 4:3 center from 16:9 material and `blur` fills
+/// the bars with a blurred copy of the picture.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FitMode {
+    #[default]
+    Pad,
+    Crop,
+    Stretch,
+    CenterCut,
+    Blur,
+}
+
 impl fmt::Display for ProcessMode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -113,6 +170,22 @@ where
     }
 }
 
+/// Timezone name from the IANA database, empty value means system timezone.
+fn string_to_timezone<'de, D>(deserializer: D) -> Result<Option<Tz>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Deserialize::deserialize(deserializer)?;
+
+    match s.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(name) => name
+            .parse::<Tz>()
+            .map(Some)
+            .map_err(|_| de::Error::custom(format!("Timezone \"{name}\" not exists!"))),
+    }
+}
+
 fn log_level_to_string<S>(l: &LevelFilter, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -179,9 +252,21 @@ pub struct General {
     #[serde(skip_serializing, skip_deserializing)]
     pub ffmpeg_libs: Vec<String>,
 
+    /// Acceleration profile from the advanced config, falls back to none when it is not available.
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub hw_accel: HwAccel,
+
+    /// Run fps, fade and tpad also with an acceleration profile.
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub hw_cpu_filters: bool,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub template: Option<Template>,
 
+    /// Tolerance in seconds, for generating playlists with exact length.
+    #[serde(default, skip_serializing, skip_deserializing)]
+    pub exact_length: Option<f64>,
+
     #[serde(default, skip_serializing, skip_deserializing)]
     pub skip_validation: bool,
 
@@ -231,6 +316,9 @@ pub struct Logging {
     pub detect_silence: bool,
     #[serde(default)]
     pub ignore_lines: Vec<String>,
+    /// mark log lines with the thread name, which is the channel name in multi channel mode
+    #[serde(skip_serializing, skip_deserializing)]
+    pub channel_names: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -248,22 +336,71 @@ pub struct Processing {
     pub width: i64,
     pub height: i64,
     pub aspect: f64,
+    #[serde(default)]
+    pub fit: FitMode,
     pub fps: f64,
     pub add_logo: bool,
     pub logo: String,
     pub logo_scale: String,
     pub logo_opacity: f32,
     pub logo_position: String,
+    #[serde(default)]
+    pub logo_layers: Vec<LogoLayer>,
     #[serde(default = "default_tracks")]
     pub audio_tracks: i32,
     #[serde(default = "default_channels")]
     pub audio_channels: u8,
     pub volume: f64,
+    /// Two pass loudness normalization, with a gain per clip from the measured loudness.
+    #[serde(default)]
+    pub normalize_loudness: bool,
+    #[serde(default = "default_loudness_target")]
+    pub loudness_target: f64,
+    #[serde(default = "default_true_peak")]
+    pub loudness_true_peak: f64,
+    #[serde(default)]
+    pub loudness_cache: PathBuf,
     #[serde(default)]
     pub custom_filter: String,
+    #[serde(default)]
+    pub subtitle_mode: SubtitleMode,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub cmd: Option<Vec<String>>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub logo_zmq_socket: Option<String>,
+
+    /// Logo layer states, which are switched on air. They are shared between all clones from the channel config.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub logo_overrides: Arc<Mutex<HashMap<String, bool>>>,
+}
+
+/// Additional logo, which is shown over the main logo, like a "LIVE" badge or a sponsor logo.
+///
+/// Each layer gets its own filter `colorchannelmixer@logo_<name>`, which hides the logo and can be switched over zmq.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct LogoLayer {
+    pub name: String,
+    pub path: String,
+    #[serde(default = "default_logo_position")]
+    pub position: String,
+    #[serde(default)]
+    pub scale: String,
+    #[serde(default = "default_logo_opacity")]
+    pub opacity: f32,
+    #[serde(default = "default_true")]
+    pub enable: bool,
+    /// Show the logo only on clips from this categories, empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hide_categories: Vec<String>,
+    /// Daily time window in format hh:mm:ss, in the playlist timezone.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub from_time: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub to_time: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -284,6 +421,9 @@ pub struct Playlist {
     pub path: PathBuf,
     pub day_start: String,
 
+    #[serde(default, deserialize_with = "string_to_timezone")]
+    pub timezone: Option<Tz>,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub start_sec: Option<f64>,
 
@@ -292,6 +432,10 @@ pub struct Playlist {
     #[serde(skip_serializing, skip_deserializing)]
     pub length_sec: Option<f64>,
 
+    /// First day from the running playlist, when it has a multi day length.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub first_day: Option<NaiveDate>,
+
     pub infinit: bool,
 }
 
@@ -305,6 +449,8 @@ pub struct Storage {
     pub filler: PathBuf,
     pub extensions: Vec<String>,
     pub shuffle: bool,
+    #[serde(default)]
+    pub best_fit: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -323,8 +469,40 @@ pub struct Text {
 
     pub fontfile: String,
     pub text_from_filename: bool,
+    #[serde(default)]
+    pub title_from_tags: bool,
     pub style: String,
     pub regex: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<TextLayer>,
+}
+
+impl Text {
+    /// Get the zmq target from a text layer, messages without layer go to the default layer.
+    pub fn layer_target(&self, layer: Option<&str>) -> Option<String> {
+        match layer.filter(|l| !l.is_empty()) {
+            None => Some(format!("drawtext@{DEFAULT_TEXT_LAYER}")),
+            Some(name)
+                if name == DEFAULT_TEXT_LAYER || self.layers.iter().any(|l| l.name == name) =>
+            {
+                Some(format!("drawtext@{name}"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Additional text layer, with its own drawtext filter `drawtext@<name>`.
+///
+/// `text` is shown from start on and is used unescaped, to support drawtext expansions like `%{localtime}`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct TextLayer {
+    pub name: String,
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub style: String,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -360,9 +538,49 @@ fn default_channels() -> u8 {
     2
 }
 
+fn default_loudness_target() -> f64 {
+    -23.0
+}
+
+fn default_true_peak() -> f64 {
+    -1.0
+}
+
+fn default_logo_position() -> String {
+    "W-w-12:12".to_string()
+}
+
+fn default_logo_opacity() -> f32 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
 impl PlayoutConfig {
     /// Read config from YAML file, and set some extra config values.
     pub fn new(cfg_path: Option<PathBuf>) -> Self {
+        let config_path = Self::config_path(cfg_path);
+
+        let f = match File::open(&config_path) {
+            Ok(file) => file,
+            Err(_) => {
+                eprintln!(
+                    "ffplayout.yml not found!\nPut \"ffplayout.yml\" in \"/etc/playout/\" or beside the executable!"
+                );
+                process::exit(1);
+            }
+        };
+
+        let config: PlayoutConfig =
+            serde_yaml::from_reader(f).expect("Could not read config file.");
+
+        config.prepare(&config_path.to_string_lossy())
+    }
+
+    /// Find the config file, fall back to the assets folder or the folder from the executable.
+    pub fn config_path(cfg_path: Option<PathBuf>) -> PathBuf {
         let mut config_path = PathBuf::from("/etc/ffplayout/ffplayout.yml");
 
         if let Some(cfg) = cfg_path {
@@ -377,21 +595,26 @@ impl PlayoutConfig {
             };
         }
 
-        let f = match File::open(&config_path) {
-            Ok(file) => file,
-            Err(_) => {
-                eprintln!(
-                    "ffplayout.yml not found!\nPut \"ffplayout.yml\" in \"/etc/playout/\" or beside the executable!"
-                );
-                process::exit(1);
-            }
-        };
+        config_path
+    }
 
-        let mut config: PlayoutConfig =
-            serde_yaml::from_reader(f).expect("Could not read config file.");
+    /// Read config from YAML content, like the export from ffpapi.
+    ///
+    /// `source` is only used as info, from where the config comes.
+    pub fn from_yaml(content: &str, source: &str) -> Result<Self, serde_yaml::Error> {
+        let config: PlayoutConfig = serde_yaml::from_str(content)?;
+
+        Ok(config.prepare(source))
+    }
+
+    /// Fill the runtime values, which are not part of the config file.
+    fn prepare(self, config_path: &str) -> Self {
+        let mut config = self;
         config.general.generate = None;
 
-        config.general.config_path = config_path.to_string_lossy().to_string();
+        config.general.config_path = config_path.to_string();
+        config.general.hw_accel = ADVANCED_CONFIG.acceleration;
+        config.general.hw_cpu_filters = ADVANCED_CONFIG.acceleration_cpu_filters;
 
         config.general.stat_file = home_dir()
             .unwrap_or_else(env::temp_dir)
@@ -403,11 +626,20 @@ impl PlayoutConfig {
             .display()
             .to_string();
 
+        if config.processing.loudness_cache.as_os_str().is_empty() {
+            config.processing.loudness_cache = home_dir()
+                .unwrap_or_else(env::temp_dir)
+                .join(".ffp_loudness");
+        }
+
         if config.logging.ingest_level.is_none() {
             config.logging.ingest_level = Some(config.logging.ffmpeg_level.clone())
         }
 
-        config.playlist.start_sec = Some(time_to_sec(&config.playlist.day_start));
+        config.playlist.start_sec = Some(day_start_to_sec(
+            &config.playlist.day_start,
+            config.playlist.timezone,
+        ));
 
         if config.playlist.length.contains(':') {
             config.playlist.length_sec = Some(time_to_sec(&config.playlist.length));
@@ -524,10 +756,92 @@ impl PlayoutConfig {
             config.text.node_pos = None;
         }
 
+        // logo layers get their own zmq socket in the decoder, to switch them on air
+        if config.processing.logo_layers.is_empty() {
+            config.processing.logo_zmq_socket = None;
+        } else {
+            let text_sockets = [
+                config.text.zmq_stream_socket.clone(),
+                config.text.zmq_server_socket.clone(),
+            ];
+
+            config.rpc_server.enable = true;
+            config.processing.logo_zmq_socket = (0..10)
+                .filter_map(|_| free_tcp_socket(text_sockets[0].clone().unwrap_or_default()))
+                .find(|s| !text_sockets.contains(&Some(s.clone())));
+        }
+
         config
     }
 }
 
+impl PlayoutConfig {
+    /// Compare with a new config, and sort the changed fields in the ones,
+    /// which can be reloaded and the ones which need a restart.
+    pub fn changes(&self, new_config: &Self) -> ConfigChanges {
+        let mut changes = ConfigChanges::default();
+
+        let (Ok(Value::Object(old)), Ok(Value::Object(new))) =
+            (serde_json::to_value(self), serde_json::to_value(new_config))
+        else {
+            return changes;
+        };
+
+        for (section, values) in &new {
+            let (Some(Value::Object(old_values)), Value::Object(values)) =
+                (old.get(section), values)
+            else {
+                continue;
+            };
+
+            for (key, value) in values {
+                if key == "help_text" || old_values.get(key) == Some(value) {
+                    continue;
+                }
+
+                let field = format!("{section}.{key}");
+
+                if RELOAD_FIELDS.contains(&field.as_str()) {
+                    changes.applied.push(field);
+                } else {
+                    changes.restart.push(field);
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// Take over the reloadable fields from a new config.
+    pub fn apply_reload(&mut self, new_config: &Self) {
+        let processing = &new_config.processing;
+
+        self.processing.add_logo = processing.add_logo;
+        self.processing.logo = processing.logo.clone();
+        self.processing.logo_scale = processing.logo_scale.clone();
+        self.processing.logo_opacity = processing.logo_opacity;
+        self.processing.logo_position = processing.logo_position.clone();
+        self.processing.logo_layers = processing.logo_layers.clone();
+        self.processing.volume = processing.volume;
+        self.processing.custom_filter = processing.custom_filter.clone();
+
+        self.text.fontfile = new_config.text.fontfile.clone();
+        self.text.style = new_config.text.style.clone();
+        self.text.regex = new_config.text.regex.clone();
+        self.text.title_from_tags = new_config.text.title_from_tags;
+
+        self.storage = new_config.storage.clone();
+        self.playlist.path = new_config.playlist.path.clone();
+    }
+}
+
+/// Result from a config reload.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigChanges {
+    pub applied: Vec<String>,
+    pub restart: Vec<String>,
+}
+
 impl Default for PlayoutConfig {
     fn default() -> Self {
         Self::new(None)
@@ -0,0 +1,499 @@
+/// Config Check
+///
+/// Validate a playout config without starting the playout. Problems are collected as errors,
+/// which would stop the playout, or warnings, where the engine falls back to defaults.
+///
+/// You can call ffplayout[.exe] --check-config
+use std::{fmt, net::SocketAddr, path::Path};
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{
+    decoder_input_args, gen_dummy, is_remote, prepare_output_cmd, validate_ffmpeg, Media,
+    OutputMode, PlayoutConfig, ProcessMode, ProcessUnit, SubtitleMode, DEFAULT_TEXT_LAYER,
+    DUMMY_LEN,
+};
+use crate::{vec_strings, ADVANCED_CONFIG};
+
+const FFMPEG_LEVELS: [&str; 9] = [
+    "quiet", "panic", "fatal", "error", "warning", "info", "verbose", "debug", "trace",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// config is no valid YAML, or has wrong value types
+    Syntax,
+    /// value is not allowed
+    Value,
+    /// file or folder not exists
+    Path,
+    /// ffmpeg not exists, or misses a filter or library
+    Ffmpeg,
+    /// ffmpeg command line can't be build
+    Command,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    pub kind: IssueKind,
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+/// Result from the config check, with the test build ffmpeg commands.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigCheck {
+    pub errors: Vec<ConfigIssue>,
+    pub warnings: Vec<ConfigIssue>,
+    pub decoder_cmd: Vec<String>,
+    pub encoder_cmd: Vec<String>,
+}
+
+impl ConfigCheck {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, kind: IssueKind, field: &str, message: impl Into<String>) {
+        self.errors.push(ConfigIssue {
+            kind,
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn warning(&mut self, kind: IssueKind, field: &str, message: impl Into<String>) {
+        self.warnings.push(ConfigIssue {
+            kind,
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+}
+
+fn check_values(config: &PlayoutConfig, check: &mut ConfigCheck) {
+    if config.general.stop_threshold > 0.0 && config.general.stop_threshold < 3.0 {
+        check.warning(
+            IssueKind::Value,
+            "general.stop_threshold",
+            "values below 3 can cause unexpected errors",
+        );
+    }
+
+    if !FFMPEG_LEVELS.contains(&config.logging.ffmpeg_level.to_lowercase().as_str()) {
+        check.warning(
+            IssueKind::Value,
+            "logging.ffmpeg_level",
+            format!("unknown level '{}'", config.logging.ffmpeg_level),
+        );
+    }
+
+    if config.processing.width <= 0 || config.processing.height <= 0 {
+        check.error(
+            IssueKind::Value,
+            "processing.width",
+            "width and height must be greater than 0",
+        );
+    }
+
+    if config.processing.fps <= 0.0 {
+        check.error(IssueKind::Value, "processing.fps", "must be greater than 0");
+    }
+
+    if !matches!(config.processing.audio_channels, 2 | 4 | 6 | 8) {
+        check.error(
+            IssueKind::Value,
+            "processing.audio_channels",
+            "only 2, 4, 6 and 8 channels are supported",
+        );
+    }
+
+    if !(0.0..=1.0).contains(&config.processing.logo_opacity) {
+        check.warning(
+            IssueKind::Value,
+            "processing.logo_opacity",
+            "must be between 0.0 and 1.0",
+        );
+    }
+
+    if NaiveTime::parse_from_str(&config.playlist.day_start, "%H:%M:%S%.f").is_err() {
+        check.error(
+            IssueKind::Value,
+            "playlist.day_start",
+            format!("'{}' is not in format hh:mm:ss", config.playlist.day_start),
+        );
+    }
+
+    let length = &config.playlist.length;
+    let length_hours = length
+        .split(':')
+        .next()
+        .and_then(|h| h.parse::<u32>().ok())
+        .unwrap_or_default();
+
+    if length.contains(':') && length_hours >= 24 {
+        if !length_hours.is_multiple_of(24) || !length.ends_with(":00:00") {
+            check.error(
+                IssueKind::Value,
+                "playlist.length",
+                format!("'{length}' is over one day, but no multiple of 24:00:00"),
+            );
+        }
+    } else if length.contains(':') && NaiveTime::parse_from_str(length, "%H:%M:%S%.f").is_err() {
+        check.error(
+            IssueKind::Value,
+            "playlist.length",
+            format!("'{length}' is not in format hh:mm:ss"),
+        );
+    }
+
+    if config.rpc_server.enable && config.rpc_server.address.parse::<SocketAddr>().is_err() {
+        check.error(
+            IssueKind::Value,
+            "rpc_server.address",
+            format!("'{}' is not a valid IP:PORT", config.rpc_server.address),
+        );
+    }
+
+    let mut layer_names = vec![DEFAULT_TEXT_LAYER];
+
+    for layer in &config.text.layers {
+        if layer.name.is_empty()
+            || !layer
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            check.error(
+                IssueKind::Value,
+                "text.layers",
+                format!(
+                    "'{}' is no valid layer name, use only letters, numbers and _",
+                    layer.name
+                ),
+            );
+        } else if layer_names.contains(&layer.name.as_str()) {
+            check.error(
+                IssueKind::Value,
+                "text.layers",
+                format!("layer name '{}' is not unique", layer.name),
+            );
+        }
+
+        layer_names.push(&layer.name);
+    }
+
+    if !config.text.layers.is_empty() && config.text.text_from_filename {
+        check.warning(
+            IssueKind::Value,
+            "text.layers",
+            "layers are ignored, when text_from_filename is on",
+        );
+    }
+
+    let mut logo_names: Vec<&str> = vec![];
+
+    for layer in &config.processing.logo_layers {
+        if layer.name.is_empty()
+            || !layer
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            check.error(
+                IssueKind::Value,
+                "processing.logo_layers",
+                format!(
+                    "'{}' is no valid layer name, use only letters, numbers and _",
+                    layer.name
+                ),
+            );
+        } else if logo_names.contains(&layer.name.as_str()) {
+            check.error(
+                IssueKind::Value,
+                "processing.logo_layers",
+                format!("layer name '{}' is not unique", layer.name),
+            );
+        }
+
+        for time in [&layer.from_time, &layer.to_time] {
+            if !time.is_empty() && NaiveTime::parse_from_str(time, "%H:%M:%S%.f").is_err() {
+                check.error(
+                    IssueKind::Value,
+                    "processing.logo_layers",
+                    format!(
+                        "'{time}' from layer '{}' is not in format hh:mm:ss",
+                        layer.name
+                    ),
+                );
+            }
+        }
+
+        logo_names.push(&layer.name);
+    }
+
+    if config.processing.normalize_loudness {
+        if !(-70.0..=-5.0).contains(&config.processing.loudness_target) {
+            check.error(
+                IssueKind::Value,
+                "processing.loudness_target",
+                "must be between -70 and -5 LUFS",
+            );
+        }
+
+        if !(-9.0..=0.0).contains(&config.processing.loudness_true_peak) {
+            check.error(
+                IssueKind::Value,
+                "processing.loudness_true_peak",
+                "must be between -9 and 0 dBTP",
+            );
+        }
+
+        if config.processing.copy_audio {
+            check.warning(
+                IssueKind::Value,
+                "processing.normalize_loudness",
+                "has no effect in audio copy mode",
+            );
+        }
+    }
+
+    if config.processing.subtitle_mode == SubtitleMode::Pass && config.out.mode != OutputMode::HLS {
+        check.warning(
+            IssueKind::Value,
+            "processing.subtitle_mode",
+            "subtitles can only pass in HLS mode, they get burned in",
+        );
+    }
+
+    if config.out.mode != OutputMode::Null && config.out.output_param.trim().is_empty() {
+        check.error(IssueKind::Value, "out.output_param", "is empty");
+    }
+}
+
+fn check_paths(config: &PlayoutConfig, check: &mut ConfigCheck) {
+    let playlist_path = config.playlist.path.to_string_lossy();
+
+    if !is_remote(&playlist_path) && !config.playlist.path.exists() {
+        let message = format!("'{playlist_path}' not exists");
+
+        if config.processing.mode == ProcessMode::Playlist {
+            check.error(IssueKind::Path, "playlist.path", message);
+        } else {
+            check.warning(IssueKind::Path, "playlist.path", message);
+        }
+    }
+
+    if !config.storage.path.is_dir() {
+        let message = format!("'{}' not exists", config.storage.path.display());
+
+        if config.processing.mode == ProcessMode::Folder {
+            check.error(IssueKind::Path, "storage.path", message);
+        } else {
+            check.warning(IssueKind::Path, "storage.path", message);
+        }
+    }
+
+    if !config.storage.filler.as_os_str().is_empty() && !config.storage.filler.exists() {
+        check.warning(
+            IssueKind::Path,
+            "storage.filler",
+            format!(
+                "'{}' not exists, a dummy clip is used instead",
+                config.storage.filler.display()
+            ),
+        );
+    }
+
+    if config.processing.add_logo && !Path::new(&config.processing.logo).is_file() {
+        check.warning(
+            IssueKind::Path,
+            "processing.logo",
+            format!(
+                "'{}' not exists, logo gets disabled",
+                config.processing.logo
+            ),
+        );
+    }
+
+    for layer in &config.processing.logo_layers {
+        if !Path::new(&layer.path).is_file() {
+            check.warning(
+                IssueKind::Path,
+                "processing.logo_layers",
+                format!(
+                    "'{}' not exists, layer '{}' is skipped",
+                    layer.path, layer.name
+                ),
+            );
+        }
+    }
+
+    if config.text.add_text && !Path::new(&config.text.fontfile).is_file() {
+        check.warning(
+            IssueKind::Path,
+            "text.fontfile",
+            format!(
+                "'{}' not exists, the default font is used",
+                config.text.fontfile
+            ),
+        );
+    }
+
+    if config.logging.log_to_file && !config.logging.path.is_dir() && !config.logging.path.is_file()
+    {
+        check.warning(
+            IssueKind::Path,
+            "logging.path",
+            format!(
+                "'{}' not exists, logs/ffplayout.log is used instead",
+                config.logging.path.display()
+            ),
+        );
+    }
+
+    if config.task.enable && !config.task.path.is_file() {
+        check.warning(
+            IssueKind::Path,
+            "task.path",
+            format!("'{}' not exists", config.task.path.display()),
+        );
+    }
+}
+
+/// Build the decoder and encoder commands, like the engine does for a clip.
+fn check_commands(config: &PlayoutConfig, check: &mut ConfigCheck) {
+    if config.ingest.enable && config.ingest.input_cmd.is_none() {
+        check.error(
+            IssueKind::Command,
+            "ingest.input_param",
+            "can't split parameters",
+        );
+    }
+
+    match &config.out.output_cmd {
+        None => {
+            check.error(
+                IssueKind::Command,
+                "out.output_param",
+                "can't split parameters",
+            );
+
+            return;
+        }
+        Some(cmd) if cmd.is_empty() => return,
+        Some(_) => (),
+    }
+
+    if config.out.output_count == 0 {
+        check.error(IssueKind::Command, "out.output_param", "no output target");
+
+        return;
+    }
+
+    if config.out.mode == OutputMode::HLS && !config.out.output_param.contains(".m3u8") {
+        check.warning(
+            IssueKind::Command,
+            "out.output_param",
+            "HLS output has no .m3u8 playlist",
+        );
+    }
+
+    // build with the dummy clip, which is also used for missing clips
+    let (source, cmd) = gen_dummy(config, DUMMY_LEN);
+    let mut media = Media::new(0, "", false);
+    media.source = source;
+    media.cmd = Some(cmd);
+    media.out = DUMMY_LEN;
+    media.duration = DUMMY_LEN;
+    media.add_filter(config, &None);
+
+    let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-v", "level+error"];
+
+    dec_cmd.append(&mut decoder_input_args(
+        &media,
+        config,
+        &ADVANCED_CONFIG.decoder.input_cmd,
+    ));
+    dec_cmd.append(&mut media.cmd.clone().unwrap_or_default());
+
+    if let Some(mut filter) = media.filter {
+        dec_cmd.append(&mut filter.cmd());
+        dec_cmd.append(&mut filter.map());
+    }
+
+    if let Some(mut cmd) = config.processing.cmd.clone() {
+        dec_cmd.append(&mut cmd);
+    }
+
+    check.decoder_cmd = dec_cmd;
+
+    if config.out.mode == OutputMode::Stream || config.out.mode == OutputMode::Null {
+        let mut media = Media::new(0, "", false);
+        media.unit = ProcessUnit::Encoder;
+        media.add_filter(config, &None);
+
+        let enc_prefix = vec_strings![
+            "-hide_banner",
+            "-nostats",
+            "-v",
+            "level+error",
+            "-re",
+            "-i",
+            "pipe:0"
+        ];
+
+        check.encoder_cmd = prepare_output_cmd(config, enc_prefix, &media.filter);
+    }
+}
+
+/// Check config content and return all found errors and warnings.
+///
+/// With `check_ffmpeg` also the ffmpeg installation is validated,
+/// together with the filters and libraries the config needs.
+pub fn check_config(content: &str, check_ffmpeg: bool) -> ConfigCheck {
+    let mut check = ConfigCheck::default();
+
+    let raw_config: PlayoutConfig = match serde_yaml::from_str(content) {
+        Ok(config) => config,
+        Err(e) => {
+            check.error(IssueKind::Syntax, "", e.to_string());
+
+            return check;
+        }
+    };
+
+    check_values(&raw_config, &mut check);
+    check_paths(&raw_config, &mut check);
+
+    // preparing the config can panic on invalid values, like a wrong time format
+    if check.errors.iter().any(|e| e.kind == IssueKind::Value) {
+        return check;
+    }
+
+    let Ok(mut config) = PlayoutConfig::from_yaml(content, "") else {
+        return check;
+    };
+
+    if check_ffmpeg && config.out.output_cmd.is_some() {
+        if let Err(e) = validate_ffmpeg(&mut config) {
+            check.error(IssueKind::Ffmpeg, "", e);
+        }
+    }
+
+    check_commands(&config, &mut check);
+
+    check
+}
@@ -1,6 +1,6 @@
 use std::{
     fmt,
-    process::Child,
+    process::{self, Child},
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Mutex,
@@ -13,7 +13,7 @@ use signal_child::Signalable;
 use serde::{Deserialize, Serialize};
 use simplelog::*;
 
-use crate::utils::Media;
+use crate::utils::{Media, PlayoutConfig};
 
 /// Defined process units.
 #[derive(Clone, Debug, Default, Copy, Eq, Serialize, Deserialize, PartialEq)]
@@ -48,6 +48,8 @@ pub struct ProcessControl {
     pub server_is_running: Arc<AtomicBool>,
     pub is_terminated: Arc<AtomicBool>,
     pub is_alive: Arc<AtomicBool>,
+    pub is_failed: Arc<AtomicBool>,
+    pub exit_on_error: Arc<AtomicBool>,
 }
 
 impl ProcessControl {
@@ -59,6 +61,8 @@ impl ProcessControl {
             server_is_running: Arc::new(AtomicBool::new(false)),
             is_terminated: Arc::new(AtomicBool::new(false)),
             is_alive: Arc::new(AtomicBool::new(true)),
+            is_failed: Arc::new(AtomicBool::new(false)),
+            exit_on_error: Arc::new(AtomicBool::new(true)),
         }
     }
 }
@@ -163,6 +167,28 @@ impl ProcessControl {
     }
 }
 
+impl ProcessControl {
+    /// Stop playout after an unrecoverable error.
+    ///
+    /// A single channel engine exits, to get restarted from systemd or ffpapi.
+    /// In multi channel mode only the channel stops, and the channel runner restarts it.
+    pub fn fail(&self) {
+        self.is_failed.store(true, Ordering::SeqCst);
+        self.stop_all();
+
+        if self.exit_on_error.load(Ordering::SeqCst) {
+            process::exit(1);
+        }
+    }
+
+    /// Reset the states from a stopped playout, to start it again.
+    pub fn reset(&self) {
+        self.is_terminated.store(false, Ordering::SeqCst);
+        self.is_alive.store(true, Ordering::SeqCst);
+        self.is_failed.store(false, Ordering::SeqCst);
+    }
+}
+
 // impl Drop for ProcessControl {
 //     fn drop(&mut self) {
 //         self.stop_all()
@@ -177,6 +203,9 @@ pub struct PlayerControl {
     pub filler_list: Arc<Mutex<Vec<Media>>>,
     pub current_index: Arc<AtomicUsize>,
     pub filler_index: Arc<AtomicUsize>,
+    pub last_fillers: Arc<Mutex<Vec<String>>>,
+    pub clip_count: Arc<AtomicUsize>,
+    pub config_reload: Arc<Mutex<Option<PlayoutConfig>>>,
 }
 
 impl PlayerControl {
@@ -187,6 +216,9 @@ impl PlayerControl {
             filler_list: Arc::new(Mutex::new(vec![])),
             current_index: Arc::new(AtomicUsize::new(0)),
             filler_index: Arc::new(AtomicUsize::new(0)),
+            last_fillers: Arc::new(Mutex::new(vec![])),
+            clip_count: Arc::new(AtomicUsize::new(0)),
+            config_reload: Arc::new(Mutex::new(None)),
         }
     }
 }
@@ -210,7 +242,7 @@ pub struct PlayoutStatus {
 impl PlayoutStatus {
     pub fn new() -> Self {
         Self {
-            chain: None,
+            chain: Some(Arc::new(Mutex::new(vec![]))),
             current_date: Arc::new(Mutex::new(String::new())),
             date: Arc::new(Mutex::new(String::new())),
             list_init: Arc::new(AtomicBool::new(true)),
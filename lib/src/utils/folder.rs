@@ -104,6 +104,32 @@ impl FolderSource {
         }
     }
 
+    /// Take over config changes, which got reloaded while playing.
+    fn check_config_reload(&mut self) {
+        let Some(config) = self.player_control.config_reload.lock().unwrap().take() else {
+            return;
+        };
+
+        let rescan = self.config.storage.path != config.storage.path
+            || self.config.storage.extensions != config.storage.extensions;
+
+        self.config.apply_reload(&config);
+
+        if rescan {
+            info!(
+                "Read new storage: <b><magenta>{:?}</></b>",
+                self.config.storage.path
+            );
+
+            *self = Self::new(
+                &self.config,
+                self.filter_chain.clone(),
+                &self.player_control,
+            );
+            self.player_control.current_index.store(0, Ordering::SeqCst);
+        }
+    }
+
     fn shuffle(&mut self) {
         let mut rng = thread_rng();
         let mut nodes = self.player_control.current_list.lock().unwrap();
@@ -131,6 +157,8 @@ impl Iterator for FolderSource {
     type Item = Media;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.check_config_reload();
+
         if self.player_control.current_index.load(Ordering::SeqCst)
             < self.player_control.current_list.lock().unwrap().len()
         {
@@ -139,7 +167,7 @@ impl Iterator for FolderSource {
             let _ = self.current_node.add_probe(false).ok();
             self.current_node
                 .add_filter(&self.config, &self.filter_chain);
-            self.current_node.begin = Some(time_in_seconds());
+            self.current_node.begin = Some(time_in_seconds(&self.config));
 
             self.player_control
                 .current_index
@@ -165,7 +193,7 @@ impl Iterator for FolderSource {
             let _ = self.current_node.add_probe(false).ok();
             self.current_node
                 .add_filter(&self.config, &self.filter_chain);
-            self.current_node.begin = Some(time_in_seconds());
+            self.current_node.begin = Some(time_in_seconds(&self.config));
 
             self.player_control.current_index.store(1, Ordering::SeqCst);
 
@@ -191,7 +219,8 @@ pub fn fill_filler_list(
         {
             let mut media = Media::new(index, &entry.path().to_string_lossy(), false);
 
-            if player_control.is_none() {
+            // Best fit needs the durations in advance.
+            if player_control.is_none() || config.storage.best_fit {
                 if let Err(e) = media.add_probe(false) {
                     error!("{e:?}");
                 };
@@ -233,3 +262,171 @@ pub fn fill_filler_list(
 
     filler_list
 }
+
+/// Subset sum over the candidates, each clip can be used only once.
+/// Returns for every reachable frame count the clip which reaches it first.
+pub(crate) fn reachable_frames(
+    frames: &[usize],
+    candidates: &[usize],
+    limit: usize,
+) -> Vec<Option<usize>> {
+    let mut reach: Vec<Option<usize>> = vec![None; limit + 1];
+    reach[0] = Some(usize::MAX);
+
+    for &i in candidates {
+        let length = frames[i];
+
+        if length == 0 || length > limit {
+            continue;
+        }
+
+        for sum in (length..=limit).rev() {
+            if reach[sum].is_none() && reach[sum - length].is_some() {
+                reach[sum] = Some(i);
+            }
+        }
+    }
+
+    reach
+}
+
+pub(crate) fn collect_clips(
+    frames: &[usize],
+    reach: &[Option<usize>],
+    mut sum: usize,
+) -> Vec<usize> {
+    let mut clips = vec![];
+
+    while sum > 0 {
+        match reach[sum] {
+            Some(i) if i != usize::MAX => {
+                clips.push(i);
+                sum -= frames[i];
+            }
+            _ => break,
+        }
+    }
+
+    clips.reverse();
+    clips
+}
+
+/// Best fit filler
+///
+/// Pick a combination of clips from the filler list, which durations sum up to the length within one frame.
+/// Clips are not repeated, unless the length is longer then all fillers together,
+/// and clips from `last_used` are only taken when there is no other fit.
+/// Only when no combination fits, the longest clip from the next bigger combination gets cut.
+pub fn best_fit_filler(
+    config: &PlayoutConfig,
+    filler_list: &[Media],
+    length: f64,
+    last_used: &[String],
+) -> Vec<Media> {
+    let fps = config.processing.fps;
+    let fillers: Vec<&Media> = filler_list
+        .iter()
+        .filter(|m| m.duration > 0.0 && (m.duration * fps).round() >= 1.0)
+        .collect();
+    let mut fill_list = vec![];
+
+    if fillers.is_empty() || length <= 0.0 {
+        return fill_list;
+    }
+
+    let frames: Vec<usize> = fillers
+        .iter()
+        .map(|m| (m.duration * fps).round() as usize)
+        .collect();
+    let total_frames: usize = frames.iter().sum();
+    let mut target = (length * fps).round() as usize;
+
+    // Gap is longer then all fillers together, play them in full rounds.
+    while target > total_frames {
+        for filler in &fillers {
+            let mut media = (*filler).clone();
+            media.seek = 0.0;
+            media.out = media.duration;
+
+            fill_list.push(media);
+        }
+
+        target -= total_frames;
+    }
+
+    let all: Vec<usize> = (0..fillers.len()).collect();
+    let fresh: Vec<usize> = all
+        .iter()
+        .copied()
+        .filter(|i| !last_used.contains(&fillers[*i].source))
+        .collect();
+    let max_frames = frames.iter().max().copied().unwrap_or_default();
+    let mut clips = vec![];
+    let mut cut = false;
+    let mut candidate_lists = vec![&all];
+
+    if !fresh.is_empty() && fresh.len() < all.len() {
+        candidate_lists.insert(0, &fresh);
+    }
+
+    for candidates in candidate_lists {
+        let reach = reachable_frames(&frames, candidates, target + 1);
+
+        if let Some(sum) = [target, target.saturating_sub(1), target + 1]
+            .into_iter()
+            .find(|s| reach[*s].is_some())
+        {
+            clips = collect_clips(&frames, &reach, sum);
+            break;
+        }
+
+        if candidates.len() == all.len() {
+            // No exact fit, take the smallest combination which is longer then the gap.
+            let reach = reachable_frames(&frames, candidates, target + max_frames);
+
+            if let Some(sum) = (target + 2..=target + max_frames).find(|s| reach[*s].is_some()) {
+                clips = collect_clips(&frames, &reach, sum);
+                cut = true;
+            }
+        }
+    }
+
+    let mut best_fit: Vec<Media> = clips
+        .iter()
+        .map(|i| {
+            let mut media = fillers[*i].clone();
+            media.seek = 0.0;
+            media.out = media.duration;
+
+            media
+        })
+        .collect();
+
+    if cut {
+        if let Some(longest) = (0..best_fit.len()).max_by(|a, b| {
+            best_fit[*a]
+                .duration
+                .partial_cmp(&best_fit[*b].duration)
+                .unwrap()
+        }) {
+            let rest: f64 = fill_list.iter().map(|m| m.out).sum::<f64>()
+                + best_fit
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != longest)
+                    .map(|(_, m)| m.out)
+                    .sum::<f64>();
+
+            best_fit[longest].out = length - rest;
+
+            debug!(
+                "No filler combination fits, cut <b><magenta>{}</></b> to <yellow>{:.3}</> seconds",
+                best_fit[longest].source, best_fit[longest].out
+            );
+        }
+    }
+
+    fill_list.append(&mut best_fit);
+
+    fill_list
+}
@@ -5,6 +5,7 @@
 /// The generator takes the files from storage, which are set in config.
 /// It also respect the shuffle/sort mode.
 use std::{
+    collections::VecDeque,
     fs::{create_dir_all, write},
     io::Error,
     process::exit,
@@ -18,8 +19,10 @@ use walkdir::WalkDir;
 
 use super::{folder::FolderSource, PlayerControl};
 use crate::utils::{
-    folder::fill_filler_list, gen_dummy, get_date_range, include_file_extension,
-    json_serializer::JsonPlaylist, sum_durations, time_to_sec, Media, PlayoutConfig, Template,
+    folder::{best_fit_filler, collect_clips, fill_filler_list, reachable_frames},
+    gen_dummy, get_date_range, include_file_extension,
+    json_serializer::JsonPlaylist,
+    sum_durations, time_to_sec, Media, PlayoutConfig, Template,
 };
 
 pub fn random_list(clip_list: Vec<Media>, total_length: f64) -> Vec<Media> {
@@ -86,8 +89,141 @@ pub fn ordered_list(clip_list: Vec<Media>, total_length: f64) -> Vec<Media> {
     ordered_clip_list
 }
 
+// Maximal clips in the subset sum, its cost grows with clips * frames
+const EXACT_POOL_SIZE: usize = 256;
+
+/// Fill the length with clips from the list, as exact as possible.
+///
+/// Lengths are counted in frames, like the output plays them. Clips are taken in
+/// list order, until the rest fits in a window which we can solve as subset sum.
+/// The next clips in list order, with repeats, form the pool for the rest, and the
+/// combination is selected which sums up closest to it.
+pub fn exact_list(
+    config: &PlayoutConfig,
+    clip_list: Vec<Media>,
+    total_length: f64,
+    tolerance: f64,
+) -> Vec<Media> {
+    let fps = config.processing.fps;
+    let clips: Vec<Media> = clip_list
+        .into_iter()
+        .filter(|c| (c.duration * fps).round() >= 1.0)
+        .collect();
+    let mut exact_clip_list: Vec<Media> = vec![];
+
+    if clips.is_empty() {
+        return exact_clip_list;
+    }
+
+    let frames: Vec<usize> = clips
+        .iter()
+        .map(|c| (c.duration * fps).round() as usize)
+        .collect();
+    let longest = frames.iter().max().copied().unwrap_or_default();
+    let window = (longest * 8).max((14400.0 * fps) as usize);
+    let total_frames = (total_length * fps).round() as usize;
+    let mut rest = total_frames;
+    let mut index = 0;
+    let mut skip_count = 0;
+
+    while rest > window && skip_count < clips.len() {
+        let i = index % clips.len();
+        index += 1;
+
+        if frames[i] > rest
+            || exact_clip_list
+                .last()
+                .is_some_and(|c| c.source == clips[i].source)
+        {
+            skip_count += 1;
+            continue;
+        }
+
+        skip_count = 0;
+        rest -= frames[i];
+        exact_clip_list.push(clips[i].clone());
+    }
+
+    // The pool needs some more clips than the rest, to have a choice.
+    // When it is full before, the first clips are played as they are.
+    let mut pool = VecDeque::new();
+    let mut pool_frames = 0;
+
+    loop {
+        while pool.len() < EXACT_POOL_SIZE && pool_frames < rest + longest * 2 {
+            let i = index % clips.len();
+            index += 1;
+            pool_frames += frames[i];
+            pool.push_back(i);
+        }
+
+        match pool.front() {
+            Some(&i) if pool_frames < rest + longest * 2 && frames[i] <= rest => {
+                pool.pop_front();
+                pool_frames -= frames[i];
+                rest -= frames[i];
+                exact_clip_list.push(clips[i].clone());
+            }
+            _ => break,
+        }
+    }
+
+    let pool = Vec::from(pool);
+    let pool_lengths: Vec<usize> = pool.iter().map(|i| frames[*i]).collect();
+    let reach = reachable_frames(
+        &pool_lengths,
+        &(0..pool.len()).collect::<Vec<usize>>(),
+        rest,
+    );
+    let mut selection = vec![];
+
+    if let Some(sum) = (0..=rest).rev().find(|s| reach[*s].is_some()) {
+        let mut selected = collect_clips(&pool_lengths, &reach, sum);
+        selected.sort();
+        selection = selected.into_iter().map(|i| pool[i]).collect();
+        rest -= sum;
+    }
+
+    // Avoid the same clip back to back.
+    for i in 0..selection.len() {
+        let prev = match i {
+            0 => exact_clip_list.last().map(|c| c.source.clone()),
+            _ => Some(clips[selection[i - 1]].source.clone()),
+        };
+
+        if prev.as_ref() == Some(&clips[selection[i]].source) {
+            if let Some(j) = (i + 1..selection.len())
+                .find(|j| Some(&clips[selection[*j]].source) != prev.as_ref())
+            {
+                selection.swap(i, j);
+            }
+        }
+    }
+
+    for i in selection {
+        exact_clip_list.push(clips[i].clone());
+    }
+
+    let missing = rest as f64 / fps;
+
+    if missing > tolerance {
+        debug!("Exact length not reached, <yellow>{missing:.3}</> seconds are missing");
+    }
+
+    exact_clip_list
+}
+
 pub fn filler_list(config: &PlayoutConfig, total_length: f64) -> Vec<Media> {
     let filler_list = fill_filler_list(config, None);
+
+    if config.storage.best_fit && config.storage.filler.is_dir() {
+        let best_fit = best_fit_filler(config, &filler_list, total_length, &[]);
+
+        if !best_fit.is_empty() {
+            return best_fit;
+        }
+    }
+
     let mut index = 0;
     let mut filler_clip_list: Vec<Media> = vec![];
     let mut target_duration = 0.0;
@@ -162,7 +298,13 @@ pub fn generate_from_template(
             }
         }
 
-        let mut timed_list = if source.shuffle {
+        let mut timed_list = if let Some(tolerance) = config.general.exact_length {
+            if source.shuffle {
+                source_list.shuffle(&mut rng);
+            }
+
+            exact_list(config, source_list, duration, tolerance)
+        } else if source.shuffle {
             source_list.shuffle(&mut rng);
 
             random_list(source_list, duration)
@@ -172,7 +314,7 @@ pub fn generate_from_template(
 
         let total_length = sum_durations(&timed_list);
 
-        if duration > total_length {
+        if duration - total_length > config.general.exact_length.unwrap_or_default() {
             let mut filler = filler_list(config, duration - total_length);
 
             timed_list.append(&mut filler);
@@ -283,23 +425,31 @@ pub fn generate_playlist(
             let media_list = player_control.current_list.lock().unwrap();
             playlist.program = media_list.to_vec();
         } else {
-            for item in folder_iter.clone() {
-                let duration = item.duration;
-
-                if total_length >= length + duration {
-                    playlist.program.push(item);
+            if let Some(tolerance) = config.general.exact_length {
+                let clip_list = folder_iter.clone().take(list_length).collect();
 
-                    length += duration;
-                } else if round == list_length - 1 {
-                    break;
-                } else {
-                    round += 1;
+                playlist.program = exact_list(config, clip_list, total_length, tolerance);
+            } else {
+                for item in folder_iter.clone() {
+                    let duration = item.duration;
+
+                    if total_length >= length + duration {
+                        playlist.program.push(item);
+
+                        length += duration;
+                    } else if round == list_length - 1 {
+                        break;
+                    } else {
+                        round += 1;
+                    }
                 }
             }
 
             let list_duration = sum_durations(&playlist.program);
 
-            if config.playlist.length_sec.unwrap() > list_duration {
+            if config.playlist.length_sec.unwrap() - list_duration
+                > config.general.exact_length.unwrap_or_default()
+            {
                 let time_left = config.playlist.length_sec.unwrap() - list_duration;
                 let mut fillers = filler_list(config, time_left);
 
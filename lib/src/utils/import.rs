@@ -2,7 +2,7 @@
 use std::{
     //error::Error,
     fs::{create_dir_all, File},
-    io::{BufRead, BufReader, Error, ErrorKind},
+    io::{BufRead, BufReader, Error},
     path::Path,
 };
 
@@ -28,13 +28,10 @@ pub fn import_file(
 
     let playlist_root = &config.playlist.path;
     if !playlist_root.is_dir() {
-        return Err(Error::new(
-            ErrorKind::Other,
-            format!(
-                "Playlist folder <b><magenta>{:?}</></b> not exists!",
-                config.playlist.path,
-            ),
-        ));
+        return Err(Error::other(format!(
+            "Playlist folder <b><magenta>{:?}</></b> not exists!",
+            config.playlist.path,
+        )));
     }
 
     let d: Vec<&str> = date.split('-').collect();
@@ -75,6 +72,6 @@ pub fn import_file(
 
     match json_writer(playlist_file, playlist) {
         Ok(_) => Ok(msg),
-        Err(e) => Err(Error::new(ErrorKind::Other, e)),
+        Err(e) => Err(Error::other(e)),
     }
 }
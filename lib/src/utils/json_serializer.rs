@@ -3,14 +3,14 @@ use std::{
     fs::File,
     path::Path,
     sync::{atomic::AtomicBool, Arc},
-    thread,
 };
 
+use chrono::{Days, NaiveDate};
 use simplelog::*;
 
 use crate::utils::{
-    get_date, is_remote, modified_time, time_from_header, validate_playlist, Media, PlayerControl,
-    PlayoutConfig, DUMMY_LEN,
+    days_between, get_date, is_remote, modified_time, playlist_days, spawn_thread,
+    time_from_header, validate_playlist, Media, PlayerControl, PlayoutConfig, DUMMY_LEN,
 };
 
 /// This is our main playlist object, it holds all necessary information for the current day.
@@ -86,6 +86,106 @@ pub fn set_defaults(playlist: &mut JsonPlaylist) {
     playlist.length = Some(length)
 }
 
+/// Get the playlist path for the given date.
+///
+/// When the playlist path is a folder, the playlist is in a subfolder from year and month.
+pub fn playlist_file(config: &PlayoutConfig, date: NaiveDate) -> String {
+    let path = &config.playlist.path;
+
+    if path.is_dir() || is_remote(&path.to_string_lossy()) {
+        return path
+            .join(date.format("%Y").to_string())
+            .join(date.format("%m").to_string())
+            .join(date.format("%Y-%m-%d").to_string())
+            .with_extension("json")
+            .display()
+            .to_string();
+    }
+
+    path.display().to_string()
+}
+
+fn playlist_exists(path: &str) -> bool {
+    if is_remote(path) {
+        return reqwest::blocking::Client::new()
+            .head(path)
+            .send()
+            .is_ok_and(|r| r.status().is_success());
+    }
+
+    Path::new(path).is_file()
+}
+
+/// Find the playlist, which plays on the given date.
+///
+/// Playlists with a multi day length play also on the following days,
+/// so we look back for the last playlist, which covers the date.
+pub fn find_playlist(config: &PlayoutConfig, date: NaiveDate) -> String {
+    let path = &config.playlist.path;
+
+    if path.is_dir() || is_remote(&path.to_string_lossy()) {
+        for day in 0..playlist_days(config) {
+            let file = playlist_file(config, date - Days::new(day));
+
+            if playlist_exists(&file) {
+                return file;
+            }
+        }
+    }
+
+    playlist_file(config, date)
+}
+
+/// Get the first day from a playlist, which plays on the given date.
+///
+/// A multi day playlist starts on its date, a single playlist file
+/// loops over its days and starts again after the last one.
+pub fn playlist_first_day(
+    config: &PlayoutConfig,
+    playlist: &JsonPlaylist,
+    date: NaiveDate,
+) -> NaiveDate {
+    let days = playlist_days(config) as i64;
+
+    let Ok(playlist_date) = NaiveDate::parse_from_str(&playlist.date, "%Y-%m-%d") else {
+        return date;
+    };
+
+    let offset = (date - playlist_date).num_days();
+
+    if config.playlist.path.is_file() {
+        return date - Days::new(offset.rem_euclid(days) as u64);
+    }
+
+    if (0..days).contains(&offset) {
+        return playlist_date;
+    }
+
+    date
+}
+
+/// Move the playlist start to the first day, when the playlist covers multiple days.
+fn set_first_day(config: &mut PlayoutConfig, playlist: &mut JsonPlaylist, date: NaiveDate) {
+    config.playlist.first_day = None;
+
+    if playlist_days(config) < 2 {
+        return;
+    }
+
+    let first_day = playlist_first_day(config, playlist, date);
+    let start_sec = playlist.start_sec.unwrap_or_default();
+
+    if first_day != date {
+        info!(
+            "Play day <yellow>{}</> from multi day playlist",
+            (date - first_day).num_days() + 1
+        );
+    }
+
+    playlist.start_sec = Some(start_sec - days_between(config, first_day, date));
+    config.playlist.first_day = Some(first_day);
+}
+
 /// Read json playlist file, fills JsonPlaylist struct and set some extra values,
 /// which we need to process.
 pub fn read_json(
@@ -96,27 +196,16 @@ pub fn read_json(
     seek: bool,
     get_next: bool,
 ) -> JsonPlaylist {
-    let config_clone = config.clone();
-    let control_clone = player_control.clone();
-    let mut playlist_path = config.playlist.path.clone();
     let start_sec = config.playlist.start_sec.unwrap();
-    let date = get_date(seek, start_sec, get_next);
-
-    if playlist_path.is_dir() || is_remote(&config.playlist.path.to_string_lossy()) {
-        let d: Vec<&str> = date.split('-').collect();
-        playlist_path = playlist_path
-            .join(d[0])
-            .join(d[1])
-            .join(date.clone())
-            .with_extension("json");
-    }
+    let date = get_date(config.playlist.timezone, seek, start_sec, get_next);
+    let day = NaiveDate::parse_from_str(&date, "%Y-%m-%d").unwrap_or_default();
+    let current_file = path.unwrap_or_else(|| find_playlist(config, day));
+    let playlist_path = Path::new(&current_file).to_owned();
 
-    let mut current_file = playlist_path.as_path().display().to_string();
+    config.playlist.first_day = None;
 
-    if let Some(p) = path {
-        playlist_path = Path::new(&p).to_owned();
-        current_file = p
-    }
+    let config_clone = config.clone();
+    let control_clone = player_control.clone();
 
     if is_remote(&current_file) {
         let response = reqwest::blocking::Client::new().get(&current_file).send();
@@ -130,6 +219,7 @@ pub fn read_json(
                         serde_json::from_str(&body).expect("Could't read remote json playlist.");
                     playlist.path = Some(current_file);
                     playlist.start_sec = Some(start_sec);
+                    set_first_day(config, &mut playlist, day);
 
                     if let Some(time) = time_from_header(&headers) {
                         playlist.modified = Some(time.to_string());
@@ -138,7 +228,7 @@ pub fn read_json(
                     let list_clone = playlist.clone();
 
                     if !config.general.skip_validation {
-                        thread::spawn(move || {
+                        spawn_thread(move || {
                             validate_playlist(
                                 config_clone,
                                 control_clone,
@@ -178,11 +268,12 @@ pub fn read_json(
         playlist.path = Some(current_file);
         playlist.start_sec = Some(start_sec);
         playlist.modified = modified;
+        set_first_day(config, &mut playlist, day);
 
         let list_clone = playlist.clone();
 
         if !config.general.skip_validation {
-            thread::spawn(move || {
+            spawn_thread(move || {
                 validate_playlist(config_clone, control_clone, list_clone, is_terminated)
             });
         }
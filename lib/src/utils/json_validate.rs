@@ -1,5 +1,6 @@
 use std::{
     io::{BufRead, BufReader},
+    path::Path,
     process::{Command, Stdio},
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -11,11 +12,11 @@ use std::{
 use regex::Regex;
 use simplelog::*;
 
-use crate::filter::FilterType::Audio;
+use crate::filter::{self, FilterType::Audio};
 use crate::utils::{
-    errors::ProcError, is_close, is_remote, loop_image, sec_to_time, seek_and_length, vec_strings,
-    JsonPlaylist, Media, OutputMode::Null, PlayerControl, PlayoutConfig, FFMPEG_IGNORE_ERRORS,
-    IMAGE_FORMAT,
+    decoder_input_args, errors::ProcError, is_close, is_remote, loop_image, loudness, sec_to_time,
+    seek_and_length, vec_strings, JsonPlaylist, Media, OutputMode::Null, PlayerControl,
+    PlayoutConfig, FFMPEG_IGNORE_ERRORS, IMAGE_FORMAT,
 };
 use crate::ADVANCED_CONFIG;
 
@@ -31,16 +32,26 @@ fn check_media(
     begin: f64,
     config: &PlayoutConfig,
 ) -> Result<(), ProcError> {
-    let mut dec_cmd = vec_strings!["-hide_banner", "-nostats", "-threads", "4", "-hwaccel", "cuvid", "-c:v", "h264_cuvid", "-fix_sub_duration", "-drop_second_field", "true", "-v", "level+info"];
+    let mut dec_cmd = vec_strings![
+        "-hide_banner",
+        "-nostats",
+        "-threads",
+        "4",
+        "-fix_sub_duration",
+        "-v",
+        "level+info"
+    ];
     let mut error_list = vec![];
     let mut config = config.clone();
     config.out.mode = Null;
 
     let mut process_length = 0.1;
 
-    if let Some(decoder_input_cmd) = &ADVANCED_CONFIG.decoder.input_cmd {
-        dec_cmd.append(&mut decoder_input_cmd.clone());
-    }
+    dec_cmd.append(&mut decoder_input_args(
+        &node,
+        &config,
+        &ADVANCED_CONFIG.decoder.input_cmd,
+    ));
 
     if config.logging.detect_silence {
         process_length = 15.0;
@@ -71,7 +82,7 @@ fn check_media(
     let mut filter = node.filter.unwrap_or_default();
 
     if filter.cmd().len() > 1 {
-        let re_clean = Regex::new(r"volume=[0-9.]+")?;
+        let re_clean = Regex::new(r"volume=-?[0-9.]+(dB)?")?;
 
         filter.audio_chain = re_clean
             .replace_all(&filter.audio_chain, "anull")
@@ -194,6 +205,44 @@ pub fn validate_playlist(
             }
         }
 
+        if !item.subtitle.is_empty()
+            && item.subtitle_index().is_none()
+            && !Path::new(&item.subtitle).is_file()
+        {
+            error!(
+                "[Validation] Subtitle on position <yellow>{pos:0>3}</> <yellow>{}</> not exists: <b><magenta>{}</></b>",
+                sec_to_time(begin),
+                item.subtitle
+            );
+        }
+
+        if config.processing.normalize_loudness && !is_remote(&item.source) {
+            // separate audio files have one stream, when they are not probed
+            let stream_count = match Path::new(&item.audio).is_file() {
+                true => item
+                    .probe_audio
+                    .as_ref()
+                    .map_or(1, |p| p.audio_streams.len()),
+                false => item.probe.as_ref().map_or(0, |p| p.audio_streams.len()),
+            };
+            let mut streams = filter::audio_streams(item, &config);
+            streams.retain(|s| (*s as usize) < stream_count);
+            streams.sort();
+            streams.dedup();
+
+            for stream in streams {
+                match loudness::measure_cached(&config, loudness::audio_source(item), stream) {
+                    Ok(l) => {
+                        item.loudness.insert(stream, l);
+                    }
+                    Err(e) => warn!(
+                        "[Validation] Position <yellow>{pos:0>3}</> <yellow>{}</>: {e}",
+                        sec_to_time(begin)
+                    ),
+                }
+            }
+        }
+
         if item.probe.is_some() {
             if let Err(e) = check_media(item.clone(), pos, begin, &config) {
                 error!("{e}");
@@ -224,6 +273,10 @@ pub fn validate_playlist(
                         }
                     }
 
+                    if o.audio == item.audio && !item.loudness.is_empty() {
+                        o.loudness = item.loudness.clone();
+                    }
+
                     if o.audio == item.audio && item.probe_audio.is_some() {
                         o.probe_audio = item.probe_audio.clone();
                         o.duration_audio = item.duration_audio;
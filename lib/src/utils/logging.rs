@@ -76,7 +76,7 @@ fn mail_queue(
     while !proc_ctl.is_terminated.load(Ordering::SeqCst) {
         let mut msg = messages.lock().unwrap();
 
-        if msg.len() > 0 {
+        if !msg.is_empty() {
             send_mail(&cfg, msg.join("\n"));
 
             msg.clear();
@@ -197,6 +197,12 @@ pub fn init_logging(
         .set_time_level(time_level)
         .clone();
 
+    if app_config.channel_names {
+        log_config
+            .set_thread_level(LevelFilter::Error)
+            .set_thread_mode(ThreadLogMode::Names);
+    }
+
     if app_config.local_time {
         log_config = match log_config.set_time_offset_to_local() {
             Ok(local) => local.clone(),
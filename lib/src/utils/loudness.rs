@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use simplelog::*;
+
+use crate::utils::{errors::ProcError, file_stamp, is_remote, Media, PlayoutConfig};
+
+lazy_static! {
+    /// Measurements from all channels, the loudness of a file is the same everywhere.
+    static ref CACHE: Mutex<HashMap<PathBuf, HashMap<String, CacheEntry>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Integrated loudness (LUFS) and true peak (dBTP) of a file, measured with the loudnorm filter.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    pub integrated: f64,
+    pub true_peak: f64,
+}
+
+impl Loudness {
+    /// Gain in dB to reach the target loudness, without going over the true peak limit.
+    pub fn gain(&self, target: f64, true_peak: f64) -> f64 {
+        let gain = (target - self.integrated).min(true_peak - self.true_peak);
+
+        (gain * 100.0).round() / 100.0
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct CacheEntry {
+    size: u64,
+    modified: u64,
+    #[serde(flatten)]
+    loudness: Loudness,
+}
+
+fn read_cache(path: &Path) -> HashMap<String, CacheEntry> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        warn!("Loudness cache <b><magenta>{path:?}</></b> is not readable, start a new one: {e}");
+        HashMap::new()
+    })
+}
+
+fn with_cache<T>(path: &Path, f: impl FnOnce(&mut HashMap<String, CacheEntry>) -> T) -> T {
+    let mut cache = CACHE.lock().unwrap();
+    let entries = cache
+        .entry(path.to_path_buf())
+        .or_insert_with(|| read_cache(path));
+
+    f(entries)
+}
+
+/// Merge the entries with the cache file and replace it.
+///
+/// Other engine processes can use the same file, so their entries are kept. The file is written
+/// to a temporary file first and renamed, so a crash leaves no broken cache.
+fn write_cache(path: &Path, entries: &mut HashMap<String, CacheEntry>) -> Result<(), ProcError> {
+    let mut merged = read_cache(path);
+    merged.extend(entries.drain());
+    *entries = merged;
+
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+
+    fs::write(&tmp, serde_json::to_string(entries)?).map_err(ProcError::IO)?;
+    fs::rename(&tmp, path).map_err(ProcError::IO)
+}
+
+/// Cache key from the file and the audio stream, the first stream uses only the file.
+fn cache_key(source: &str, stream: i32) -> String {
+    match stream {
+        0 => source.to_string(),
+        _ => format!("{source}#a:{stream}"),
+    }
+}
+
+/// The audio from a clip, the separate audio file comes first.
+pub fn audio_source(node: &Media) -> &str {
+    if Path::new(&node.audio).is_file() {
+        &node.audio
+    } else {
+        &node.source
+    }
+}
+
+/// Get the loudness of an audio stream from the cache, when the file is not changed since the measurement.
+pub fn cached(config: &PlayoutConfig, source: &str, stream: i32) -> Option<Loudness> {
+    let (size, modified) = file_stamp(source)?;
+
+    with_cache(&config.processing.loudness_cache, |entries| {
+        entries
+            .get(&cache_key(source, stream))
+            .filter(|e| e.size == size && e.modified == modified)
+            .map(|e| e.loudness)
+    })
+}
+
+/// Measure the loudness of an audio stream.
+pub fn measure(source: &str, stream: i32) -> Result<Loudness, ProcError> {
+    let output = Command::new("ffmpeg")
+        .args([
+            "-hide_banner",
+            "-nostats",
+            "-i",
+            source,
+            "-map",
+            &format!("0:a:{stream}"),
+            "-af",
+            "loudnorm=print_format=json",
+            "-f",
+            "null",
+            "-",
+        ])
+        .stdout(Stdio::null())
+        .output()?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let value = |name: &str| -> Option<f64> {
+        Regex::new(&format!(r#""{name}"\s*:\s*"([^"]+)""#))
+            .ok()?
+            .captures(&stderr)?
+            .get(1)?
+            .as_str()
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+    };
+
+    match (value("input_i"), value("input_tp")) {
+        (Some(integrated), Some(true_peak)) => Ok(Loudness {
+            integrated,
+            true_peak,
+        }),
+        _ => Err(ProcError::Custom(format!(
+            "No loudness from <b><magenta>{source}</></b>, audio is missing or silent"
+        ))),
+    }
+}
+
+/// Get the loudness of an audio stream from the cache, or measure it and store it in the cache file.
+pub fn measure_cached(
+    config: &PlayoutConfig,
+    source: &str,
+    stream: i32,
+) -> Result<Loudness, ProcError> {
+    if let Some(loudness) = cached(config, source, stream) {
+        return Ok(loudness);
+    }
+
+    if is_remote(source) {
+        return Err(ProcError::Custom(format!(
+            "Loudness from remote source <b><magenta>{source}</></b> is not measured"
+        )));
+    }
+
+    let Some((size, modified)) = file_stamp(source) else {
+        return Err(ProcError::Custom(format!(
+            "File <b><magenta>{source}</></b> not exist!"
+        )));
+    };
+
+    let loudness = measure(source, stream)?;
+
+    debug!(
+        "Loudness from <b><magenta>{source}</></b> (audio stream {stream}): <yellow>{}</> LUFS, true peak: <yellow>{}</> dBTP",
+        loudness.integrated, loudness.true_peak
+    );
+
+    let path = &config.processing.loudness_cache;
+
+    with_cache(path, |entries| {
+        entries.insert(
+            cache_key(source, stream),
+            CacheEntry {
+                size,
+                modified,
+                loudness,
+            },
+        );
+
+        write_cache(path, entries)
+    })?;
+
+    Ok(loudness)
+}
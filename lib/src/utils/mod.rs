@@ -1,19 +1,23 @@
 use std::{
+    collections::HashMap,
     ffi::OsStr,
     fmt,
     fs::{self, metadata, File},
-    io::{BufRead, BufReader, Error},
+    io::{BufRead, BufReader, Error, ErrorKind},
     net::TcpListener,
     path::{Path, PathBuf},
     process::{exit, ChildStderr, Command, Stdio},
     sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::UNIX_EPOCH,
 };
 
 #[cfg(not(windows))]
 use std::env;
 
-use chrono::{prelude::*, TimeDelta};
-use ffprobe::{ffprobe, Stream as FFStream};
+use chrono::{prelude::*, Days, LocalResult, TimeDelta};
+use ffprobe::{FfProbe, FfProbeError, Stream as FFStream};
+use lazy_static::lazy_static;
 use rand::prelude::*;
 use regex::Regex;
 use reqwest::header;
@@ -23,6 +27,7 @@ use simplelog::*;
 
 pub mod advanced_config;
 pub mod config;
+pub mod config_check;
 pub mod controller;
 pub mod errors;
 pub mod folder;
@@ -31,17 +36,22 @@ pub mod import;
 pub mod json_serializer;
 mod json_validate;
 mod logging;
+pub mod loudness;
+pub mod playlist_ops;
 
 #[cfg(windows)]
 mod windows;
 
+use advanced_config::HwAccel;
 pub use config::{
-    self as playout_config,
+    self as playout_config, ConfigChanges, FitMode, LogoLayer,
     OutputMode::{self, *},
     PlayoutConfig,
     ProcessMode::{self, *},
-    Template, DUMMY_LEN, FFMPEG_IGNORE_ERRORS, FFMPEG_UNRECOVERABLE_ERRORS, IMAGE_FORMAT,
+    SubtitleMode, Template, TextLayer, DEFAULT_TEXT_LAYER, DUMMY_LEN, FFMPEG_IGNORE_ERRORS,
+    FFMPEG_UNRECOVERABLE_ERRORS, IMAGE_FORMAT,
 };
+pub use config_check::{check_config, ConfigCheck, ConfigIssue, IssueKind};
 pub use controller::{
     PlayerControl, PlayoutStatus, ProcessControl,
     ProcessUnit::{self, *},
@@ -51,10 +61,14 @@ pub use generator::generate_playlist;
 pub use json_serializer::{read_json, JsonPlaylist};
 pub use json_validate::validate_playlist;
 pub use logging::{init_logging, send_mail};
+pub use loudness::Loudness;
+pub use playlist_ops::{bulk_playlists, BulkAction, BulkOperation, BulkResult, ExistsPolicy};
+
+pub use chrono_tz::Tz;
 
 use crate::{
     filter::{filter_chains, Filters},
-    vec_strings,
+    vec_strings, ADVANCED_CONFIG,
 };
 
 /// Video clip struct to hold some important states and comments for current media.
@@ -82,6 +96,19 @@ pub struct Media {
     #[serde(deserialize_with = "null_string")]
     pub source: String,
 
+    #[serde(
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub title: String,
+    #[serde(
+        default,
+        deserialize_with = "null_string",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub description: String,
+
     #[serde(
         default,
         deserialize_with = "null_string",
@@ -89,6 +116,14 @@ pub struct Media {
     )]
     pub audio: String,
 
+    /// Subtitle file (srt, ass, vtt), or the index from a subtitle stream in the source.
+    #[serde(
+        default,
+        deserialize_with = "string_or_index",
+        skip_serializing_if = "is_empty_string"
+    )]
+    pub subtitle: String,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub cmd: Option<Vec<String>>,
 
@@ -104,6 +139,10 @@ pub struct Media {
     #[serde(skip_serializing, skip_deserializing)]
     pub probe_audio: Option<MediaProbe>,
 
+    /// Measured loudness by audio stream index.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub loudness: HashMap<i32, Loudness>,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub last_ad: bool,
 
@@ -113,6 +152,20 @@ pub struct Media {
     #[serde(skip_serializing, skip_deserializing)]
     pub process: Option<bool>,
 
+    #[serde(default, skip_serializing_if = "AudioOptions::is_default")]
+    pub audio_options: AudioOptions,
+
+    /// Picture fit mode for this clip, instead of the channel default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fit: Option<FitMode>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secondary_events: Vec<SecondaryEvent>,
+
+    /// Show (true) or hide (false) logo layers on this clip, independent of the category rules.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub logos: HashMap<String, bool>,
+
     #[serde(default, skip_serializing)]
     pub unit: ProcessUnit,
 }
@@ -144,15 +197,23 @@ impl Media {
             duration_audio: 0.0,
             category: String::new(),
             source: src.to_string(),
+            title: String::new(),
+            description: String::new(),
             audio: String::new(),
+            subtitle: String::new(),
             cmd: Some(vec_strings!["-i", src]),
             filter: None,
             custom_filter: String::new(),
             probe,
             probe_audio: None,
+            loudness: HashMap::new(),
             last_ad: false,
             next_ad: false,
             process: Some(true),
+            audio_options: AudioOptions::default(),
+            fit: None,
+            secondary_events: vec![],
+            logos: HashMap::new(),
             unit: Decoder,
         }
     }
@@ -211,9 +272,58 @@ impl Media {
         config: &PlayoutConfig,
         filter_chain: &Option<Arc<Mutex<Vec<String>>>>,
     ) {
+        subtitle_input(config, self);
+
         let mut node = self.clone();
         self.filter = Some(filter_chains(config, &mut node, filter_chain))
     }
+
+    /// Get the title for drawtext, EPG, log and RPC.
+    ///
+    /// The title from the playlist comes first, then the container tags, when `title_from_tags` is on,
+    /// and at last the file name, formatted with `text.regex`.
+    pub fn get_title(&self, config: &PlayoutConfig) -> String {
+        if !self.title.is_empty() {
+            return self.title.clone();
+        }
+
+        if let Some(probe) = self.probe.as_ref().filter(|_| config.text.title_from_tags) {
+            match (probe.tag("title"), probe.tag("artist")) {
+                (Some(title), Some(artist)) => return format!("{artist} - {title}"),
+                (Some(title), None) => return title,
+                _ => (),
+            }
+        }
+
+        match Regex::new(&config.text.regex)
+            .ok()
+            .and_then(|r| r.captures(&self.source))
+        {
+            Some(t) => t[1].to_string(),
+            None => Path::new(&self.source)
+                .file_stem()
+                .unwrap_or_else(|| OsStr::new(&self.source))
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+
+    /// Index from an embedded subtitle stream, when `subtitle` is a number.
+    pub fn subtitle_index(&self) -> Option<usize> {
+        self.subtitle.trim().parse().ok()
+    }
+
+    /// Get the description from the playlist, or from the container tags.
+    pub fn get_description(&self, config: &PlayoutConfig) -> String {
+        if !self.description.is_empty() || !config.text.title_from_tags {
+            return self.description.clone();
+        }
+
+        self.probe
+            .as_ref()
+            .and_then(|p| p.tag("description").or_else(|| p.tag("comment")))
+            .unwrap_or_default()
+    }
 }
 
 impl PartialEq for Media {
@@ -223,13 +333,104 @@ impl PartialEq for Media {
             && self.duration == other.duration
             && self.source == other.source
             && self.category == other.category
+            && self.title == other.title
+            && self.description == other.description
             && self.audio == other.audio
+            && self.subtitle == other.subtitle
             && self.custom_filter == other.custom_filter
+            && self.audio_options == other.audio_options
+            && self.fit == other.fit
+            && self.secondary_events == other.secondary_events
+            && self.logos == other.logos
     }
 }
 
 impl Eq for Media {}
 
+/// Audio settings for a single clip.
+///
+/// `gain` is in dB, on top of the channel volume. A positive `delay` (seconds) delays the audio
+/// and cuts its end at the clip length, a negative one cuts the audio begin. `language` selects the audio tracks by the language tag,
+/// the first entry goes to the first output track, and so on.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AudioOptions {
+    #[serde(default)]
+    pub gain: f64,
+    #[serde(default)]
+    pub delay: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<ChannelMap>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub language: Vec<String>,
+}
+
+impl AudioOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Channel mapping for stereo output.
+///
+/// `downmix` mixes 5.1 to stereo, `left` and `right` take one channel from dual mono audio,
+/// `mono` mixes both channels and `swap` changes the sides.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelMap {
+    Downmix,
+    Left,
+    Right,
+    Mono,
+    Swap,
+}
+
+/// Secondary event, like a lower third or a bug, which runs at an offset inside a clip.
+///
+/// `offset` and `duration` can be seconds or `hh:mm:ss`, the offset counts from the clip in point.
+/// `text` takes the same values like a text message over RPC, `command` and `end_command`
+/// are raw zmq filter commands, like `overlay@bug enable 1`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SecondaryEvent {
+    #[serde(deserialize_with = "sec_or_time")]
+    pub offset: f64,
+    #[serde(deserialize_with = "sec_or_time")]
+    pub duration: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "is_empty_string")]
+    pub command: String,
+    #[serde(default, skip_serializing_if = "is_empty_string")]
+    pub end_command: String,
+}
+
+fn sec_or_time<'de, D>(d: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SecOrTime {
+        Sec(f64),
+        Time(String),
+    }
+
+    match SecOrTime::deserialize(d)? {
+        SecOrTime::Sec(sec) => Ok(sec),
+        SecOrTime::Time(time) => {
+            if Regex::new(r"^[0-9]+:[0-5][0-9]:[0-5][0-9](\.[0-9]+)?$")
+                .unwrap()
+                .is_match(&time)
+            {
+                Ok(time_to_sec(&time))
+            } else {
+                Err(serde::de::Error::custom(format!(
+                    "Time \"{time}\" is not in format hh:mm:ss"
+                )))
+            }
+        }
+    }
+}
+
 fn null_string<'de, D>(d: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
@@ -237,27 +438,151 @@ where
     Deserialize::deserialize(d).map(|x: Option<_>| x.unwrap_or_default())
 }
 
+fn string_or_index<'de, D>(d: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Deserialize::deserialize(d)? {
+        Some(serde_json::Value::Number(n)) => Ok(n.to_string()),
+        Some(serde_json::Value::String(s)) => Ok(s),
+        _ => Ok(String::new()),
+    }
+}
+
 #[allow(clippy::trivially_copy_pass_by_ref)]
 fn is_empty_string(st: &String) -> bool {
     *st == String::new()
 }
 
 /// We use the ffprobe crate, but we map the metadata to our needs.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MediaProbe {
     pub format: ffprobe::Format,
     pub audio_streams: Vec<FFStream>,
     pub video_streams: Vec<FFStream>,
+    #[serde(default)]
+    pub color: ColorInfo,
+}
+
+/// Color properties from the first video stream, the ffprobe crate doesn't map them.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ColorInfo {
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    pub color_space: Option<String>,
+}
+
+impl ColorInfo {
+    /// PQ (HDR10) or HLG transfer.
+    pub fn is_hdr(&self) -> bool {
+        matches!(
+            self.color_transfer.as_deref(),
+            Some("smpte2084" | "arib-std-b67")
+        )
+    }
+}
+
+/// Run ffprobe with the same arguments like the ffprobe crate, but keep also the color properties.
+fn ffprobe(input: &str) -> Result<(FfProbe, ColorInfo), FfProbeError> {
+    let out = Command::new("ffprobe")
+        .args([
+            "-v",
+            "quiet",
+            "-show_format",
+            "-show_streams",
+            "-print_format",
+            "json",
+            input,
+        ])
+        .output()
+        .map_err(FfProbeError::Io)?;
+
+    if !out.status.success() {
+        return Err(FfProbeError::Status(out));
+    }
+
+    let probe =
+        serde_json::from_slice::<FfProbe>(&out.stdout).map_err(FfProbeError::Deserialize)?;
+    let color = serde_json::from_slice::<serde_json::Value>(&out.stdout)
+        .ok()
+        .and_then(|v| {
+            v["streams"]
+                .as_array()?
+                .iter()
+                .find(|s| s["codec_type"] == "video")
+                .and_then(|s| serde_json::from_value(s.clone()).ok())
+        })
+        .unwrap_or_default();
+
+    Ok((probe, color))
+}
+
+lazy_static! {
+    /// Probe results from local files, shared by all channels in the process.
+    static ref PROBE_CACHE: Mutex<HashMap<String, (u64, u64, MediaProbe)>> =
+        Mutex::new(HashMap::new());
+}
+
+// Maximal entries in the probe cache, it starts new when it is full
+const PROBE_CACHE_SIZE: usize = 10_000;
+
+/// Size and modification time, to find changed files.
+pub(crate) fn file_stamp(source: &str) -> Option<(u64, u64)> {
+    let meta = metadata(source).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    Some((meta.len(), modified.as_secs()))
 }
 
 impl MediaProbe {
+    /// Probe a file, local files come from the cache, when they are not changed since the last probe.
     pub fn new(input: &str) -> Result<Self, ProcError> {
+        Self::cached(input, Self::probe)
+    }
+
+    /// Get the probe result from the cache, or from the `probe` function, when the file is new or changed.
+    pub fn cached<F>(input: &str, probe: F) -> Result<Self, ProcError>
+    where
+        F: FnOnce(&str) -> Result<Self, ProcError>,
+    {
+        let stamp = match is_remote(input) {
+            true => None,
+            false => file_stamp(input),
+        };
+
+        if let Some((size, modified)) = stamp {
+            if let Some((_, _, probe)) = PROBE_CACHE
+                .lock()
+                .unwrap()
+                .get(input)
+                .filter(|(s, m, _)| *s == size && *m == modified)
+            {
+                return Ok(probe.clone());
+            }
+        }
+
+        let probe = probe(input)?;
+
+        if let Some((size, modified)) = stamp {
+            let mut cache = PROBE_CACHE.lock().unwrap();
+
+            if cache.len() >= PROBE_CACHE_SIZE {
+                cache.clear();
+            }
+
+            cache.insert(input.to_string(), (size, modified, probe.clone()));
+        }
+
+        Ok(probe)
+    }
+
+    fn probe(input: &str) -> Result<Self, ProcError> {
         let probe = ffprobe(input);
         let mut a_stream = vec![];
         let mut v_stream = vec![];
 
         match probe {
-            Ok(obj) => {
+            Ok((obj, color)) => {
                 for stream in obj.streams {
                     let cp_stream = stream.clone();
 
@@ -276,6 +601,7 @@ impl MediaProbe {
                     format: obj.format,
                     audio_streams: a_stream,
                     video_streams: v_stream,
+                    color,
                 })
             }
             Err(e) => {
@@ -289,6 +615,19 @@ impl MediaProbe {
             }
         }
     }
+
+    /// Get a container tag, the tag name is not case sensitive (Matroska tags are upper case).
+    pub fn tag(&self, name: &str) -> Option<String> {
+        self.format
+            .tags
+            .as_ref()?
+            .extra
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .and_then(|(_, v)| v.as_str())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
 }
 
 /// Calculate fps from rate/factor string
@@ -344,28 +683,150 @@ pub fn write_status(config: &PlayoutConfig, date: &str, shift: f64) {
 //     local.timestamp_millis() as i32
 // }
 
-/// Get current time in seconds.
-pub fn time_in_seconds() -> f64 {
-    let local: DateTime<Local> = time_now();
+/// Get current date and time in the given timezone, or in system timezone.
+pub fn local_now(timezone: Option<Tz>) -> NaiveDateTime {
+    let now = time_now();
+
+    match timezone {
+        Some(tz) => now.with_timezone(&tz).naive_local(),
+        None => now.naive_local(),
+    }
+}
+
+/// Convert date and time from the given timezone, or from system timezone, to UTC.
+///
+/// Times which not exists, because the clock jumps forward on a DST change,
+/// are moved forward by one hour.
+pub fn local_to_utc(timezone: Option<Tz>, date_time: &NaiveDateTime) -> DateTime<Utc> {
+    fn resolve<T: TimeZone>(tz: &T, date_time: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        match tz.from_local_datetime(date_time) {
+            LocalResult::Single(t) | LocalResult::Ambiguous(t, _) => Some(t.with_timezone(&Utc)),
+            LocalResult::None => tz
+                .from_local_datetime(&(*date_time + TimeDelta::try_hours(1).unwrap()))
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+        }
+    }
+
+    match timezone {
+        Some(tz) => resolve(&tz, date_time),
+        None => resolve(&Local, date_time),
+    }
+    .unwrap_or_else(|| date_time.and_utc())
+}
+
+/// Convert UTC time to the given timezone, or to system timezone.
+pub fn utc_to_local(timezone: Option<Tz>, date_time: &DateTime<Utc>) -> DateTime<FixedOffset> {
+    match timezone {
+        Some(tz) => date_time.with_timezone(&tz).fixed_offset(),
+        None => date_time.with_timezone(&Local).fixed_offset(),
+    }
+}
+
+/// Get time of the day in seconds, in the given timezone or in system timezone.
+pub fn time_of_day(timezone: Option<Tz>) -> f64 {
+    let local = local_now(timezone);
 
     (local.hour() * 3600 + local.minute() * 60 + local.second()) as f64
         + (local.nanosecond() as f64 / 1000000000.0)
 }
 
+/// Get the moment, when the playlist from the given date starts.
+pub fn day_start(config: &PlayoutConfig, date: NaiveDate) -> DateTime<Utc> {
+    let start = config.playlist.start_sec.unwrap_or_default();
+    let time = date.and_time(NaiveTime::MIN)
+        + TimeDelta::try_milliseconds((start * 1000.0) as i64).unwrap_or_default();
+
+    local_to_utc(config.playlist.timezone, &time)
+}
+
+/// Get length in seconds from the playlist day, which starts on the given date.
+///
+/// On DST changes a day has 23 or 25 hours.
+pub fn day_length(config: &PlayoutConfig, date: NaiveDate) -> f64 {
+    let next_date = date.succ_opt().unwrap_or(date);
+
+    (day_start(config, next_date) - day_start(config, date)).num_milliseconds() as f64 / 1000.0
+}
+
+/// Get the date from the running playlist day.
+pub fn playlist_date(config: &PlayoutConfig) -> NaiveDate {
+    let date = local_now(config.playlist.timezone).date();
+
+    if time_now() < day_start(config, date) {
+        return date.pred_opt().unwrap_or(date);
+    }
+
+    date
+}
+
+/// Get the number of days, which a playlist covers.
+///
+/// A playlist length over 24 hours, like 48:00:00 or 168:00:00, plays over multiple days.
+pub fn playlist_days(config: &PlayoutConfig) -> u64 {
+    let length = config.playlist.length_sec.unwrap_or(86400.0);
+
+    if length > 86400.0 {
+        (length / 86400.0).ceil() as u64
+    } else {
+        1
+    }
+}
+
+/// Get seconds between the starts of two playlist days.
+pub fn days_between(config: &PlayoutConfig, from: NaiveDate, to: NaiveDate) -> f64 {
+    (day_start(config, to) - day_start(config, from)).num_milliseconds() as f64 / 1000.0
+}
+
+/// Get the target length in seconds, counted from the start of the running playlist day.
+///
+/// A full day has 23 or 25 hours on DST changes, and a multi day playlist
+/// runs until its last day is over.
+pub fn target_length(config: &PlayoutConfig) -> f64 {
+    let length = config.playlist.length_sec.unwrap_or(86400.0);
+    let date = playlist_date(config);
+
+    if length > 0.0 && length < 86400.0 {
+        return length;
+    }
+
+    if let Some(first_day) = config.playlist.first_day {
+        let end_day = first_day + Days::new(playlist_days(config));
+
+        if first_day <= date && date < end_day {
+            return days_between(config, date, end_day);
+        }
+    }
+
+    day_length(config, date)
+}
+
+/// Get current time in seconds.
+///
+/// The time is counted from the start of the running playlist day, on top of the start time.
+/// So it runs continuously from `start_sec` to `start_sec` + day length, also on DST changes.
+pub fn time_in_seconds(config: &PlayoutConfig) -> f64 {
+    let start = config.playlist.start_sec.unwrap_or_default();
+    let elapsed = time_now().with_timezone(&Utc) - day_start(config, playlist_date(config));
+
+    start + elapsed.num_nanoseconds().unwrap_or_default() as f64 / 1000000000.0
+}
+
 /// Get current date for playlist, but check time with conditions:
 ///
 /// - When time is before playlist start, get date from yesterday.
 /// - When given next_start is over target length (normally a full day), get date from tomorrow.
-pub fn get_date(seek: bool, start: f64, get_next: bool) -> String {
-    let local: DateTime<Local> = time_now();
+pub fn get_date(timezone: Option<Tz>, seek: bool, start: f64, get_next: bool) -> String {
+    let local = local_now(timezone);
+    let time = time_of_day(timezone);
 
-    if seek && start > time_in_seconds() {
+    if seek && start > time {
         return (local - TimeDelta::try_days(1).unwrap())
             .format("%Y-%m-%d")
             .to_string();
     }
 
-    if start == 0.0 && get_next && time_in_seconds() > 86397.9 {
+    if start == 0.0 && get_next && time > 86397.9 {
         return (local + TimeDelta::try_days(1).unwrap())
             .format("%Y-%m-%d")
             .to_string();
@@ -410,10 +871,14 @@ pub fn modified_time(path: &str) -> Option<String> {
     None
 }
 
+fn is_time_now(time_str: &str) -> bool {
+    matches!(time_str, "now" | "" | "none") || !time_str.contains(':')
+}
+
 /// Convert a formatted time string to seconds.
 pub fn time_to_sec(time_str: &str) -> f64 {
-    if matches!(time_str, "now" | "" | "none") || !time_str.contains(':') {
-        return time_in_seconds();
+    if is_time_now(time_str) {
+        return time_of_day(None);
     }
 
     let t: Vec<&str> = time_str.split(':').collect();
@@ -424,6 +889,15 @@ pub fn time_to_sec(time_str: &str) -> f64 {
     h * 3600.0 + m * 60.0 + s
 }
 
+/// Convert the playlist day start to seconds, "now" is the current time in the given timezone.
+pub fn day_start_to_sec(day_start: &str, timezone: Option<Tz>) -> f64 {
+    if is_time_now(day_start) {
+        return time_of_day(timezone);
+    }
+
+    time_to_sec(day_start)
+}
+
 /// Convert floating number (seconds) to a formatted time string.
 pub fn sec_to_time(sec: f64) -> String {
     format!(
@@ -460,30 +934,27 @@ pub fn sum_durations(clip_list: &Vec<Media>) -> f64 {
 /// if we still in sync.
 ///
 /// We also get here the global delta between clip start and time when a new playlist should start.
+/// The global delta follows the real day length, and the length from multi day playlists.
 pub fn get_delta(config: &PlayoutConfig, begin: &f64) -> (f64, f64) {
-    let mut current_time = time_in_seconds();
+    let mut current_time = time_in_seconds(config);
     let start = config.playlist.start_sec.unwrap();
-    let length = config.playlist.length_sec.unwrap_or(86400.0);
-    let mut target_length = 86400.0;
+    let day_length = day_length(config, playlist_date(config));
+    let target_length = target_length(config);
 
-    if length > 0.0 && length != target_length {
-        target_length = length
-    }
-
-    if begin == &start && start == 0.0 && 86400.0 - current_time < 4.0 {
-        current_time -= 86400.0
+    if begin == &start && start == 0.0 && day_length - current_time < 4.0 {
+        current_time -= day_length
     } else if start >= current_time && begin != &start {
-        current_time += 86400.0
+        current_time += day_length
     }
 
     let mut current_delta = begin - current_time;
 
     if is_close(
         current_delta.abs(),
-        86400.0,
+        day_length,
         config.general.stop_threshold + 2.0,
     ) {
-        current_delta = current_delta.abs() - 86400.0
+        current_delta = current_delta.abs() - day_length
     }
 
     let total_delta = if current_time < start {
@@ -583,6 +1054,69 @@ pub fn seek_and_length(node: &mut Media) -> Vec<String> {
     source_cmd
 }
 
+/// Subtitles are passed as stream only in HLS mode, the mpegts pipe to the encoder can not carry text subtitles.
+pub fn subtitle_pass(config: &PlayoutConfig) -> bool {
+    config.processing.subtitle_mode == SubtitleMode::Pass && config.out.mode == HLS
+}
+
+fn empty_webvtt() -> String {
+    let path = std::env::temp_dir().join("ffplayout_empty.vtt");
+
+    if !path.is_file() {
+        if let Err(e) = fs::write(&path, "WEBVTT\n\n") {
+            error!("Create empty subtitle file: {e}");
+        }
+    }
+
+    path.to_string_lossy().to_string()
+}
+
+/// Add the subtitle file as last input, for the subtitle stream in HLS mode.
+///
+/// Clips without subtitle file get an empty WebVTT file, so all clips have the same streams.
+pub fn subtitle_input(config: &PlayoutConfig, node: &mut Media) {
+    if node.unit != Decoder || !subtitle_pass(config) || node.subtitle_index().is_some() {
+        return;
+    }
+
+    let subtitle = if Path::new(&node.subtitle).is_file() {
+        node.subtitle.clone()
+    } else {
+        if !node.subtitle.is_empty() {
+            error!("Subtitle <b><magenta>{}</></b> not exists!", node.subtitle);
+        }
+
+        empty_webvtt()
+    };
+
+    let Some(cmd) = node.cmd.as_mut() else {
+        return;
+    };
+
+    if cmd.windows(2).any(|c| c[0] == "-i" && c[1] == subtitle) {
+        return;
+    }
+
+    let mut input = vec![];
+
+    if subtitle == node.subtitle {
+        if node.seek > 0.5 {
+            input.append(&mut vec_strings!["-ss", node.seek]);
+        }
+
+        input.append(&mut vec_strings!["-t", node.out - node.seek]);
+    }
+
+    input.append(&mut vec_strings!["-i", subtitle]);
+
+    // after the last input, so a following -t stays an output option
+    let pos = cmd
+        .iter()
+        .rposition(|c| c == "-i")
+        .map_or(cmd.len(), |p| p + 2);
+    cmd.splice(pos..pos, input);
+}
+
 /// Create a dummy clip as a placeholder for missing video files.
 pub fn gen_dummy(config: &PlayoutConfig, duration: f64) -> (String, Vec<String>) {
     let color = "#121212";
@@ -717,8 +1251,7 @@ pub fn stderr_reader(
                 || (line.contains("No such file or directory")
                     && !line.contains("failed to delete old segment"))
             {
-                proc_control.stop_all();
-                exit(1);
+                proc_control.fail();
             }
         }
     }
@@ -810,6 +1343,168 @@ fn ffmpeg_filter_and_libs(config: &mut PlayoutConfig) -> Result<(), String> {
     Ok(())
 }
 
+/// Hardware device for ffmpeg `-init_hw_device`, named `hw` for the filters.
+fn hw_device(accel: HwAccel) -> String {
+    match (accel, &ADVANCED_CONFIG.acceleration_device) {
+        (HwAccel::Vaapi, None) => "vaapi=hw:/dev/dri/renderD128".to_string(),
+        (_, Some(device)) => format!("{}=hw:{device}", accel.name()),
+        (_, None) => format!("{}=hw", accel.name()),
+    }
+}
+
+/// Decoder arguments from the acceleration profile.
+pub fn hw_decoder_args(config: &PlayoutConfig) -> Vec<String> {
+    let accel = config.general.hw_accel;
+
+    match accel {
+        HwAccel::None => vec![],
+        HwAccel::Cuda => vec_strings![
+            "-hwaccel",
+            "cuvid",
+            "-c:v",
+            "h264_cuvid",
+            "-drop_second_field",
+            "true"
+        ],
+        HwAccel::Vaapi | HwAccel::Qsv => vec_strings![
+            "-init_hw_device",
+            hw_device(accel),
+            "-filter_hw_device",
+            "hw",
+            "-hwaccel",
+            accel.name(),
+            "-hwaccel_device",
+            "hw",
+            "-hwaccel_output_format",
+            accel.name()
+        ],
+    }
+}
+
+/// Looped images and generated clips (lavfi) can not be decoded on the GPU.
+pub fn hw_decodable(node: &Media) -> bool {
+    let image = node
+        .source
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .filter(|c| IMAGE_FORMAT.contains(&c.as_str()))
+        .is_some();
+    let generated = node.cmd.as_ref().is_some_and(|cmd| {
+        cmd.iter()
+            .take_while(|c| *c != "-i")
+            .any(|c| c == "-loop" || c == "lavfi")
+    });
+
+    !image && !generated
+}
+
+/// Options from the acceleration profile, they get removed from the input_param.
+const HW_INPUT_OPTIONS: [&str; 6] = [
+    "-hwaccel",
+    "-hwaccel_device",
+    "-hwaccel_output_format",
+    "-init_hw_device",
+    "-filter_hw_device",
+    "-drop_second_field",
+];
+
+/// Does the input_param contain options, which the acceleration profile sets.
+fn has_hw_input_options(input_cmd: &Option<Vec<String>>) -> bool {
+    input_cmd
+        .iter()
+        .flatten()
+        .any(|a| HW_INPUT_OPTIONS.contains(&a.as_str()))
+}
+
+/// Input arguments for decoding a clip.
+///
+/// The acceleration profile comes first, clips which are not decoded on the GPU get only the device for uploading.
+/// The hwaccel options from `input_cmd` are removed, so the CPU fallback gets no GPU frames.
+pub fn decoder_input_args(
+    node: &Media,
+    config: &PlayoutConfig,
+    input_cmd: &Option<Vec<String>>,
+) -> Vec<String> {
+    let accel = config.general.hw_accel;
+    let mut args = match accel {
+        _ if hw_decodable(node) => hw_decoder_args(config),
+        HwAccel::Vaapi | HwAccel::Qsv => {
+            vec_strings![
+                "-init_hw_device",
+                hw_device(accel),
+                "-filter_hw_device",
+                "hw"
+            ]
+        }
+        _ => vec![],
+    };
+    let mut input = input_cmd.iter().flatten();
+
+    while let Some(arg) = input.next() {
+        if HW_INPUT_OPTIONS.contains(&arg.as_str()) {
+            input.next();
+        } else {
+            args.push(arg.clone());
+        }
+    }
+
+    args
+}
+
+/// Test if ffmpeg supports the acceleration profile and the device can be opened.
+fn hw_accel_available(accel: HwAccel, filters: &[String]) -> Result<(), String> {
+    let needed = match accel {
+        HwAccel::None => return Ok(()),
+        HwAccel::Cuda => ["scale_npp", "overlay_cuda"],
+        HwAccel::Vaapi => ["scale_vaapi", "overlay_vaapi"],
+        HwAccel::Qsv => ["scale_qsv", "overlay_qsv"],
+    };
+
+    if let Some(filter) = needed.iter().find(|f| !filters.contains(&f.to_string())) {
+        return Err(format!("ffmpeg contains no {filter} filter"));
+    }
+
+    let hwaccels = Command::new("ffmpeg")
+        .args(["-hide_banner", "-hwaccels"])
+        .output()
+        .map_err(|e| format!("couldn't spawn ffmpeg process: {e}"))?;
+
+    if !String::from_utf8_lossy(&hwaccels.stdout)
+        .lines()
+        .any(|l| l.trim() == accel.name())
+    {
+        return Err(format!("ffmpeg supports no {} hwaccel", accel.name()));
+    }
+
+    let device = Command::new("ffmpeg")
+        .args([
+            "-hide_banner",
+            "-v",
+            "error",
+            "-init_hw_device",
+            &hw_device(accel),
+            "-f",
+            "lavfi",
+            "-i",
+            "nullsrc=s=64x64:d=0.1",
+            "-f",
+            "null",
+            "-",
+        ])
+        .output()
+        .map_err(|e| format!("couldn't spawn ffmpeg process: {e}"))?;
+
+    if !device.status.success() {
+        return Err(format!(
+            "no {} device: {}",
+            accel.name(),
+            String::from_utf8_lossy(&device.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
 /// Validate ffmpeg/ffprobe/ffplay.
 ///
 /// Check if they are in system and has all libs and codecs we need.
@@ -823,6 +1518,19 @@ pub fn validate_ffmpeg(config: &mut PlayoutConfig) -> Result<(), String> {
 
     ffmpeg_filter_and_libs(config)?;
 
+    if let Err(e) = hw_accel_available(config.general.hw_accel, &config.general.ffmpeg_filters) {
+        warn!(
+            "Acceleration <yellow>{}</> is not available ({e}), use the CPU!",
+            config.general.hw_accel.name()
+        );
+
+        config.general.hw_accel = HwAccel::None;
+    }
+
+    if has_hw_input_options(&ADVANCED_CONFIG.decoder.input_cmd) {
+        warn!("The hwaccel options from decoder <b><magenta>input_param</></b> are replaced by the acceleration profile!");
+    }
+
     if config
         .out
         .output_cmd
@@ -844,6 +1552,15 @@ pub fn validate_ffmpeg(config: &mut PlayoutConfig) -> Result<(), String> {
         );
     }
 
+    if ADVANCED_CONFIG.decoder.filters.tonemap.is_none()
+        && !config
+            .general
+            .ffmpeg_filters
+            .contains(&"zscale".to_string())
+    {
+        warn!("ffmpeg contains no zscale filter, HDR sources are played without tone mapping!");
+    }
+
     if config
         .out
         .output_cmd
@@ -861,6 +1578,107 @@ pub fn validate_ffmpeg(config: &mut PlayoutConfig) -> Result<(), String> {
     Ok(())
 }
 
+/// Prepare output parameters
+///
+/// Seek for multiple outputs and add mapping for it.
+pub fn prepare_output_cmd(
+    config: &PlayoutConfig,
+    mut cmd: Vec<String>,
+    filters: &Option<Filters>,
+) -> Vec<String> {
+    let mut output_params = config.out.clone().output_cmd.unwrap();
+    let mut new_params = vec![];
+    let mut count = 0;
+    let re_v = Regex::new(r"\[?0:v(:0)?\]?").unwrap();
+
+    if let Some(mut filter) = filters.clone() {
+        for (i, param) in output_params.iter().enumerate() {
+            if filter.video_out_link.len() > count && re_v.is_match(param) {
+                // replace mapping with link from filter struct
+                new_params.push(filter.video_out_link[count].clone());
+            } else {
+                new_params.push(param.clone());
+            }
+
+            // Check if parameter is a output
+            if i > 0
+                && !param.starts_with('-')
+                && !output_params[i - 1].starts_with('-')
+                && i < output_params.len() - 1
+            {
+                count += 1;
+
+                if filter.video_out_link.len() > count
+                    && !output_params.contains(&"-map".to_string())
+                {
+                    new_params.append(&mut vec_strings![
+                        "-map",
+                        filter.video_out_link[count].clone()
+                    ]);
+
+                    for i in 0..config.processing.audio_tracks {
+                        new_params.append(&mut vec_strings![
+                            "-map",
+                            format!("0:a:{}", filter.audio_stream(i))
+                        ]);
+                    }
+                }
+            }
+        }
+
+        output_params = new_params;
+
+        cmd.append(&mut filter.cmd());
+
+        // add mapping at the begin, if needed
+        if !filter.map().iter().all(|item| output_params.contains(item))
+            && filter.output_chain.is_empty()
+            && filter.video_out_link.is_empty()
+        {
+            cmd.append(&mut filter.map())
+        } else if &output_params[0] != "-map" && !filter.video_out_link.is_empty() {
+            cmd.append(&mut vec_strings!["-map", filter.video_out_link[0].clone()]);
+
+            for i in 0..config.processing.audio_tracks {
+                cmd.append(&mut vec_strings![
+                    "-map",
+                    format!("0:a:{}", filter.audio_stream(i))
+                ]);
+            }
+        }
+
+        if !filter
+            .subtitle_map
+            .iter()
+            .all(|item| output_params.contains(item))
+        {
+            cmd.append(&mut filter.subtitle_map.clone());
+        }
+    }
+
+    cmd.append(&mut output_params);
+
+    cmd
+}
+
+/// Spawn a thread, which gets the name from the current thread.
+///
+/// In multi channel mode the channel threads are named by the channel,
+/// so log lines from sub threads can be assigned to the channel too.
+pub fn spawn_thread<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let mut builder = thread::Builder::new();
+
+    if let Some(name) = thread::current().name().filter(|n| *n != "main") {
+        builder = builder.name(name.to_string());
+    }
+
+    builder.spawn(f).expect("failed to spawn thread")
+}
+
 /// get a free tcp socket
 pub fn free_tcp_socket(exclude_socket: String) -> Option<String> {
     for _ in 0..100 {
@@ -902,23 +1720,38 @@ pub fn test_tcp_port(url: &str) -> bool {
 
 /// Generate a vector with dates, from given range.
 pub fn get_date_range(date_range: &[String]) -> Vec<String> {
-    let mut range = vec![];
-
-    let start = match NaiveDate::parse_from_str(&date_range[0], "%Y-%m-%d") {
-        Ok(s) => s,
-        Err(_) => {
-            error!("date format error in: <yellow>{:?}</>", date_range[0]);
+    match try_date_range(date_range) {
+        Ok(range) => range,
+        Err(e) => {
+            error!("{e}");
             exit(1);
         }
+    }
+}
+
+/// Generate a vector with dates, from given range, like: `[start, "-", end]`.
+/// Single dates, or a list of dates, are validated and returned as they are.
+pub fn try_date_range(date_range: &[String]) -> Result<Vec<String>, Error> {
+    let parse = |date: &str| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("date format error in: <yellow>{date:?}</>"),
+            )
+        })
     };
 
-    let end = match NaiveDate::parse_from_str(&date_range[2], "%Y-%m-%d") {
-        Ok(e) => e,
-        Err(_) => {
-            error!("date format error in: <yellow>{:?}</>", date_range[2]);
-            exit(1);
+    if date_range.len() != 3 || date_range[1] != "-" {
+        for date in date_range {
+            parse(date)?;
         }
-    };
+
+        return Ok(date_range.to_vec());
+    }
+
+    let mut range = vec![];
+    let start = parse(&date_range[0])?;
+    let end = parse(&date_range[2])?;
 
     let duration = end.signed_duration_since(start);
     let days = duration.num_days() + 1;
@@ -931,7 +1764,7 @@ pub fn get_date_range(date_range: &[String]) -> Vec<String> {
         );
     }
 
-    range
+    Ok(range)
 }
 
 pub fn parse_log_level_filter(s: &str) -> Result<LevelFilter, &'static str> {
@@ -0,0 +1,319 @@
+/// Bulk Playlist Operations
+///
+/// Copy a playlist to a date range, repeat a weekly pattern, shift a range of days
+/// or delete a range of playlists. All operations support a dry run, which only reports
+/// what would be done, and a policy for already existing target playlists.
+///
+/// You can call ffplayout[.exe] --copy YYYY-mm-dd --range YYYY-mm-dd - YYYY-mm-dd
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::{self, create_dir_all},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{Datelike, NaiveDate, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{json_reader, json_writer, PlayoutConfig};
+
+/// What to do, when a target playlist already exists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExistsPolicy {
+    #[default]
+    Skip,
+    Overwrite,
+}
+
+impl FromStr for ExistsPolicy {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            _ => Err(format!("Policy '{input}' not exists!")),
+        }
+    }
+}
+
+/// Supported bulk operations, the target dates are given separately.
+///
+/// - copy: copy the `source` playlist to every target date, with `weekly` only to dates
+///   with the same weekday as the source
+/// - repeat: repeat the week which starts at `source` over the target dates
+/// - shift: move every playlist in the target dates by `days`
+/// - delete: delete every playlist in the target dates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "lowercase")]
+pub enum BulkOperation {
+    Copy {
+        source: String,
+        #[serde(default)]
+        weekly: bool,
+    },
+    Repeat {
+        source: String,
+    },
+    Shift {
+        days: i64,
+    },
+    Delete,
+}
+
+/// Action which is (or in dry run mode would be) applied to a target date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkAction {
+    Write,
+    Overwrite,
+    Skip,
+    Delete,
+    Missing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkResult {
+    pub source: Option<String>,
+    pub target: String,
+    pub action: BulkAction,
+}
+
+impl BulkResult {
+    fn new(source: Option<&str>, target: &str, action: BulkAction) -> Self {
+        Self {
+            source: source.map(|s| s.to_string()),
+            target: target.to_string(),
+            action,
+        }
+    }
+}
+
+impl fmt::Display for BulkResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = match self.action {
+            BulkAction::Write => "write",
+            BulkAction::Overwrite => "overwrite",
+            BulkAction::Skip => "skip, playlist exists",
+            BulkAction::Delete => "delete",
+            BulkAction::Missing => "skip, no playlist",
+        };
+
+        match &self.source {
+            Some(source) => write!(f, "{source} -> {}: {action}", self.target),
+            None => write!(f, "{}: {action}", self.target),
+        }
+    }
+}
+
+/// Get the playlist path for a date, in the form: `root/YYYY/MM/YYYY-MM-DD.json`
+pub fn playlist_file(root: &Path, date: &str) -> PathBuf {
+    let d: Vec<&str> = date.split('-').collect();
+
+    root.join(d[0])
+        .join(d.get(1).unwrap_or(&""))
+        .join(date)
+        .with_extension("json")
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, Error> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Date format error in: {date}"),
+        )
+    })
+}
+
+fn shift_date(date: &str, days: i64) -> Result<String, Error> {
+    let start = parse_date(date)?;
+    let shifted = TimeDelta::try_days(days)
+        .and_then(|delta| start.checked_add_signed(delta))
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Shift of {days} days from {date} is out of range"),
+            )
+        })?;
+
+    Ok(shifted.format("%Y-%m-%d").to_string())
+}
+
+/// Keeps track of the playlist files, so that a dry run reports the same
+/// actions as a real run, even when operations depend on each other.
+struct BulkWriter<'a> {
+    root: &'a Path,
+    policy: ExistsPolicy,
+    dry_run: bool,
+    written: HashSet<String>,
+    removed: HashSet<String>,
+}
+
+impl<'a> BulkWriter<'a> {
+    fn new(root: &'a Path, policy: ExistsPolicy, dry_run: bool) -> Self {
+        Self {
+            root,
+            policy,
+            dry_run,
+            written: HashSet::new(),
+            removed: HashSet::new(),
+        }
+    }
+
+    fn exists(&self, date: &str) -> bool {
+        self.written.contains(date)
+            || (!self.removed.contains(date) && playlist_file(self.root, date).is_file())
+    }
+
+    fn copy(&mut self, source: &str, target: &str) -> Result<BulkResult, Error> {
+        if !self.exists(source) {
+            return Ok(BulkResult::new(Some(source), target, BulkAction::Missing));
+        }
+
+        let action = if self.exists(target) {
+            if self.policy == ExistsPolicy::Skip {
+                return Ok(BulkResult::new(Some(source), target, BulkAction::Skip));
+            }
+
+            BulkAction::Overwrite
+        } else {
+            BulkAction::Write
+        };
+
+        if !self.dry_run {
+            let target_file = playlist_file(self.root, target);
+            let mut playlist = json_reader(&playlist_file(self.root, source))?;
+            playlist.date = target.to_string();
+
+            if let Some(p) = target_file.parent() {
+                create_dir_all(p)?;
+            }
+
+            json_writer(&target_file, playlist)?;
+        }
+
+        self.removed.remove(target);
+        self.written.insert(target.to_string());
+
+        Ok(BulkResult::new(Some(source), target, action))
+    }
+
+    fn delete(&mut self, date: &str) -> Result<BulkResult, Error> {
+        if !self.exists(date) {
+            return Ok(BulkResult::new(None, date, BulkAction::Missing));
+        }
+
+        if !self.dry_run {
+            fs::remove_file(playlist_file(self.root, date))?;
+        }
+
+        self.written.remove(date);
+        self.removed.insert(date.to_string());
+
+        Ok(BulkResult::new(None, date, BulkAction::Delete))
+    }
+}
+
+/// Run a bulk operation over the given target dates.
+///
+/// The dates should come from `get_date_range`. In dry run mode nothing is written
+/// or deleted, but the result list contains the actions which would be applied.
+pub fn bulk_playlists(
+    config: &PlayoutConfig,
+    operation: &BulkOperation,
+    dates: &[String],
+    policy: ExistsPolicy,
+    dry_run: bool,
+) -> Result<Vec<BulkResult>, Error> {
+    let playlist_root = &config.playlist.path;
+    let mut writer = BulkWriter::new(playlist_root, policy, dry_run);
+    let mut results = vec![];
+
+    if !playlist_root.is_dir() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Playlist folder {playlist_root:?} not exists!"),
+        ));
+    }
+
+    for date in dates {
+        parse_date(date)?;
+    }
+
+    match operation {
+        BulkOperation::Copy { source, weekly } => {
+            let source_weekday = parse_date(source)?.weekday();
+
+            if !writer.exists(source) {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("Source playlist from {source} not exists!"),
+                ));
+            }
+
+            for date in dates {
+                if date == source || (*weekly && parse_date(date)?.weekday() != source_weekday) {
+                    continue;
+                }
+
+                results.push(writer.copy(source, date)?);
+            }
+        }
+        BulkOperation::Repeat { source } => {
+            let week_start = parse_date(source)?;
+
+            for date in dates {
+                let offset = (parse_date(date)? - week_start).num_days().rem_euclid(7);
+                let source_date = shift_date(source, offset)?;
+
+                if &source_date == date {
+                    continue;
+                }
+
+                results.push(writer.copy(&source_date, date)?);
+            }
+        }
+        BulkOperation::Shift { days } => {
+            if *days == 0 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Shift needs a day offset!",
+                ));
+            }
+
+            let mut sources = dates.to_vec();
+
+            // move the latest playlist first, when shifting forward, so no source gets overwritten
+            if *days > 0 {
+                sources.reverse();
+            }
+
+            for date in sources {
+                let target = shift_date(&date, *days)?;
+
+                if !writer.exists(&date) {
+                    results.push(BulkResult::new(Some(&date), &target, BulkAction::Missing));
+                    continue;
+                }
+
+                let result = writer.copy(&date, &target)?;
+
+                if result.action != BulkAction::Skip {
+                    writer.delete(&date)?;
+                }
+
+                results.push(result);
+            }
+        }
+        BulkOperation::Delete => {
+            for date in dates {
+                results.push(writer.delete(date)?);
+            }
+        }
+    }
+
+    Ok(results)
+}
@@ -2,9 +2,15 @@ use std::{fs, path::PathBuf};
 
 use ffplayout::{input::playlist::gen_source, utils::prepare_output_cmd};
 use ffplayout_lib::{
-    utils::{Media, OutputMode::*, PlayerControl, PlayoutConfig, PlayoutStatus, ProcessUnit::*},
+    filter::v_drawtext,
+    utils::{
+        advanced_config::HwAccel, decoder_input_args, gen_dummy, hw_decoder_args, seek_and_length,
+        AudioOptions, ChannelMap, ColorInfo, FitMode, LogoLayer, Media, MediaProbe, OutputMode::*,
+        PlayerControl, PlayoutConfig, PlayoutStatus, ProcessUnit::*, SubtitleMode, TextLayer,
+    },
     vec_strings,
 };
+use ffprobe::{Stream as FFStream, StreamTags};
 
 #[test]
 fn video_audio_input() {
@@ -1318,6 +1324,158 @@ fn video_audio_text_filter_stream() {
     assert_eq!(enc_cmd, test_cmd);
 }
 
+#[test]
+fn video_text_layers_stream() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.text.add_text = true;
+    config.text.fontfile = String::new();
+    config.text.layers = vec![
+        TextLayer {
+            name: "clock".to_string(),
+            text: "%{localtime}".to_string(),
+            style: "x=10:y=10".to_string(),
+        },
+        TextLayer {
+            name: "ticker".to_string(),
+            ..Default::default()
+        },
+    ];
+
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
+
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='',drawtext@clock=text='%{{localtime}}':x=10:y=10,drawtext@ticker=text=''[vout0]")
+    ];
+
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+    assert_eq!(
+        config.text.layer_target(Some("ticker")),
+        Some("drawtext@ticker".to_string())
+    );
+    assert_eq!(
+        config.text.layer_target(None),
+        Some("drawtext@dyntext".to_string())
+    );
+    assert_eq!(config.text.layer_target(Some("logo")), None);
+}
+
+#[test]
+fn video_text_layers_keep_last_text() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = HLS;
+    config.processing.add_logo = false;
+    config.text.add_text = true;
+    config.text.fontfile = String::new();
+    config.text.layers = vec![TextLayer {
+        name: "lower third".to_string(),
+        text: "start".to_string(),
+        style: "x=10:y=10".to_string(),
+    }];
+
+    let socket = config
+        .text
+        .zmq_stream_socket
+        .clone()
+        .unwrap()
+        .replace(':', "\\:");
+
+    let playout_stat = PlayoutStatus::new();
+    let chain = playout_stat.chain.clone().unwrap();
+
+    v_drawtext::store_text(&chain, "drawtext@dyntext", "text='news'");
+    v_drawtext::store_text(&chain, "drawtext@lower third", "text='first'");
+    v_drawtext::store_text(&chain, "drawtext@lower third", "text='second':y=20");
+
+    let mut media = Media::new(0, "", false);
+    media.unit = Encoder;
+    media.add_filter(&config, &playout_stat.chain);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        format!("[0:v:0]zmq=b=tcp\\\\://'{socket}',drawtext@dyntext=text='news',drawtext@lower third=text='second':y=20[vout0]")
+    ];
+
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+    assert_eq!(chain.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn video_logo_layers() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    let logo_path = fs::canonicalize("./assets/logo.png").unwrap();
+    let logo = logo_path.to_string_lossy().to_string();
+
+    config.processing.logo_layers = vec![
+        LogoLayer {
+            name: "live".to_string(),
+            path: logo.clone(),
+            position: "12:12".to_string(),
+            opacity: 1.0,
+            enable: true,
+            categories: vec!["live".to_string()],
+            ..Default::default()
+        },
+        LogoLayer {
+            name: "sponsor".to_string(),
+            path: logo.clone(),
+            position: "W-w-12:12".to_string(),
+            scale: "0.5".to_string(),
+            opacity: 0.8,
+            enable: true,
+            from_time: "08:00:00".to_string(),
+            to_time: "18:00:00".to_string(),
+            ..Default::default()
+        },
+        LogoLayer {
+            name: "missing".to_string(),
+            path: "./assets/not_exists.png".to_string(),
+            enable: true,
+            ..Default::default()
+        },
+    ];
+    config.processing.logo_zmq_socket = Some("127.0.0.1:5555".to_string());
+
+    let mut media = Media::new(0, "", false);
+    media.begin = Some(3600.0);
+    media.add_filter(&config, &None);
+
+    assert!(media.filter.unwrap().video_chain.ends_with(&format!(",null[vlogo0];movie={logo}:loop=0,setpts=N/(FRAME_RATE*TB),zmq=b=tcp\\\\://'127.0.0.1\\:5555',format=rgba,colorchannelmixer=aa=1,colorchannelmixer@logo_live=aa=0:enable='1',hwupload_cuda[llogo0];[vlogo0][llogo0]overlay_cuda=12:12:shortest=1,null[vlogo1];movie={logo}:loop=0,setpts=N/(FRAME_RATE*TB),format=rgba,colorchannelmixer=aa=0.8,colorchannelmixer@logo_sponsor=aa=0:enable='not(gte(mod(t+3600.000,86400),28800)*lt(mod(t+3600.000,86400),64800))',scale=iw*0.5:ih*0.5,hwupload_cuda[llogo1];[vlogo1][llogo1]overlay_cuda=W-w-12:12:shortest=1")));
+
+    // clip and switch rules win over the category and time rules
+    let mut media = Media::new(0, "", false);
+    media.begin = Some(3600.0);
+    media.unit = Ingest;
+    media.logos.insert("live".to_string(), true);
+    config
+        .processing
+        .logo_overrides
+        .lock()
+        .unwrap()
+        .insert("sponsor".to_string(), false);
+    media.add_filter(&config, &None);
+
+    let video_chain = media.filter.unwrap().video_chain;
+
+    assert!(video_chain.contains("colorchannelmixer@logo_live=aa=0:enable='0'"));
+    assert!(video_chain.contains("colorchannelmixer@logo_sponsor=aa=0:enable='1'"));
+    assert!(!video_chain.contains("zmq"));
+}
+
 #[test]
 fn video_audio_hls() {
     let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
@@ -1826,3 +1984,424 @@ fn multi_video_multi_audio_hls() {
 
     assert_eq!(enc_cmd, test_cmd);
 }
+
+#[test]
+fn video_subtitle_burn() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+
+    let mut media = Media::new(0, "./assets/media_mix/with_audio.mp4", false);
+    media.seek = 5.0;
+    media.out = 30.0;
+    media.duration = 30.0;
+    media.subtitle = "./assets/media_mix/subtitle.vtt".to_string();
+    media.cmd = Some(seek_and_length(&mut media));
+    media.add_filter(&config, &None);
+
+    let test_filter_cmd = vec_strings![
+        "-filter_complex",
+        "[0:v:0]scale_npp=format=yuv420p,scale_npp=1024:576:interp_algo=super:force_original_aspect_ratio=decrease,setdar=dar=1.778,hwdownload,format=yuv420p,setpts=PTS+5/TB,subtitles=./assets/media_mix/subtitle.vtt,setpts=PTS-5/TB,format=yuv420p,hwupload[vout0];aevalsrc=0:channel_layout=stereo:duration=25:sample_rate=48000,anull,afade=in:st=0:d=0.5[aout0]"
+    ];
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings![
+            "-ss",
+            "5",
+            "-i",
+            "./assets/media_mix/with_audio.mp4"
+        ])
+    );
+    assert_eq!(media.filter.unwrap().cmd(), test_filter_cmd);
+}
+
+#[test]
+fn video_subtitle_pass_hls() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = HLS;
+    config.processing.add_logo = false;
+    config.processing.subtitle_mode = SubtitleMode::Pass;
+    config.text.add_text = false;
+    config.out.output_cmd = Some(vec_strings![
+        "-c:v",
+        "libx264",
+        "-c:a",
+        "aac",
+        "-c:s",
+        "webvtt",
+        "-f",
+        "hls",
+        "-master_pl_name",
+        "master.m3u8",
+        "/usr/share/ffplayout/public/live/stream.m3u8"
+    ]);
+
+    let mut media = Media::new(0, "./assets/media_mix/with_audio.mp4", false);
+    media.seek = 5.0;
+    media.out = 30.0;
+    media.duration = 30.0;
+    media.subtitle = "./assets/media_mix/subtitle.vtt".to_string();
+    media.cmd = Some(seek_and_length(&mut media));
+    media.add_filter(&config, &None);
+
+    assert_eq!(
+        media.cmd,
+        Some(vec_strings![
+            "-ss",
+            "5",
+            "-i",
+            "./assets/media_mix/with_audio.mp4",
+            "-ss",
+            "5",
+            "-t",
+            "25",
+            "-i",
+            "./assets/media_mix/subtitle.vtt"
+        ])
+    );
+    assert_eq!(
+        media.filter.as_ref().unwrap().subtitle_map,
+        vec_strings!["-map", "1:s:0"]
+    );
+
+    let enc_cmd = prepare_output_cmd(&config, vec![], &media.filter);
+    let map_pos = enc_cmd.iter().position(|p| p == "1:s:0").unwrap();
+
+    assert_eq!(enc_cmd[map_pos - 1], "-map");
+    assert!(map_pos < enc_cmd.iter().position(|p| p == "-c:v").unwrap());
+}
+
+#[test]
+fn audio_options_input() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.audio_only = true;
+    config.text.add_text = false;
+
+    let stream = |language: &str| FFStream {
+        codec_type: Some("audio".to_string()),
+        tags: Some(StreamTags {
+            language: Some(language.to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let mut media = Media::new(0, "./assets/media_mix/dual_audio.mp4", false);
+    media.duration = 30.0;
+    media.out = 30.0;
+    media.probe = Some(MediaProbe {
+        format: Default::default(),
+        audio_streams: vec![stream("deu"), stream("eng")],
+        video_streams: vec![],
+        ..Default::default()
+    });
+    media.audio_options = AudioOptions {
+        gain: -3.0,
+        delay: 0.12,
+        channels: Some(ChannelMap::Left),
+        language: vec!["eng".to_string()],
+    };
+    media.add_filter(&config, &None);
+
+    let mut filter = media.filter.unwrap();
+
+    assert_eq!(
+        filter.cmd(),
+        vec_strings![
+            "-filter_complex",
+            "[0:a:1]adelay=delays=120:all=1,atrim=end=30,anull,pan=stereo|c0=c0|c1=c0,volume=-3dB[aout0]"
+        ]
+    );
+    assert_eq!(filter.map(), vec_strings!["-map", "[aout0]"]);
+}
+
+#[test]
+fn video_fit_modes() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.processing.aspect = 1.778;
+    config.processing.fit = FitMode::Crop;
+    config.text.add_text = false;
+
+    let mut media = Media::new(0, "./assets/media_mix/with_audio.mp4", false);
+    media.duration = 30.0;
+    media.out = 30.0;
+    media.probe = Some(MediaProbe {
+        format: Default::default(),
+        audio_streams: vec![],
+        video_streams: vec![FFStream {
+            codec_type: Some("video".to_string()),
+            width: Some(720),
+            height: Some(576),
+            display_aspect_ratio: Some("4:3".to_string()),
+            field_order: Some("progressive".to_string()),
+            r_frame_rate: "25/1".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+
+    let scale = "scale_npp=format=yuv420p,scale_npp=1024:576:interp_algo=super";
+
+    media.add_filter(&config, &None);
+    assert!(media.filter.as_ref().unwrap().video_chain.starts_with(&format!(
+        "[0:v:0]hwdownload,format=nv12,crop=w='min(iw,ih*1.778/sar)':h='min(ih,iw*sar/1.778)',format=nv12,hwupload,{scale}:force_original_aspect_ratio=decrease"
+    )));
+
+    // the clip setting comes before the channel default
+    media.fit = Some(FitMode::Stretch);
+    media.add_filter(&config, &None);
+    assert!(media
+        .filter
+        .as_ref()
+        .unwrap()
+        .video_chain
+        .starts_with(&format!("[0:v:0]{scale},setsar=1")));
+
+    media.fit = Some(FitMode::Blur);
+    media.add_filter(&config, &None);
+    assert!(media
+        .filter
+        .as_ref()
+        .unwrap()
+        .video_chain
+        .contains("[vfit_b][vfit_f]overlay=(W-w)/2:(H-h)/2,format=nv12,hwupload"));
+}
+
+#[test]
+fn video_hdr_tonemap() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = Stream;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+
+    let mut media = Media::new(0, "./assets/media_mix/with_audio.mp4", false);
+    media.duration = 30.0;
+    media.out = 30.0;
+    media.probe = Some(MediaProbe {
+        video_streams: vec![FFStream {
+            codec_type: Some("video".to_string()),
+            width: Some(1024),
+            height: Some(576),
+            display_aspect_ratio: Some("16:9".to_string()),
+            field_order: Some("progressive".to_string()),
+            r_frame_rate: "25/1".to_string(),
+            ..Default::default()
+        }],
+        color: ColorInfo {
+            color_transfer: Some("smpte2084".to_string()),
+            color_primaries: Some("bt2020".to_string()),
+            color_space: Some("bt2020nc".to_string()),
+        },
+        ..Default::default()
+    });
+
+    config.general.ffmpeg_filters = vec_strings!["zscale"];
+    media.add_filter(&config, &None);
+    assert!(media.filter.as_ref().unwrap().video_chain.starts_with(
+        "[0:v:0]hwdownload,format=p010le,zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p,format=nv12,hwupload,scale_npp=format=yuv420p"
+    ));
+
+    // without zscale the clip is played untouched
+    config.general.ffmpeg_filters.clear();
+    media.add_filter(&config, &None);
+    assert_eq!(
+        media.filter.as_ref().unwrap().video_chain,
+        "[0:v:0]scale_npp=format=yuv420p"
+    );
+
+    // SD material gets only a colorspace conversion
+    media.probe.as_mut().unwrap().color = ColorInfo {
+        color_transfer: Some("bt709".to_string()),
+        color_primaries: Some("bt470bg".to_string()),
+        color_space: Some("bt470bg".to_string()),
+    };
+    media.add_filter(&config, &None);
+    assert!(media
+        .filter
+        .as_ref()
+        .unwrap()
+        .video_chain
+        .starts_with("[0:v:0]hwdownload,format=nv12,colorspace=all=bt709:iall=bt470bg:fast=1,format=nv12,hwupload,scale_npp=format=yuv420p"));
+}
+
+#[test]
+fn video_acceleration_profiles() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.out.mode = Stream;
+    config.processing.add_logo = true;
+    config.processing.logo = fs::canonicalize("./assets/logo.png")
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+    config.processing.logo_scale = String::new();
+    config.text.add_text = false;
+    config.general.hw_accel = HwAccel::None;
+
+    let mut media = Media::new(0, "./assets/media_mix/with_audio.mp4", false);
+    media.duration = 30.0;
+    media.seek = 5.0;
+    media.out = 30.0;
+    media.probe = Some(MediaProbe {
+        video_streams: vec![FFStream {
+            codec_type: Some("video".to_string()),
+            width: Some(720),
+            height: Some(576),
+            display_aspect_ratio: Some("4:3".to_string()),
+            field_order: Some("progressive".to_string()),
+            r_frame_rate: "30/1".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+
+    // the CPU pipeline has no hardware frames, so nothing gets downloaded
+    media.add_filter(&config, &None);
+    assert_eq!(
+        media.filter.as_ref().unwrap().video_chain,
+        format!("[0:v:0]pad=1024:576:(ow-iw)/2:(oh-ih)/2:color=black,setdar=16:9,setsar=1:1,fps=25,scale=1024:576:force_original_aspect_ratio=decrease,setdar=dar=1.778,fade=in:st=0:d=0.5,null[v];movie={}[l0];[l0]format=rgba,colorchannelmixer=aa=0.7[l];[v][l]overlay=W-w-12:12:shortest=1", config.processing.logo)
+    );
+    assert!(hw_decoder_args(&config).is_empty());
+
+    config.general.hw_accel = HwAccel::Vaapi;
+    media.add_filter(&config, &None);
+    assert_eq!(
+        media.filter.as_ref().unwrap().video_chain,
+        format!("[0:v:0]hwdownload,format=nv12,pad=1024:576:(ow-iw)/2:(oh-ih)/2:color=black,setdar=16:9,setsar=1:1,format=nv12,hwupload,scale_vaapi=w=1024:h=576:format=nv12:force_original_aspect_ratio=decrease,setdar=dar=1.778,null[v];movie={}[l0];[l0]format=rgba,colorchannelmixer=aa=0.7,hwupload[l];[v][l]overlay_vaapi=W-w-12:12:shortest=1", config.processing.logo)
+    );
+
+    // fps and fade cost a GPU round trip, they only run when they are enabled
+    config.general.hw_cpu_filters = true;
+    media.add_filter(&config, &None);
+    assert_eq!(
+        media.filter.as_ref().unwrap().video_chain,
+        format!("[0:v:0]hwdownload,format=nv12,pad=1024:576:(ow-iw)/2:(oh-ih)/2:color=black,setdar=16:9,setsar=1:1,format=nv12,hwupload,hwdownload,format=nv12,fps=25,format=nv12,hwupload,scale_vaapi=w=1024:h=576:format=nv12:force_original_aspect_ratio=decrease,setdar=dar=1.778,hwdownload,format=nv12,fade=in:st=0:d=0.5,format=nv12,hwupload,null[v];movie={}[l0];[l0]format=rgba,colorchannelmixer=aa=0.7,hwupload[l];[v][l]overlay_vaapi=W-w-12:12:shortest=1", config.processing.logo)
+    );
+    config.general.hw_cpu_filters = false;
+    assert_eq!(
+        hw_decoder_args(&config),
+        vec_strings![
+            "-init_hw_device",
+            "vaapi=hw:/dev/dri/renderD128",
+            "-filter_hw_device",
+            "hw",
+            "-hwaccel",
+            "vaapi",
+            "-hwaccel_device",
+            "hw",
+            "-hwaccel_output_format",
+            "vaapi"
+        ]
+    );
+}
+
+#[test]
+fn decoder_acceleration_profiles() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    let input_cmd = Some(vec_strings![
+        "-thread_queue_size",
+        "1024",
+        "-hwaccel",
+        "cuvid",
+        "-hwaccel_output_format",
+        "cuda"
+    ]);
+
+    let video = Media::new(0, "./assets/media_mix/with_audio.mp4", false);
+    let mut image = Media::new(0, "./assets/logo.png", false);
+    image.cmd = Some(vec_strings!["-loop", "1", "-i", "./assets/logo.png"]);
+    let (source, cmd) = gen_dummy(&config, 30.0);
+    let mut dummy = Media::new(0, &source, false);
+    dummy.cmd = Some(cmd);
+
+    // the hwaccel options from the input_param are replaced by the profile
+    let profiles = [
+        (HwAccel::None, vec![], vec![]),
+        (
+            HwAccel::Cuda,
+            vec_strings![
+                "-hwaccel",
+                "cuvid",
+                "-c:v",
+                "h264_cuvid",
+                "-drop_second_field",
+                "true"
+            ],
+            vec![],
+        ),
+        (
+            HwAccel::Vaapi,
+            vec_strings![
+                "-init_hw_device",
+                "vaapi=hw:/dev/dri/renderD128",
+                "-filter_hw_device",
+                "hw",
+                "-hwaccel",
+                "vaapi",
+                "-hwaccel_device",
+                "hw",
+                "-hwaccel_output_format",
+                "vaapi"
+            ],
+            vec_strings![
+                "-init_hw_device",
+                "vaapi=hw:/dev/dri/renderD128",
+                "-filter_hw_device",
+                "hw"
+            ],
+        ),
+        (
+            HwAccel::Qsv,
+            vec_strings![
+                "-init_hw_device",
+                "qsv=hw",
+                "-filter_hw_device",
+                "hw",
+                "-hwaccel",
+                "qsv",
+                "-hwaccel_device",
+                "hw",
+                "-hwaccel_output_format",
+                "qsv"
+            ],
+            vec_strings!["-init_hw_device", "qsv=hw", "-filter_hw_device", "hw"],
+        ),
+    ];
+
+    for (accel, video_args, cpu_args) in profiles {
+        config.general.hw_accel = accel;
+        let queue = vec_strings!["-thread_queue_size", "1024"];
+
+        assert_eq!(
+            decoder_input_args(&video, &config, &input_cmd),
+            [video_args, queue.clone()].concat()
+        );
+        assert_eq!(
+            decoder_input_args(&image, &config, &input_cmd),
+            [cpu_args.clone(), queue.clone()].concat()
+        );
+        assert_eq!(
+            decoder_input_args(&dummy, &config, &input_cmd),
+            [cpu_args, queue].concat()
+        );
+    }
+
+    // the CPU decoded frames get uploaded for the hardware filters
+    config.general.hw_accel = HwAccel::Vaapi;
+    config.processing.add_logo = false;
+    config.text.add_text = false;
+    dummy.out = 30.0;
+    dummy.duration = 30.0;
+    dummy.add_filter(&config, &None);
+
+    assert!(dummy
+        .filter
+        .unwrap()
+        .video_chain
+        .starts_with("[0:v:0]format=nv12,hwupload,"));
+}
@@ -50,6 +50,70 @@ fn test_ordered_list() {
     assert_eq!(sum_durations(&o_list), 115.0);
 }
 
+#[test]
+fn test_exact_list() {
+    let config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    let clip_list: Vec<Media> = [1320.0, 1500.0, 1710.5, 2580.0, 3000.0, 845.2, 600.0]
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let mut media = Media::new(i, &format!("clip_{i}.mp4"), false);
+            media.duration = *d;
+            media.out = *d;
+
+            media
+        })
+        .collect();
+
+    let e_list = exact_list(&config, clip_list.clone(), 86400.0, 1.0);
+    let e_duration = sum_durations(&e_list);
+
+    assert!(86400.0 >= e_duration, "duration is {e_duration}");
+    assert!(86400.0 - e_duration <= 1.0, "duration is {e_duration}");
+    assert!(e_list.windows(2).all(|c| c[0].source != c[1].source));
+
+    let e_list = exact_list(&config, clip_list.clone(), 6876.0, 1.0);
+    let e_duration = sum_durations(&e_list);
+
+    assert!(6876.0 >= e_duration, "duration is {e_duration}");
+    assert!(6876.0 - e_duration <= 1.0, "duration is {e_duration}");
+
+    // No combination fits, so the rest needs a filler.
+    let e_list = exact_list(&config, clip_list, 500.0, 1.0);
+
+    assert!(e_list.is_empty());
+}
+
+#[test]
+fn test_exact_list_many_clips() {
+    let config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+
+    // more clips than fit in the subset sum, with lengths which are not whole frames
+    let clip_list: Vec<Media> = (0..2000)
+        .map(|i| {
+            let duration = 20.0 + ((i * 7919) % 6000) as f64 / 100.0;
+            let mut media = Media::new(i, &format!("clip_{i}.mp4"), false);
+            media.duration = duration;
+            media.out = duration;
+
+            media
+        })
+        .collect();
+
+    let e_list = exact_list(&config, clip_list, 86400.0, 0.5);
+    let fps = config.processing.fps;
+
+    // the output plays whole frames
+    let e_frames: f64 = e_list.iter().map(|c| (c.duration * fps).round()).sum();
+    let missing = 86400.0 - e_frames / fps;
+
+    assert!(
+        (0.0..=0.5).contains(&missing),
+        "{missing} seconds are missing"
+    );
+    assert!(e_list.windows(2).all(|c| c[0].source != c[1].source));
+}
+
 #[test]
 #[ignore]
 fn test_filler_list() {
@@ -1,10 +1,16 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::atomic::Ordering};
 
 #[cfg(test)]
 use chrono::prelude::*;
 
 #[cfg(test)]
-use ffplayout_lib::utils::*;
+use ffplayout_lib::filter;
+#[cfg(test)]
+use ffplayout_lib::utils::{
+    folder::best_fit_filler,
+    json_serializer::{find_playlist, playlist_first_day},
+    *,
+};
 
 #[test]
 fn mock_date_time() {
@@ -24,7 +30,7 @@ fn mock_date_time() {
 fn get_date_yesterday() {
     mock_time::set_mock_time("2022-05-20T05:59:24");
 
-    let date = get_date(true, 21600.0, false);
+    let date = get_date(None, true, 21600.0, false);
 
     assert_eq!("2022-05-19".to_string(), date);
 }
@@ -33,7 +39,7 @@ fn get_date_yesterday() {
 fn get_date_tomorrow() {
     mock_time::set_mock_time("2022-05-20T23:59:58");
 
-    let date = get_date(false, 0.0, true);
+    let date = get_date(None, false, 0.0, true);
 
     assert_eq!("2022-05-21".to_string(), date);
 }
@@ -52,3 +58,522 @@ fn test_delta() {
 
     assert!(delta < 2.0);
 }
+
+#[test]
+fn test_dst_day_length() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.playlist.timezone = Some(Tz::Europe__Berlin);
+    config.playlist.start_sec = Some(21600.0);
+
+    let date = |d| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+
+    assert_eq!(day_length(&config, date("2024-03-30")), 82800.0);
+    assert_eq!(day_length(&config, date("2024-05-20")), 86400.0);
+    assert_eq!(day_length(&config, date("2024-10-26")), 90000.0);
+    assert_eq!(
+        day_start(&config, date("2024-07-01")).to_rfc3339(),
+        "2024-07-01T04:00:00+00:00"
+    );
+
+    // 02:30 not exists on the day, when the clock jumps forward
+    config.playlist.start_sec = Some(9000.0);
+
+    assert_eq!(
+        day_start(&config, date("2024-03-31")).to_rfc3339(),
+        "2024-03-31T01:30:00+00:00"
+    );
+}
+
+#[test]
+fn test_multi_day_playlist() {
+    let root = std::env::temp_dir().join("ffplayout_multi_day");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("2023/02")).unwrap();
+    std::fs::copy(
+        "assets/playlists/2023/02/2023-02-08.json",
+        root.join("2023/02/2023-02-08.json"),
+    )
+    .unwrap();
+
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.playlist.path = root.clone();
+    config.playlist.length_sec = Some(172800.0);
+
+    let date = |d| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+    let file = root.join("2023/02/2023-02-08.json");
+    let playlist = json_reader(&file).unwrap();
+
+    assert_eq!(playlist_days(&config), 2);
+
+    // second day plays the playlist from the day before
+    assert_eq!(
+        find_playlist(&config, date("2023-02-09")),
+        file.display().to_string()
+    );
+    assert_eq!(
+        playlist_first_day(&config, &playlist, date("2023-02-09")),
+        date("2023-02-08")
+    );
+    assert!(!find_playlist(&config, date("2023-02-10")).ends_with("2023-02-08.json"));
+
+    // single file loops over its days
+    config.playlist.path = file;
+    config.playlist.length_sec = Some(604800.0);
+
+    assert_eq!(
+        playlist_first_day(&config, &playlist, date("2023-02-17")),
+        date("2023-02-15")
+    );
+    assert_eq!(
+        playlist_first_day(&config, &playlist, date("2023-02-07")),
+        date("2023-02-01")
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_date_range() {
+    let range = try_date_range(&[
+        "2024-02-27".to_string(),
+        "-".to_string(),
+        "2024-03-02".to_string(),
+    ])
+    .unwrap();
+
+    assert_eq!(range.len(), 5);
+    assert_eq!(range[2], "2024-02-29");
+
+    assert!(try_date_range(&["2024-02-30".to_string()]).is_err());
+}
+
+#[test]
+fn test_bulk_playlists() {
+    let root = std::env::temp_dir().join("ffplayout_bulk_playlists");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("2023/02")).unwrap();
+    std::fs::copy(
+        "assets/playlists/2023/02/2023-02-08.json",
+        root.join("2023/02/2023-02-08.json"),
+    )
+    .unwrap();
+
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.playlist.path = root.clone();
+
+    let march = try_date_range(&[
+        "2023-03-01".to_string(),
+        "-".to_string(),
+        "2023-03-31".to_string(),
+    ])
+    .unwrap();
+    let copy = BulkOperation::Copy {
+        source: "2023-02-08".to_string(),
+        weekly: true,
+    };
+
+    // dry run reports every wednesday, but writes nothing
+    let results = bulk_playlists(&config, &copy, &march, ExistsPolicy::Skip, true).unwrap();
+
+    assert_eq!(results.len(), 5);
+    assert!(!root.join("2023/03/2023-03-01.json").is_file());
+
+    bulk_playlists(&config, &copy, &march, ExistsPolicy::Skip, false).unwrap();
+    let playlist = json_reader(&root.join("2023/03/2023-03-15.json")).unwrap();
+
+    assert_eq!(playlist.date, "2023-03-15");
+
+    let results = bulk_playlists(&config, &copy, &march, ExistsPolicy::Skip, false).unwrap();
+
+    assert!(results.iter().all(|r| r.action == BulkAction::Skip));
+
+    // moving forward by one week must not overwrite following playlists
+    let shift = BulkOperation::Shift { days: 7 };
+    let results = bulk_playlists(&config, &shift, &march, ExistsPolicy::Skip, false).unwrap();
+
+    assert_eq!(
+        results
+            .iter()
+            .filter(|r| r.action == BulkAction::Write)
+            .count(),
+        5
+    );
+    assert!(!root.join("2023/03/2023-03-01.json").is_file());
+    assert!(root.join("2023/04/2023-04-05.json").is_file());
+
+    let shift = BulkOperation::Shift { days: i64::MAX };
+
+    assert!(bulk_playlists(&config, &shift, &march, ExistsPolicy::Skip, true).is_err());
+
+    let results = bulk_playlists(
+        &config,
+        &BulkOperation::Delete,
+        &march,
+        ExistsPolicy::Skip,
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(
+        results
+            .iter()
+            .filter(|r| r.action == BulkAction::Delete)
+            .count(),
+        4
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_config_from_yaml() {
+    let content = std::fs::read_to_string("../assets/ffplayout.yml").unwrap();
+    let config = PlayoutConfig::from_yaml(&content, "http://127.0.0.1:8787").unwrap();
+
+    assert_eq!(config.general.config_path, "http://127.0.0.1:8787");
+    assert_eq!(
+        config.playlist.start_sec,
+        Some(time_to_sec(&config.playlist.day_start))
+    );
+    assert!(config.processing.cmd.is_some());
+
+    assert!(PlayoutConfig::from_yaml("general: [", "").is_err());
+}
+
+#[test]
+fn test_check_config() {
+    let content = std::fs::read_to_string("../assets/ffplayout.yml").unwrap();
+
+    let check = check_config("general: [", false);
+    assert_eq!(check.errors[0].kind, IssueKind::Syntax);
+
+    let invalid = content.replace("day_start: \"05:59:25\"", "day_start: \"25:00\"");
+    let check = check_config(&invalid, false);
+    assert!(check
+        .errors
+        .iter()
+        .any(|e| e.kind == IssueKind::Value && e.field == "playlist.day_start"));
+    assert!(check.decoder_cmd.is_empty());
+
+    let invalid = content.replace("length: \"24:00:00\"", "length: \"36:00:00\"");
+    let check = check_config(&invalid, false);
+    assert!(check
+        .errors
+        .iter()
+        .any(|e| e.kind == IssueKind::Value && e.field == "playlist.length"));
+
+    let invalid = content.replace(
+        "    regex: ",
+        "    layers:\n      - name: dyntext\n      - name: lower third\n    regex: ",
+    );
+    let check = check_config(&invalid, false);
+    assert_eq!(
+        check
+            .errors
+            .iter()
+            .filter(|e| e.kind == IssueKind::Value && e.field == "text.layers")
+            .count(),
+        2
+    );
+
+    let check = check_config(&content, false);
+    assert!(check.errors.iter().all(|e| e.kind != IssueKind::Value));
+    assert!(check.decoder_cmd.contains(&"lavfi".to_string()));
+    assert!(check.encoder_cmd.is_empty());
+}
+
+#[test]
+fn test_config_reload_changes() {
+    let content = std::fs::read_to_string("../assets/ffplayout.yml").unwrap();
+    let mut config = PlayoutConfig::from_yaml(&content, "").unwrap();
+    let mut new_config = config.clone();
+
+    new_config.processing.volume = 0.5;
+    new_config.storage.path = PathBuf::from("/tmp/media");
+    new_config.out.output_param = "-f null -".to_string();
+
+    let changes = config.changes(&new_config);
+
+    assert_eq!(changes.applied, vec!["processing.volume", "storage.path"]);
+    assert_eq!(changes.restart, vec!["out.output_param"]);
+
+    config.apply_reload(&new_config);
+
+    assert_eq!(config.processing.volume, 0.5);
+    assert_eq!(config.storage.path, PathBuf::from("/tmp/media"));
+    assert_ne!(config.out.output_param, "-f null -");
+    assert!(config.changes(&config.clone()).applied.is_empty());
+}
+
+#[test]
+fn test_channel_thread_fail() {
+    let proc_control = ProcessControl::new();
+    proc_control.exit_on_error.store(false, Ordering::SeqCst);
+
+    let ctl = proc_control.clone();
+    let name = std::thread::Builder::new()
+        .name("channel-1".to_string())
+        .spawn(move || {
+            spawn_thread(move || {
+                ctl.fail();
+
+                std::thread::current().name().map(|n| n.to_string())
+            })
+            .join()
+            .unwrap()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    assert_eq!(name, Some("channel-1".to_string()));
+    assert!(proc_control.is_failed.load(Ordering::SeqCst));
+    assert!(proc_control.is_terminated.load(Ordering::SeqCst));
+
+    proc_control.reset();
+
+    assert!(!proc_control.is_failed.load(Ordering::SeqCst));
+    assert!(!proc_control.is_terminated.load(Ordering::SeqCst));
+}
+
+#[test]
+fn test_best_fit_filler() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.processing.fps = 25.0;
+
+    let fillers: Vec<Media> = [30.0, 20.0, 15.0, 12.0, 10.0]
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let mut media = Media::new(i, &format!("filler_{i}.mp4"), false);
+            media.duration = *d;
+            media.out = *d;
+
+            media
+        })
+        .collect();
+
+    // 37 seconds can only be: 15 + 12 + 10
+    let fill = best_fit_filler(&config, &fillers, 37.0, &[]);
+    let mut sources: Vec<&str> = fill.iter().map(|m| m.source.as_str()).collect();
+    sources.sort();
+
+    assert_eq!(
+        sources,
+        vec!["filler_2.mp4", "filler_3.mp4", "filler_4.mp4"]
+    );
+    assert!(fill.iter().all(|m| m.out == m.duration));
+
+    // Recently used clips are avoided, when there is another fit.
+    let fill = best_fit_filler(&config, &fillers, 30.0, &["filler_0.mp4".to_string()]);
+    let length: f64 = fill.iter().map(|m| m.out).sum();
+
+    assert!(!fill.iter().any(|m| m.source == "filler_0.mp4"));
+    assert_eq!(length, 30.0);
+
+    // Within one frame is a fit.
+    let fill = best_fit_filler(&config, &fillers, 22.03, &[]);
+
+    assert!(fill.iter().all(|m| m.out == m.duration));
+    assert_eq!(fill.iter().map(|m| m.out).sum::<f64>(), 22.0);
+
+    // No fit, cut the longest clip from the next bigger combination.
+    let fill = best_fit_filler(&config, &fillers, 11.0, &[]);
+    let length: f64 = fill.iter().map(|m| m.out).sum();
+
+    assert!((length - 11.0).abs() < 0.001);
+    assert_eq!(fill.iter().filter(|m| m.out < m.duration).count(), 1);
+
+    // Longer then all fillers together: full rounds plus best fit.
+    let fill = best_fit_filler(&config, &fillers, 209.0, &[]);
+    let length: f64 = fill.iter().map(|m| m.out).sum();
+
+    assert_eq!(length, 209.0);
+    assert!(fill.iter().all(|m| m.out == m.duration));
+}
+
+#[test]
+fn test_secondary_events() {
+    let node: Media = serde_json::from_str(
+        r#"{"in":0,"out":60,"duration":60,"source":"clip.mp4","secondary_events":[
+            {"offset":"00:00:20","duration":5.5,"text":{"text":"Next"}},
+            {"offset":30,"duration":"00:00:10","command":"overlay@bug enable 1","end_command":"overlay@bug enable 0"}]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(node.secondary_events.len(), 2);
+    assert_eq!(node.secondary_events[0].offset, 20.0);
+    assert_eq!(node.secondary_events[0].duration, 5.5);
+    assert_eq!(node.secondary_events[1].duration, 10.0);
+    assert_eq!(node.secondary_events[1].command, "overlay@bug enable 1");
+
+    let invalid = serde_json::from_str::<Media>(
+        r#"{"in":0,"out":60,"duration":60,"source":"clip.mp4","secondary_events":[{"offset":"20s","duration":5}]}"#,
+    );
+
+    assert!(invalid.is_err());
+
+    let plain = Media::new(0, "clip.mp4", false);
+
+    assert!(!serde_json::to_string(&plain)
+        .unwrap()
+        .contains("secondary_events"));
+}
+
+#[test]
+fn test_media_title() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.text.regex = r"^.+[/\\](.*)(.mp4|.mkv)$".to_string();
+
+    let mut media = Media::new(0, "/media/news/evening_news.mp4", false);
+    assert_eq!(media.get_title(&config), "evening_news");
+
+    let format: ffprobe::Format = serde_json::from_value(serde_json::json!({
+        "filename": "/media/news/evening_news.mp4",
+        "nb_streams": 2,
+        "nb_programs": 0,
+        "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+        "format_long_name": "QuickTime / MOV",
+        "probe_score": 100,
+        "tags": {"TITLE": "Evening News", "artist": "Newsroom", "comment": "Daily news"}
+    }))
+    .unwrap();
+
+    media.probe = Some(MediaProbe {
+        format,
+        audio_streams: vec![],
+        video_streams: vec![],
+        ..Default::default()
+    });
+
+    // tags are only used, when they are turned on
+    assert_eq!(media.get_title(&config), "evening_news");
+    assert_eq!(media.get_description(&config), "");
+
+    config.text.title_from_tags = true;
+    assert_eq!(media.get_title(&config), "Newsroom - Evening News");
+    assert_eq!(media.get_description(&config), "Daily news");
+
+    media.title = "News at Six".to_string();
+    assert_eq!(media.get_title(&config), "News at Six");
+}
+
+#[test]
+fn test_loudness_gain() {
+    let mut config = PlayoutConfig::new(Some(PathBuf::from("../assets/ffplayout.yml")));
+    config.processing.normalize_loudness = true;
+    config.processing.loudness_cache = std::env::temp_dir().join("ffp_loudness_test.json");
+
+    // gain is limited by the true peak
+    let loud = Loudness {
+        integrated: -16.0,
+        true_peak: -0.5,
+    };
+    let quiet = Loudness {
+        integrated: -30.0,
+        true_peak: -5.0,
+    };
+
+    assert_eq!(loud.gain(-23.0, -1.0), -7.0);
+    assert_eq!(quiet.gain(-23.0, -1.0), 4.0);
+
+    let source = "./assets/media_mix/with_audio.mp4";
+    let meta = std::fs::metadata(source).unwrap();
+    let modified = meta
+        .modified()
+        .unwrap()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    std::fs::write(
+        &config.processing.loudness_cache,
+        serde_json::json!({
+            source: {"size": meta.len(), "modified": modified, "integrated": -26.0, "true_peak": -6.0},
+            format!("{source}#a:1"): {"size": meta.len(), "modified": modified, "integrated": -20.0, "true_peak": -4.0}
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        loudness::cached(&config, source, 0),
+        Some(Loudness {
+            integrated: -26.0,
+            true_peak: -6.0
+        })
+    );
+
+    let mut media = Media::new(0, source, false);
+    media.duration = 30.0;
+    media.out = 30.0;
+    media.add_filter(&config, &None);
+
+    assert!(media
+        .filter
+        .as_ref()
+        .unwrap()
+        .audio_chain
+        .contains("anull,volume=3dB"));
+
+    // the gain comes from the audio stream, which is played
+    let stream = |language: &str| ffprobe::Stream {
+        codec_type: Some("audio".to_string()),
+        tags: Some(ffprobe::StreamTags {
+            language: Some(language.to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    media.probe = Some(MediaProbe {
+        audio_streams: vec![stream("deu"), stream("eng")],
+        ..Default::default()
+    });
+    media.audio_options.language = vec!["eng".to_string()];
+    media.add_filter(&config, &None);
+
+    assert_eq!(filter::audio_streams(&media, &config), vec![1]);
+    assert!(media
+        .filter
+        .unwrap()
+        .audio_chain
+        .starts_with("[0:a:1]anull,volume=-3dB"));
+}
+
+#[test]
+fn probe_cache_until_file_changes() {
+    let source = std::env::temp_dir().join("ffp_probe_cache_test.mp4");
+    let input = source.to_string_lossy().to_string();
+    let probes = std::sync::atomic::AtomicUsize::new(0);
+    let probe = |_: &str| {
+        probes.fetch_add(1, Ordering::SeqCst);
+
+        let mut probe = MediaProbe::default();
+        probe.format.duration = Some(probes.load(Ordering::SeqCst).to_string());
+
+        Ok(probe)
+    };
+
+    std::fs::write(&source, "first").unwrap();
+
+    let first = MediaProbe::cached(&input, probe).unwrap();
+    let second = MediaProbe::cached(&input, probe).unwrap();
+
+    assert_eq!(probes.load(Ordering::SeqCst), 1);
+    assert_eq!(second, first);
+
+    std::fs::write(&source, "changed file").unwrap();
+
+    let changed = MediaProbe::cached(&input, probe).unwrap();
+
+    assert_eq!(probes.load(Ordering::SeqCst), 2);
+    assert_eq!(changed.format.duration, Some("2".to_string()));
+
+    // remote sources are always probed again
+    MediaProbe::cached("https://example.org/stream.m3u8", probe).unwrap();
+    MediaProbe::cached("https://example.org/stream.m3u8", probe).unwrap();
+
+    assert_eq!(probes.load(Ordering::SeqCst), 4);
+
+    std::fs::remove_file(source).unwrap();
+}